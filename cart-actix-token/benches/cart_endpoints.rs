@@ -0,0 +1,287 @@
+//Load test of the cart endpoints, reports the throughput and latency of every route it drives.
+//
+//    cargo bench --bench cart_endpoints
+//
+//starts the server binary with the current environment (STORE, MONGOURI, ...) and runs BENCH_CLIENTS
+//clients (default 32) for BENCH_SECONDS (default 10). Every client signs up once and then loops
+//add, list, update, get and delete on a line of its own. BENCH_URL drives a server that is already
+//running instead, e.g. one built from an older commit to compare it with this one.
+//
+//The MongoDB store only sells products it keeps stock for, so an admin stocks the bench products
+//first. The spawned server makes its own admin, a server at BENCH_URL needs BENCH_ADMIN_EMAIL and
+//BENCH_ADMIN_PASSWORD of an account listed in its ADMIN_EMAILS. Any answer other than 200 stops
+//the bench, so a broken setup never ends up timing error responses.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use reqwest::{header, Client, Method};
+use serde_json::{json, Value};
+
+const DEFAULT_URL: &str = "http://127.0.0.1:8060";
+
+//routes in the order a client calls them
+const ROUTES: [&str; 5] = ["add", "list", "update", "get", "delete"];
+
+fn env_number(name: &str, default: u64) -> u64 {
+    env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+//the spawned server is stopped with the bench, also when it panics
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+async fn wait_until_up(client: &Client, url: &str, server: &mut Option<Server>) {
+    let deadline = Instant::now() + Duration::from_secs(30);
+
+    while Instant::now() < deadline {
+        if let Some(Server(child)) = server {
+            if let Ok(Some(status)) = child.try_wait() {
+                panic!("The server exited before it was up: {}", status);
+            }
+        }
+
+        //any answer will do, the route wants a token
+        if client.get(format!("{}/all-cart", url)).send().await.is_ok() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+
+    panic!("The server at {} did not come up within 30 seconds", url);
+}
+
+//sends a JSON request, returns the status and the body
+async fn call(client: &Client, method: Method, url: String, token: Option<&str>, body: Option<Value>) -> Result<(u16, Value), String> {
+    let mut request = client.request(method, url);
+
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, token);
+    }
+    if let Some(body) = body {
+        request = request.header(header::CONTENT_TYPE, "application/json").body(body.to_string());
+    }
+
+    let response = request.send().await.map_err(|error| error.to_string())?;
+    let status = response.status().as_u16();
+    let bytes = response.bytes().await.map_err(|error| error.to_string())?;
+
+    Ok((status, serde_json::from_slice(&bytes).unwrap_or(Value::Null)))
+}
+
+async fn sign_up(client: &Client, url: &str, email: &str, password: &str, existing: bool) -> String {
+    let user = json!({"name": "Bench", "email": email, "password": password});
+
+    let (status, body) = call(client, Method::POST, format!("{}/user-create", url), None, Some(user)).await.unwrap();
+    //an admin of a running server may have signed up before
+    assert!(status == 200 || (existing && status == 409), "Signing up {} failed: {} {}", email, status, body);
+
+    let login = json!({"email": email, "password": password});
+    let (status, body) = call(client, Method::POST, format!("{}/user-login", url), None, Some(login)).await.unwrap();
+    assert_eq!(status, 200, "Logging in {} failed: {}", email, body);
+
+    format!("Bearer {}", body["token"].as_str().unwrap())
+}
+
+//the product every client puts in its cart
+fn product(client: usize) -> String {
+    format!("bench-item-{}", client)
+}
+
+//stocks the bench products, false when the server keeps no stock
+async fn stock_products(client: &Client, url: &str, token: &str, clients: usize) -> bool {
+    for n in 0..clients {
+        let stock = json!({"stock": 1_000_000.0, "price": 2.5});
+        let (status, body) = call(client, Method::PUT, format!("{}/inventory/{}", url, product(n)), Some(token), Some(stock)).await.unwrap();
+
+        //the memory and SQL stores mount no inventory routes
+        if status == 404 && n == 0 {
+            return false;
+        }
+        assert_eq!(status, 200, "Stocking {} failed: {}", product(n), body);
+    }
+
+    true
+}
+
+//the body of a successful call, anything else stops the bench
+fn expect_ok(route: &str, answer: Result<(u16, Value), String>) -> Option<Value> {
+    match answer {
+        Ok((200, body)) => Some(body),
+        Ok((status, body)) => panic!("The {} route answered {}: {}", route, status, body),
+        //the server did not answer in time, counted as an error
+        Err(_) => None,
+    }
+}
+
+//latencies of the successful calls and the failed count, per route
+#[derive(Default)]
+struct Samples {
+    latencies: BTreeMap<&'static str, Vec<Duration>>,
+    errors: BTreeMap<&'static str, u64>,
+}
+
+impl Samples {
+    fn record(&mut self, route: &'static str, started: Instant, ok: bool) {
+        if ok {
+            self.latencies.entry(route).or_default().push(started.elapsed());
+        } else {
+            *self.errors.entry(route).or_default() += 1;
+        }
+    }
+
+    fn merge(&mut self, other: Samples) {
+        for (route, latencies) in other.latencies {
+            self.latencies.entry(route).or_default().extend(latencies);
+        }
+        for (route, errors) in other.errors {
+            *self.errors.entry(route).or_default() += errors;
+        }
+    }
+}
+
+async fn run_client(client: Client, url: String, token: String, product: String, deadline: Instant) -> Samples {
+    let mut samples = Samples::default();
+
+    while Instant::now() < deadline {
+        let line = json!({"product_name": product, "price": 2.5, "qty": 1.0});
+        let started = Instant::now();
+        let added = expect_ok("add", call(&client, Method::POST, format!("{}/cart-create", url), Some(&token), Some(line)).await);
+
+        let id = added.map(|body| body["result"]["_id"]["$oid"].as_str().expect("The added line has no id").to_owned());
+        samples.record("add", started, id.is_some());
+
+        let id = match id {
+            Some(id) => id,
+            None => continue,
+        };
+
+        let started = Instant::now();
+        let listed = expect_ok("list", call(&client, Method::GET, format!("{}/all-cart?limit=20", url), Some(&token), None).await);
+        samples.record("list", started, listed.is_some());
+
+        let started = Instant::now();
+        let updated = expect_ok("update", call(&client, Method::PUT, format!("{}/update-cart/{}", url, id), Some(&token), Some(json!({"qty": 2.0}))).await);
+        samples.record("update", started, updated.is_some());
+
+        let started = Instant::now();
+        let found = expect_ok("get", call(&client, Method::GET, format!("{}/get-cart/{}", url, id), Some(&token), None).await);
+        samples.record("get", started, found.is_some());
+
+        let started = Instant::now();
+        let deleted = expect_ok("delete", call(&client, Method::DELETE, format!("{}/delete-cart/{}", url, id), Some(&token), None).await);
+        samples.record("delete", started, deleted.is_some());
+    }
+
+    samples
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2}", duration.as_secs_f64() * 1000.0)
+}
+
+fn report(mut samples: Samples, elapsed: Duration) {
+    println!();
+    println!("{:<8} {:>9} {:>7} {:>10} {:>9} {:>9} {:>9} {:>9}", "route", "requests", "errors", "req/s", "p50 ms", "p90 ms", "p99 ms", "max ms");
+
+    let mut total = 0;
+    for route in ROUTES {
+        let latencies = samples.latencies.entry(route).or_default();
+        latencies.sort();
+        total += latencies.len();
+
+        println!(
+            "{:<8} {:>9} {:>7} {:>10.1} {:>9} {:>9} {:>9} {:>9}",
+            route,
+            latencies.len(),
+            samples.errors.get(route).copied().unwrap_or(0),
+            latencies.len() as f64 / elapsed.as_secs_f64(),
+            millis(percentile(latencies, 0.50)),
+            millis(percentile(latencies, 0.90)),
+            millis(percentile(latencies, 0.99)),
+            millis(latencies.last().copied().unwrap_or_default()),
+        );
+    }
+
+    println!();
+    println!("{} successful requests in {:.1}s, {:.1} req/s", total, elapsed.as_secs_f64(), total as f64 / elapsed.as_secs_f64());
+}
+
+#[tokio::main]
+async fn main() {
+    let clients = env_number("BENCH_CLIENTS", 32).max(1);
+    let seconds = env_number("BENCH_SECONDS", 10).max(1);
+
+    let http = Client::builder()
+        .pool_max_idle_per_host(clients as usize)
+        .timeout(Duration::from_secs(30))
+        .build()
+        .unwrap();
+
+    //every run signs up fresh users, so runs against the same database do not clash
+    let run = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+
+    let (url, mut server, admin_email, admin_password) = match env::var("BENCH_URL") {
+        Ok(url) => {
+            let email = env::var("BENCH_ADMIN_EMAIL").expect("BENCH_ADMIN_EMAIL must be set with BENCH_URL");
+            let password = env::var("BENCH_ADMIN_PASSWORD").expect("BENCH_ADMIN_PASSWORD must be set with BENCH_URL");
+            (url.trim_end_matches('/').to_owned(), None, email, password)
+        },
+        Err(_) => {
+            let email = format!("bench-{}-admin@example.com", run);
+            let child = Command::new(env!("CARGO_BIN_EXE_cart-actix-token"))
+                .env("ADMIN_EMAILS", &email)
+                .env("PAYMENT_WEBHOOK_SECRET", env::var("PAYMENT_WEBHOOK_SECRET").unwrap_or_else(|_| format!("bench-{}", run)))
+                .stdout(Stdio::null())
+                .spawn()
+                .expect("Error starting the server");
+            (DEFAULT_URL.to_owned(), Some(Server(child)), email, "bench".to_owned())
+        },
+    };
+
+    wait_until_up(&http, &url, &mut server).await;
+
+    let admin = sign_up(&http, &url, &admin_email, &admin_password, true).await;
+    if !stock_products(&http, &url, &admin, clients as usize).await {
+        println!("The server keeps no stock, the products are not stocked");
+    }
+
+    let mut tokens = Vec::new();
+    for n in 0..clients {
+        tokens.push(sign_up(&http, &url, &format!("bench-{}-{}@example.com", run, n), "bench", false).await);
+    }
+
+    println!("Driving {} with {} clients for {}s", url, clients, seconds);
+
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(seconds);
+
+    let tasks: Vec<_> = tokens
+        .into_iter()
+        .enumerate()
+        .map(|(n, token)| tokio::spawn(run_client(http.clone(), url.clone(), token, product(n), deadline)))
+        .collect();
+
+    let mut samples = Samples::default();
+    for task in tasks {
+        samples.merge(task.await.unwrap());
+    }
+
+    report(samples, started.elapsed());
+}
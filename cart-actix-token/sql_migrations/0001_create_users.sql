@@ -0,0 +1,12 @@
+-- ids are ObjectId hex strings like in MongoDB, so tokens and API ids look the same on every backend.
+-- Times are Unix milliseconds, the one date type SQLite and PostgreSQL share through sqlx::Any
+CREATE TABLE users (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    email TEXT NOT NULL,
+    password TEXT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+-- two sign ups with the same email differing in case cannot both win
+CREATE UNIQUE INDEX users_email_key ON users (LOWER(email));
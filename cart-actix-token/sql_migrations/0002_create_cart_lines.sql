@@ -0,0 +1,24 @@
+-- owner is a user id or a guest cart id, variant and options hold JSON objects
+CREATE TABLE cart_lines (
+    id TEXT PRIMARY KEY,
+    owner TEXT NOT NULL,
+    product_name TEXT NOT NULL,
+    sku TEXT,
+    variant TEXT,
+    options TEXT,
+    price NUMERIC(12, 2) NOT NULL,
+    qty NUMERIC(12, 3) NOT NULL,
+    total NUMERIC(14, 2) NOT NULL,
+    created_at BIGINT NOT NULL,
+    -- guest lines only, the purge drops them once it passes
+    expires_at BIGINT,
+    updated_at BIGINT,
+    version BIGINT NOT NULL DEFAULT 1,
+    added_by TEXT,
+    -- removed lines can be restored for a while and are purged after the retention
+    deleted_at BIGINT
+);
+
+CREATE INDEX cart_lines_owner ON cart_lines (owner, created_at);
+CREATE INDEX cart_lines_deleted_at ON cart_lines (deleted_at);
+CREATE INDEX cart_lines_expires_at ON cart_lines (expires_at);
@@ -0,0 +1,13 @@
+-- headers hold a JSON object, status and body stay empty while the first request is running
+CREATE TABLE idempotency_keys (
+    idempotency_key TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    request TEXT NOT NULL,
+    status BIGINT,
+    headers TEXT NOT NULL,
+    body TEXT,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (idempotency_key, owner)
+);
+
+CREATE INDEX idempotency_keys_created_at ON idempotency_keys (created_at);
@@ -0,0 +1,90 @@
+            //// _____________ Admin Api____________  ////
+
+use actix_web::{ResponseError, HttpResponse, get, put, web::{self, Data, Json}, HttpRequest};
+use serde_json::json;
+
+use crate::{repository::mongodb_repo::MongoRepo, middleware::auth::{bearer_token, missing_token}, model::history_model::HistoryRetention};
+
+
+////----------------------  START - Admin routes ----------------------------- ////
+
+//handler to list the abandoned carts
+#[get("/admin/abandoned-carts")]
+pub async fn get_abandoned_carts(req: HttpRequest, db: Data<MongoRepo>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.abandoned_cart_report(&token).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to list the change history of the cart of a user
+#[get("/admin/cart-history/{user_id}")]
+pub async fn get_cart_history(req: HttpRequest, db: Data<MongoRepo>, user_id: web::Path<String>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.admin_cart_history(&token, &user_id.into_inner()).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to read how long cart history is kept
+#[get("/admin/history-retention")]
+pub async fn get_history_retention(req: HttpRequest, db: Data<MongoRepo>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.history_retention(&token).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to set how long cart history is kept, no days keeps it forever
+#[put("/admin/history-retention")]
+pub async fn set_history_retention(req: HttpRequest, db: Data<MongoRepo>, data: Json<HistoryRetention>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.set_history_retention(&token, data.days).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to list how the indexes differ from the declared ones, nothing is applied
+#[get("/admin/indexes")]
+pub async fn get_index_report(req: HttpRequest, db: Data<MongoRepo>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.index_report(&token).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+////----------------------  END - Admin routes ----------------------------- ////
+
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_abandoned_carts)
+    .service(get_cart_history)
+    .service(get_history_retention)
+    .service(set_history_retention)
+    .service(get_index_report);
+}
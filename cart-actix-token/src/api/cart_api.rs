@@ -2,12 +2,56 @@
 
 ////----------------------  START - Initial routes ----------------------------- ////
 
-use actix_web::{HttpResponse, Responder, get, web::{self, Data, Json}, post, HttpRequest, put, delete};
-use chrono::Utc;
+use actix_web::{ResponseError, HttpResponse, get, http::header, web::{self, Data, Json}, post, HttpRequest, put, delete};
 use mongodb::bson::oid::ObjectId;
 use serde_json::json;
 
-use crate::{repository::mongodb_repo::MongoRepo, model::{user_model::{User, LoginUserSchema}, cart_model::{Cart, UpdateCart}}};
+use crate::{error::AppError, repository::{mongodb_repo::MongoRepo, store::Store, guest_cart_repo::{guest_cart_cookie, issue_guest_token, GUEST_CART_COOKIE}}, middleware::{auth::{bearer_token, missing_token}, idempotency::idempotent}, model::{share_model::CartAccess, cart_model::{AcknowledgeNotices, Cart, CartBatch, CartDeleteFilter, CartQuery, CartWriteError, UpdateCart}, response_model::ErrorResponse}};
+
+//token of the caller, the bearer token of a user or else the signed cookie of a guest cart
+fn cart_token(req: &HttpRequest) -> Option<String> {
+    bearer_token(req).or_else(|| req.cookie(GUEST_CART_COOKIE).map(|cookie| cookie.value().to_owned()))
+}
+
+pub const SHARED_CART_HEADER: &str = "Shared-Cart";
+
+//cart the request works on, a shared cart when the Shared-Cart header names its owner
+async fn cart_access(req: &HttpRequest, db: &dyn Store, token: &str, write: bool) -> Result<CartAccess, HttpResponse> {
+    let shared = req.headers().get(SHARED_CART_HEADER).and_then(|value| value.to_str().ok());
+    let access = db.cart_access(token, shared).await.map_err(|error| error.error_response())?;
+
+    if write {
+        access.ensure_editor().map_err(|error| error.error_response())?;
+    }
+
+    Ok(access)
+}
+
+//ETag of a cart line, its version
+fn etag(cart: &Cart) -> (header::HeaderName, String) {
+    (header::ETAG, format!("\"{}\"", cart.version()))
+}
+
+//version expected by If-Match, a tag that is no version can never match
+fn if_match(req: &HttpRequest) -> Option<i64> {
+    let value = req.headers().get(header::IF_MATCH)?.to_str().unwrap_or_default().trim();
+
+    if value == "*" {
+        return None;
+    }
+
+    Some(value.trim_start_matches("W/").trim_matches('"').parse().unwrap_or(-1))
+}
+
+//response for a write that failed, a stale write returns 412 with the line as it is now
+fn write_failed(error: CartWriteError) -> HttpResponse {
+    match error {
+        CartWriteError::Stale(current) => HttpResponse::PreconditionFailed()
+            .insert_header(etag(&current))
+            .json(json!({"status" : "failed", "message" : "Cart item was changed by another request", "result" : current})),
+        CartWriteError::Failed(error) => error.error_response(),
+    }
+}
 
 
 ////----------------------  END - Initial routes ----------------------------- ////
@@ -17,99 +61,320 @@ use crate::{repository::mongodb_repo::MongoRepo, model::{user_model::{User, Logi
 
 //user register handler function
 #[post("/cart-create")]
-pub async fn create_cart(req: HttpRequest, db: Data<MongoRepo>, new_cart: Json<Cart>) -> HttpResponse {
+pub async fn create_cart(req: HttpRequest, db: Data<dyn Store>, new_cart: Json<Cart>) -> HttpResponse {
 
-    let auth = req.headers().get("Authorization");
-    let split: Vec<&str> = auth.unwrap().to_str().unwrap().split("Bearer").collect();
-    let token = split[1].trim();
+    //visitors without any token get a fresh guest cart
+    let (token, new_guest) = match cart_token(&req) {
+        Some(token) => (token, false),
+        None => (issue_guest_token(db.guest_cart_ttl()), true),
+    };
 
-    println!("token: {:?}",token);
+    let access = match cart_access(&req, db.get_ref(), &token, true).await {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
 
     let data = Cart {
         id: None,
         user_id: None,
         product_name: new_cart.product_name.to_owned(),
+        sku: new_cart.sku.to_owned(),
+        variant: None,
+        options: new_cart.options.to_owned(),
         price: new_cart.price,
         qty: new_cart.qty,
         total: None,
-        created_at: None
+        created_at: None,
+        expires_at: None,
+        updated_at: None,
+        abandoned_at: None,
+        version: None,
+        added_by: None,
+        deleted_at: None
     };
 
-    match db.create_cart(token, data).await {
-        Ok(list) => HttpResponse::Ok().json(json!({"status" : "success", "result" : list})),
-        Err(err) => HttpResponse::ExpectationFailed().json(json!({"status" : "failed", "message" : err})),
-    }
+    idempotent(&req, db.get_ref(), cart_token(&req).as_deref(), &*new_cart, || async {
+        match db.create_cart(&access, data).await {
+            Ok(list) if new_guest => HttpResponse::Ok()
+                .cookie(guest_cart_cookie(&token, db.guest_cart_ttl()))
+                .insert_header(etag(&list))
+                .json(json!({"status" : "success", "result" : list})),
+            Ok(list) => HttpResponse::Ok().insert_header(etag(&list)).json(json!({"status" : "success", "result" : list})),
+            Err(err) => err.error_response(),
+        }
+    }).await
 }
 
-//Find all carts by id
-#[get("/all-cart/{id}")]
-pub async fn get_all_carts(req: HttpRequest, db: Data<MongoRepo>) -> HttpResponse {
+//handler to list the cart one page at a time, filtered and sorted through the query string
+#[get("/all-cart")]
+pub async fn get_all_carts(req: HttpRequest, db: Data<dyn Store>, query: web::Query<CartQuery>) -> HttpResponse {
 
-    let auth = req.headers().get("Authorization");
-    let split: Vec<&str> = auth.unwrap().to_str().unwrap().split("Bearer").collect();
-    let token = split[1].trim();
+    let token = match cart_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
 
-    match db.list_all_carts_by_user(token).await {
-        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
-        Err(error) =>  HttpResponse::ExpectationFailed().json(json!({"status" : "failed", "message" : error})), 
+    let access = match cart_access(&req, db.get_ref(), &token, false).await {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+
+    match db.list_cart_page(&access, query.into_inner()).await {
+        Ok(page) => {
+            let notices = match db.revalidate_lines(&page.items).await {
+                Ok(notices) => notices,
+                Err(error) => return error.error_response(),
+            };
+            HttpResponse::Ok().json(json!({"status" : "success", "result" : page.items, "page" : page.page, "notices" : notices}))
+        },
+        Err(error) =>  error.error_response(), 
     }
 }
 
 //handler to update the todo
 #[put("/update-cart/{id}")]
-pub async fn update_cart(req: HttpRequest, data: Json<UpdateCart>, id: web::Path<String>, db: Data<MongoRepo>) -> HttpResponse {
+pub async fn update_cart(req: HttpRequest, data: Json<UpdateCart>, id: web::Path<String>, db: Data<dyn Store>) -> HttpResponse {
 
     let todo_id = id.into_inner();
 
-    let auth = req.headers().get("Authorization");
-    let split: Vec<&str> = auth.unwrap().to_str().unwrap().split("Bearer").collect();    
-    let token = split[1].trim();
+    let token = match cart_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
 
     let doc = UpdateCart {
-        qty: data.qty.clone(),
+        qty: data.qty,
     };
 
-    match db.update_cart(token, doc, todo_id).await {
-        Ok(result) => HttpResponse::Ok().json(json!({"result": result})),
-        Err(err) => HttpResponse::Ok().json(err),
-    }
+    let access = match cart_access(&req, db.get_ref(), &token, true).await {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+
+    idempotent(&req, db.get_ref(), Some(&token), &data.0, || async {
+        match db.update_cart(&access, doc, todo_id, if_match(&req)).await {
+            Ok(result) => HttpResponse::Ok().insert_header(etag(&result)).json(json!({"result": result})),
+            Err(error) => write_failed(error),
+        }
+    }).await
 }
 
 //handler to delete the todo
 #[delete("/delete-cart/{id}")]
-pub async fn delete_cart(req: HttpRequest ,db: Data<MongoRepo>, id: web::Path<String>) -> HttpResponse {
+pub async fn delete_cart(req: HttpRequest ,db: Data<dyn Store>, id: web::Path<String>) -> HttpResponse {
 
     let delete_id = id.into_inner();
-    let auth = req.headers().get("Authorization");
-    let split: Vec<&str> = auth.unwrap().to_str().unwrap().split("Bearer").collect();    
-    let token = split[1].trim();
+    let token = match cart_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
 
-    match db.delete_cart(token, delete_id).await {
-        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
-        Err(error) =>  HttpResponse::ExpectationFailed().json(json!({"status" : "failed", "message" : error})),
-    }
+    let access = match cart_access(&req, db.get_ref(), &token, true).await {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+
+    idempotent(&req, db.get_ref(), Some(&token), &(), || async {
+        match db.delete_cart(&access, delete_id, if_match(&req)).await {
+            Ok(deleted) => HttpResponse::Ok().json(json!({"status" : "success", "result" : {"deletedCount": deleted}})),
+            Err(error) => write_failed(error),
+        }
+    }).await
+}
+
+//handler to bring back an item deleted within the restore window
+#[post("/restore-cart/{id}")]
+pub async fn restore_cart(req: HttpRequest, db: Data<dyn Store>, id: web::Path<String>) -> HttpResponse {
+
+    let restore_id = id.into_inner();
+    let token = match cart_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    let access = match cart_access(&req, db.get_ref(), &token, true).await {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+
+    idempotent(&req, db.get_ref(), Some(&token), &(), || async {
+        match db.restore_cart(&access, restore_id).await {
+            Ok(result) => HttpResponse::Ok().insert_header(etag(&result)).json(json!({"status" : "success", "result" : result})),
+            Err(error) =>  error.error_response(),
+        }
+    }).await
 }
 
+//handler to empty the cart
+#[delete("/cart")]
+pub async fn clear_cart(req: HttpRequest, db: Data<dyn Store>) -> HttpResponse {
+    let token = match cart_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    let access = match cart_access(&req, db.get_ref(), &token, true).await {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+
+    idempotent(&req, db.get_ref(), Some(&token), &(), || async {
+        match db.clear_cart(&access).await {
+            Ok(deleted) => HttpResponse::Ok().json(json!({"status" : "success", "result" : {"deleted": deleted}})),
+            Err(error) =>  error.error_response(),
+        }
+    }).await
+}
+
+//handler to delete the cart items matching the query, e.g. ?product_name=..&created_before=..
+#[delete("/cart/items")]
+pub async fn delete_cart_items(req: HttpRequest, db: Data<dyn Store>, filter: web::Query<CartDeleteFilter>) -> HttpResponse {
+    let token = match cart_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    let access = match cart_access(&req, db.get_ref(), &token, true).await {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+
+    idempotent(&req, db.get_ref(), Some(&token), &(), || async {
+        match db.delete_cart_items(&access, filter.into_inner()).await {
+            Ok(deleted) => HttpResponse::Ok().json(json!({"status" : "success", "result" : {"deleted": deleted}})),
+            Err(error) =>  error.error_response(),
+        }
+    }).await
+}
 
 //handler to get todo list
 #[get("/get-cart/{id}")]
-pub async fn get_cart(req: HttpRequest, db: Data<MongoRepo>, id: web::Path<String>) -> HttpResponse {
+pub async fn get_cart(req: HttpRequest, db: Data<dyn Store>, id: web::Path<String>) -> HttpResponse {
     let get_id = id.into_inner();
-    let todo_id = ObjectId::parse_str(get_id).unwrap();
-    let auth = req.headers().get("Authorization");
-    let split: Vec<&str> = auth.unwrap().to_str().unwrap().split("Bearer").collect();    
-    let token = split[1].trim();
+    let todo_id = match ObjectId::parse_str(get_id) {
+        Ok(todo_id) => todo_id,
+        Err(error) => return AppError::from(error).error_response(),
+    };
+    let token = match cart_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
 
-    match db.finding_cart(token, &todo_id).await {
+    let access = match cart_access(&req, db.get_ref(), &token, false).await {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+
+    match db.finding_cart(&access, &todo_id).await {
+        Ok(Some(result)) => {
+            let notices = match db.revalidate_lines(std::slice::from_ref(&result)).await {
+                Ok(notices) => notices,
+                Err(error) => return error.error_response(),
+            };
+            HttpResponse::Ok().insert_header(etag(&result)).json(json!({"status" : "success", "result" : result, "notices" : notices}))
+        },
+        Ok(None) => HttpResponse::Ok().json(json!({"status" : "success", "result" : null})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to list the changes made to the cart, newest first
+#[get("/cart/history")]
+pub async fn get_cart_history(req: HttpRequest, db: Data<MongoRepo>) -> HttpResponse {
+    let token = match cart_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    let access = match cart_access(&req, db.get_ref(), &token, false).await {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+
+    match db.cart_history(&access).await {
         Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
-        Err(error) =>  HttpResponse::ExpectationFailed().json(json!({"status" : "failed", "message" : error})),
+        Err(error) =>  error.error_response(),
     }
 }
 
-pub fn config(cfg: &mut web::ServiceConfig) {
+//handler to apply several cart operations at once, all or nothing
+#[post("/cart/batch")]
+pub async fn cart_batch(req: HttpRequest, db: Data<MongoRepo>, batch: Json<CartBatch>) -> HttpResponse {
+    let token = match cart_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    let access = match cart_access(&req, db.get_ref(), &token, true).await {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+
+    idempotent(&req, db.get_ref(), Some(&token), &batch.clone(), || async {
+        match db.apply_cart_batch(&access, batch.into_inner()).await {
+            Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+            Err(error) =>  error.error_response(),
+        }
+    }).await
+}
+
+//handler to accept notices from revalidation, the cart takes over the changes they describe
+#[post("/cart/acknowledge")]
+pub async fn acknowledge_notices(req: HttpRequest, db: Data<MongoRepo>, data: Json<AcknowledgeNotices>) -> HttpResponse {
+    let token = match cart_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    let access = match cart_access(&req, db.get_ref(), &token, true).await {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+
+    idempotent(&req, db.get_ref(), Some(&token), &data.0, || async {
+        let notices = match db.acknowledge_notices(&access, data.notices.to_owned()).await {
+            Ok(notices) => notices,
+            Err(error) => return error.error_response(),
+        };
+
+        match db.list_all_carts_by_user(&access).await {
+            Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result, "notices" : notices})),
+            Err(error) =>  error.error_response(),
+        }
+    }).await
+}
+
+//answer of the MongoDB-only cart routes on the other stores
+async fn needs_mongo() -> HttpResponse {
+    HttpResponse::NotImplemented().json(ErrorResponse {
+        status: false,
+        message: "This route is only served by the MongoDB store".to_owned(),
+    })
+}
+
+//routes served by any Store, the in-memory one included
+pub fn store_config(cfg: &mut web::ServiceConfig) {
     cfg.service(create_cart)
     .service(get_all_carts)
     .service(update_cart)
     .service(delete_cart)
+    .service(restore_cart)
+    .service(clear_cart)
+    .service(delete_cart_items)
     .service(get_cart);
-}
\ No newline at end of file
+}
+
+//the batch, the history and the notices need the transactions, history and catalog of the MongoDB store.
+//STORE=memory and STORE=sql register these instead, so the routes say so rather than 404
+pub fn unsupported_config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/cart/batch", web::post().to(needs_mongo))
+    .route("/cart/acknowledge", web::post().to(needs_mongo))
+    .route("/cart/history", web::get().to(needs_mongo));
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    store_config(cfg);
+    cfg.service(cart_batch)
+    .service(acknowledge_notices)
+    .service(get_cart_history);
+}
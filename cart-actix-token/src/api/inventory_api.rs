@@ -0,0 +1,48 @@
+            //// _____________ Inventory Api____________  ////
+
+use actix_web::{ResponseError, HttpResponse, get, put, web::{self, Data, Json}, HttpRequest};
+use serde_json::json;
+
+use crate::{repository::mongodb_repo::MongoRepo, middleware::auth::{bearer_token, missing_token, AuthorizationService}, model::{inventory_model::UpdateInventory, response_model::ErrorResponse}};
+
+
+////----------------------  START - Inventory routes ----------------------------- ////
+
+//handler to get the stock of a SKU
+#[get("/inventory/{sku}")]
+pub async fn get_inventory(_auth: AuthorizationService, db: Data<MongoRepo>, sku: web::Path<String>) -> HttpResponse {
+    match db.find_inventory(&sku.into_inner()).await {
+        Ok(Some(result)) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            status: false,
+            message: "Product not found in inventory".to_owned(),
+        }),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to set the stock and price of a SKU, only for admins
+#[put("/inventory/{sku}")]
+pub async fn set_inventory(req: HttpRequest, db: Data<MongoRepo>, sku: web::Path<String>, data: Json<UpdateInventory>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    if let Err(error) = db.validate_admin(&token).await {
+        return error.error_response();
+    }
+
+    match db.set_inventory(&sku.into_inner(), data.product_name.as_deref(), data.stock, data.price).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+////----------------------  END - Inventory routes ----------------------------- ////
+
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_inventory)
+    .service(set_inventory);
+}
@@ -1,2 +1,14 @@
+//the route files mark their sections with //// banners, clippy would take those for broken doc comments
+#![allow(clippy::four_forward_slashes)]
+
+pub mod admin_api;
 pub mod cart_api;
-pub mod user_api;
\ No newline at end of file
+pub mod inventory_api;
+pub mod order_api;
+pub mod payment_api;
+pub mod product_api;
+pub mod share_api;
+pub mod user_api;
+pub mod wishlist_api;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,88 @@
+            //// _____________ Order Api____________  ////
+
+use actix_web::{ResponseError, HttpResponse, get, post, put, web::{self, Data, Json}, HttpRequest};
+use mongodb::bson::oid::ObjectId;
+use serde_json::json;
+
+use crate::{error::AppError, repository::mongodb_repo::MongoRepo, middleware::auth::{bearer_token, missing_token}, model::order_model::CheckoutSchema};
+
+
+////----------------------  START - Order routes ----------------------------- ////
+
+//handler to checkout the cart of the user
+#[post("/checkout")]
+pub async fn checkout(req: HttpRequest, db: Data<MongoRepo>, data: Json<CheckoutSchema>) -> HttpResponse {
+
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    //every change since the items were added has to be acknowledged first
+    match db.checkout(&token, data.into_inner()).await {
+        Ok(Ok(order)) => HttpResponse::Ok().json(json!({"status" : "success", "result" : order})),
+        Ok(Err(notices)) => HttpResponse::Conflict().json(json!({
+            "status" : "failed",
+            "message" : "Cart changed, acknowledge the notices before checkout",
+            "notices" : notices
+        })),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to list the orders of the user
+#[get("/all-orders")]
+pub async fn get_all_orders(req: HttpRequest, db: Data<MongoRepo>) -> HttpResponse {
+
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.list_orders_by_user(&token).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to get an order
+#[get("/get-order/{id}")]
+pub async fn get_order(req: HttpRequest, db: Data<MongoRepo>, id: web::Path<String>) -> HttpResponse {
+    let order_id = match ObjectId::parse_str(id.into_inner()) {
+        Ok(order_id) => order_id,
+        Err(error) => return AppError::from(error).error_response(),
+    };
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.finding_order(&token, &order_id).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to cancel a pending order
+#[put("/cancel-order/{id}")]
+pub async fn cancel_order(req: HttpRequest, db: Data<MongoRepo>, id: web::Path<String>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.cancel_order(&token, id.into_inner()).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+////----------------------  END - Order routes ----------------------------- ////
+
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(checkout)
+    .service(get_all_orders)
+    .service(get_order)
+    .service(cancel_order);
+}
@@ -0,0 +1,87 @@
+            //// _____________ Payment Api____________  ////
+
+use actix_web::{ResponseError, HttpResponse, post, web::{self, Data, Json}, HttpRequest};
+use serde_json::json;
+
+use crate::{repository::mongodb_repo::MongoRepo, middleware::auth::{bearer_token, missing_token}, payment::fake_provider::FakePaymentProvider, model::{payment_model::PayOrderSchema, response_model::ErrorResponse}};
+
+
+////----------------------  START - Payment routes ----------------------------- ////
+
+//handler to pay a pending order
+#[post("/pay-order/{id}")]
+pub async fn pay_order(req: HttpRequest, db: Data<MongoRepo>, id: web::Path<String>, data: Json<PayOrderSchema>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.pay_order(&token, id.into_inner(), data.into_inner()).await {
+        Ok((order, payment)) => HttpResponse::Ok().json(json!({"status" : "success", "result" : order, "payment" : payment})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to refund a paid order
+#[post("/refund-order/{id}")]
+pub async fn refund_order(req: HttpRequest, db: Data<MongoRepo>, id: web::Path<String>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.refund_order(&token, id.into_inner()).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler for payment provider webhooks
+#[post("/payment-webhook")]
+pub async fn payment_webhook(req: HttpRequest, db: Data<MongoRepo>, body: String) -> HttpResponse {
+    let signature = match req.headers().get("Payment-Signature").and_then(|v| v.to_str().ok()) {
+        Some(signature) => signature,
+        None => return HttpResponse::BadRequest().json(ErrorResponse {
+            status: false,
+            message: "Missing signature".to_owned(),
+        }),
+    };
+
+    match db.handle_payment_webhook(&body, signature).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to complete the simulated 3-D Secure challenge of the fake provider, for the owner of the order
+#[post("/fake-payments/{id}/confirm")]
+pub async fn confirm_fake_payment(req: HttpRequest, db: Data<MongoRepo>, payments: Data<FakePaymentProvider>, id: web::Path<String>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    let payment_id = id.into_inner();
+    if let Err(error) = db.check_payment_owner(&token, &payment_id).await {
+        return error.error_response();
+    }
+
+    match payments.confirm(&payment_id) {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+////----------------------  END - Payment routes ----------------------------- ////
+
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(pay_order)
+    .service(refund_order)
+    .service(payment_webhook);
+}
+
+//routes of the fake provider, only mounted when it is the configured one
+pub fn fake_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(confirm_fake_payment);
+}
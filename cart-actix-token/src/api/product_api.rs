@@ -0,0 +1,69 @@
+            //// _____________ Product Api____________  ////
+
+use actix_web::{ResponseError, HttpResponse, get, post, put, web::{self, Data, Json}, HttpRequest};
+use serde_json::json;
+
+use crate::{repository::mongodb_repo::MongoRepo, middleware::auth::{bearer_token, missing_token}, model::{product_model::{ProductSchema, ProductStatus}, response_model::ErrorResponse}};
+
+
+////----------------------  START - Product routes ----------------------------- ////
+
+//handler to create a product with its variants, admins only
+#[post("/product-create")]
+pub async fn create_product(req: HttpRequest, db: Data<MongoRepo>, data: Json<ProductSchema>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.create_product(&token, data.into_inner()).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to discontinue a product or bring it back, admins only
+#[put("/product-status/{id}")]
+pub async fn set_product_status(req: HttpRequest, db: Data<MongoRepo>, id: web::Path<String>, data: Json<ProductStatus>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.set_product_status(&token, id.into_inner(), data.active).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to list all products with their variants
+#[get("/all-products")]
+pub async fn get_all_products(db: Data<MongoRepo>) -> HttpResponse {
+    match db.list_products().await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to get a product with its variants
+#[get("/product/{id}")]
+pub async fn get_product(db: Data<MongoRepo>, id: web::Path<String>) -> HttpResponse {
+    match db.find_product(id.into_inner()).await {
+        Ok(Some(result)) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            status: false,
+            message: "Product Not found".to_owned(),
+        }),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+////----------------------  END - Product routes ----------------------------- ////
+
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_product)
+    .service(set_product_status)
+    .service(get_all_products)
+    .service(get_product);
+}
@@ -0,0 +1,92 @@
+            //// _____________ Share Api____________  ////
+
+use actix_web::{ResponseError, HttpResponse, get, post, delete, web::{self, Data, Json}, HttpRequest};
+use serde_json::json;
+
+use crate::{repository::mongodb_repo::MongoRepo, middleware::auth::{bearer_token, missing_token}, model::share_model::{InviteSchema, ShareLinkSchema}};
+
+
+////----------------------  START - Share routes ----------------------------- ////
+
+//handler to invite a user by email to the cart
+#[post("/cart/share/invite")]
+pub async fn invite_to_cart(req: HttpRequest, db: Data<MongoRepo>, data: Json<InviteSchema>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    let data = data.into_inner();
+
+    match db.invite_to_cart(&token, data.email, data.role).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to create a signed share link for the cart
+#[post("/cart/share/link")]
+pub async fn create_share_link(req: HttpRequest, db: Data<MongoRepo>, data: Json<ShareLinkSchema>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.create_share_link(&token, data.role).await {
+        Ok(link) => HttpResponse::Ok().json(json!({"status" : "success", "result" : {"token": link, "link": format!("/cart/share/join/{}", link)}})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to join a cart through a share link
+#[post("/cart/share/join/{link}")]
+pub async fn join_shared_cart(req: HttpRequest, db: Data<MongoRepo>, link: web::Path<String>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.join_shared_cart(&token, &link.into_inner()).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to list the members of the cart and the carts shared with the user
+#[get("/cart/shares")]
+pub async fn get_cart_shares(req: HttpRequest, db: Data<MongoRepo>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.list_cart_shares(&token).await {
+        Ok((members, shared_with_me)) => HttpResponse::Ok().json(json!({"status" : "success", "result" : {"members": members, "shared_with_me": shared_with_me}})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to remove a member from the cart or leave a shared cart
+#[delete("/cart/share/{id}")]
+pub async fn remove_cart_share(req: HttpRequest, db: Data<MongoRepo>, id: web::Path<String>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.remove_cart_share(&token, id.into_inner()).await {
+        Ok(deleted) => HttpResponse::Ok().json(json!({"status" : "success", "result" : {"deleted": deleted}})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+////----------------------  END - Share routes ----------------------------- ////
+
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(invite_to_cart)
+    .service(create_share_link)
+    .service(join_shared_cart)
+    .service(get_cart_shares)
+    .service(remove_cart_share);
+}
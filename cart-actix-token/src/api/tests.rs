@@ -0,0 +1,845 @@
+//HTTP tests of the user and cart routes. Every store runs the same tests, see conformance_suite at the end
+
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{http::{header, StatusCode}, test, web::Data, App};
+use mongodb::bson::oid::ObjectId;
+use serde_json::{json, Value};
+use sqlx::Connection;
+
+use crate::payment::fake_provider::FakePaymentProvider;
+use crate::repository::{memory_store::MemoryStore, mongo_config::MongoConfig, mongodb_repo::MongoRepo, sql_store::SqlStore, store::Store};
+
+use super::{cart_api, inventory_api, share_api, user_api};
+
+async fn memory_store() -> Data<dyn Store> {
+    Data::from(Arc::new(MemoryStore::default()) as Arc<dyn Store>)
+}
+
+async fn sqlite_store() -> Data<dyn Store> {
+    let store = SqlStore::connect("sqlite::memory:").await.unwrap();
+    Data::from(Arc::new(store) as Arc<dyn Store>)
+}
+
+//needs TEST_POSTGRES_URL, the postgres tests are ignored unless run with --ignored. Every test gets a schema of its own on that database,
+//so the tests run side by side; the schemas are left behind for a look after a failure
+async fn postgres_store() -> Data<dyn Store> {
+    let url = std::env::var("TEST_POSTGRES_URL").expect("TEST_POSTGRES_URL is not set");
+    let schema = format!("cart_test_{}", ObjectId::new().to_hex());
+
+    sqlx::any::install_default_drivers();
+    let mut conn = sqlx::AnyConnection::connect(&url).await.unwrap();
+    sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&mut conn).await.unwrap();
+
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let store = SqlStore::connect(&format!("{}{}options[search_path]={}", url, separator, schema)).await.unwrap();
+    Data::from(Arc::new(store) as Arc<dyn Store>)
+}
+
+macro_rules! app {
+    ($store:expr) => {
+        test::init_service(
+            App::new()
+                .app_data($store.clone())
+                .configure(user_api::config)
+                .configure(cart_api::store_config),
+        )
+        .await
+    };
+}
+
+//registers a user and logs in, returns the bearer token
+macro_rules! login {
+    ($app:expr, $email:expr) => {{
+        let req = test::TestRequest::post()
+            .uri("/user-create")
+            .set_json(json!({"name": "Jane", "email": $email, "password": "pwd"}))
+            .to_request();
+        let resp = test::call_service(&$app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::post()
+            .uri("/user-login")
+            .set_json(json!({"email": $email, "password": "pwd"}))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&$app, req).await;
+        format!("Bearer {}", body["token"].as_str().unwrap())
+    }};
+}
+
+macro_rules! add_line {
+    ($app:expr, $token:expr, $name:expr, $price:expr, $qty:expr) => {{
+        let req = test::TestRequest::post()
+            .uri("/cart-create")
+            .insert_header((header::AUTHORIZATION, $token.as_str()))
+            .set_json(json!({"product_name": $name, "price": $price, "qty": $qty}))
+            .to_request();
+        let mut body: Value = test::call_and_read_body_json(&$app, req).await;
+        body["result"].take()
+    }};
+}
+
+async fn register_rejects_duplicate_email(store: Data<dyn Store>) {
+    let app = app!(store);
+
+    let _ = login!(app, "jane@example.com");
+
+    let req = test::TestRequest::post()
+        .uri("/user-create")
+        .set_json(json!({"name": "Jane", "email": "jane@example.com", "password": "pwd"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+}
+
+async fn login_rejects_wrong_password(store: Data<dyn Store>) {
+    let app = app!(store);
+
+    let _ = login!(app, "jane@example.com");
+
+    let req = test::TestRequest::post()
+        .uri("/user-login")
+        .set_json(json!({"email": "jane@example.com", "password": "nope"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+async fn login_checks_the_password_case(store: Data<dyn Store>) {
+    let app = app!(store);
+
+    let _ = login!(app, "jane@example.com");
+
+    //the email is matched regardless of case, the password is not
+    let req = test::TestRequest::post()
+        .uri("/user-login")
+        .set_json(json!({"email": "Jane@Example.com", "password": "PWD"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri("/user-login")
+        .set_json(json!({"email": "Jane@Example.com", "password": "pwd"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+async fn cart_lines_are_created_merged_and_listed(store: Data<dyn Store>) {
+    let app = app!(store);
+    let token = login!(app, "jane@example.com");
+
+    let first = add_line!(app, token, "Apple", 2.0, 1.0);
+    let merged = add_line!(app, token, "Apple", 2.0, 2.0);
+    add_line!(app, token, "Pear", 3.0, 1.0);
+
+    assert_eq!(first["_id"], merged["_id"]);
+    assert_eq!(merged["qty"], 3.0);
+    assert_eq!(merged["_total"], 6.0);
+    assert_eq!(merged["version"], 2);
+
+    let req = test::TestRequest::get()
+        .uri("/all-cart?sort=price&order=desc")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    let names: Vec<&str> = body["result"].as_array().unwrap().iter().map(|line| line["product_name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["Pear", "Apple"]);
+    assert_eq!(body["page"]["has_more"], false);
+}
+
+async fn cart_listing_pages_through_the_cursor(store: Data<dyn Store>) {
+    let app = app!(store);
+    let token = login!(app, "jane@example.com");
+
+    for (name, price) in [("A", 1.0), ("B", 2.0), ("C", 3.0)] {
+        add_line!(app, token, name, price, 1.0);
+    }
+
+    let mut uri = "/all-cart?sort=price&limit=2".to_owned();
+    let mut names = Vec::new();
+
+    loop {
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header((header::AUTHORIZATION, token.as_str()))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+
+        for line in body["result"].as_array().unwrap() {
+            names.push(line["product_name"].as_str().unwrap().to_owned());
+        }
+
+        match body["page"]["next_cursor"].as_str() {
+            Some(cursor) => uri = format!("/all-cart?sort=price&limit=2&cursor={}", cursor),
+            None => break,
+        }
+    }
+
+    assert_eq!(names, ["A", "B", "C"]);
+}
+
+async fn update_checks_if_match(store: Data<dyn Store>) {
+    let app = app!(store);
+    let token = login!(app, "jane@example.com");
+
+    let line = add_line!(app, token, "Apple", 2.0, 1.0);
+    let uri = format!("/update-cart/{}", line["_id"]["$oid"].as_str().unwrap());
+
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .insert_header((header::IF_MATCH, "\"1\""))
+        .set_json(json!({"qty": 4.0}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::ETAG).unwrap(), "\"2\"");
+
+    //the first write moved the line on, the same precondition is stale now
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .insert_header((header::IF_MATCH, "\"1\""))
+        .set_json(json!({"qty": 5.0}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+}
+
+async fn update_rejects_non_positive_quantities(store: Data<dyn Store>) {
+    let app = app!(store);
+    let token = login!(app, "jane@example.com");
+
+    let line = add_line!(app, token, "Apple", 2.0, 1.0);
+    let uri = format!("/update-cart/{}", line["_id"]["$oid"].as_str().unwrap());
+
+    for qty in [0.0, -2.0] {
+        let req = test::TestRequest::put()
+            .uri(&uri)
+            .insert_header((header::AUTHORIZATION, token.as_str()))
+            .set_json(json!({"qty": qty}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&uri.replace("/update-cart/", "/get-cart/"))
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"]["_total"], 2.0);
+}
+
+async fn delete_removes_the_line(store: Data<dyn Store>) {
+    let app = app!(store);
+    let token = login!(app, "jane@example.com");
+
+    let line = add_line!(app, token, "Apple", 2.0, 1.0);
+    let id = line["_id"]["$oid"].as_str().unwrap();
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/delete-cart/{}", id))
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"]["deletedCount"], 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/get-cart/{}", id))
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"], Value::Null);
+}
+
+async fn deleted_line_can_be_restored(store: Data<dyn Store>) {
+    let app = app!(store);
+    let token = login!(app, "jane@example.com");
+
+    let line = add_line!(app, token, "Apple", 2.0, 1.0);
+    let id = line["_id"]["$oid"].as_str().unwrap();
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/delete-cart/{}", id))
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/restore-cart/{}", id))
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::ETAG).unwrap(), "\"3\"");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/get-cart/{}", id))
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"]["product_name"], "Apple");
+
+    //a line still in the cart has nothing to restore
+    let req = test::TestRequest::post()
+        .uri(&format!("/restore-cart/{}", id))
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+async fn carts_are_private_to_their_owner(store: Data<dyn Store>) {
+    let app = app!(store);
+    let jane = login!(app, "jane@example.com");
+    let john = login!(app, "john@example.com");
+
+    let line = add_line!(app, jane, "Apple", 2.0, 1.0);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/get-cart/{}", line["_id"]["$oid"].as_str().unwrap()))
+        .insert_header((header::AUTHORIZATION, john.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"], Value::Null);
+}
+
+async fn guest_cart_is_merged_on_register(store: Data<dyn Store>) {
+    let app = app!(store);
+
+    let req = test::TestRequest::post()
+        .uri("/cart-create")
+        .set_json(json!({"product_name": "Apple", "price": 2.0, "qty": 1.0}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let guest_cookie = resp.response().cookies().find(|cookie| cookie.name() == "cart").unwrap().into_owned();
+
+    let req = test::TestRequest::post()
+        .uri("/user-create")
+        .cookie(guest_cookie)
+        .set_json(json!({"name": "Jane", "email": "jane@example.com", "password": "pwd"}))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::post()
+        .uri("/user-login")
+        .set_json(json!({"email": "jane@example.com", "password": "pwd"}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let token = format!("Bearer {}", body["token"].as_str().unwrap());
+
+    let req = test::TestRequest::get()
+        .uri("/all-cart")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"].as_array().unwrap().len(), 1);
+}
+
+async fn failed_register_leaves_the_guest_cart_alone(store: Data<dyn Store>) {
+    let app = app!(store);
+
+    let _ = login!(app, "jane@example.com");
+
+    let req = test::TestRequest::post()
+        .uri("/cart-create")
+        .set_json(json!({"product_name": "Apple", "price": 2.0, "qty": 1.0}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let guest_cookie = resp.response().cookies().find(|cookie| cookie.name() == "cart").unwrap().into_owned();
+
+    //the email is taken, so the merge is rolled back with the rest of the registration
+    let req = test::TestRequest::post()
+        .uri("/user-create")
+        .cookie(guest_cookie.clone())
+        .set_json(json!({"name": "Jane", "email": "Jane@Example.com", "password": "pwd"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::get()
+        .uri("/all-cart")
+        .cookie(guest_cookie)
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"].as_array().unwrap().len(), 1);
+}
+
+async fn merged_guest_line_can_be_restored(store: Data<dyn Store>) {
+    let app = app!(store);
+    let token = login!(app, "jane@example.com");
+
+    add_line!(app, token, "Apple", 2.0, 1.0);
+
+    let req = test::TestRequest::post()
+        .uri("/cart-create")
+        .set_json(json!({"product_name": "Apple", "price": 2.0, "qty": 2.0}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let guest_cookie = resp.response().cookies().find(|cookie| cookie.name() == "cart").unwrap().into_owned();
+    let body: Value = test::read_body_json(resp).await;
+    let guest_line = body["result"]["_id"]["$oid"].as_str().unwrap().to_owned();
+
+    //logging in sums the guest line into the line of the user
+    let req = test::TestRequest::post()
+        .uri("/user-login")
+        .cookie(guest_cookie.clone())
+        .set_json(json!({"email": "jane@example.com", "password": "pwd"}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["cart"]["merged"], 1);
+
+    let req = test::TestRequest::get()
+        .uri("/all-cart")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"][0]["qty"], 3.0);
+
+    //the guest line was deleted, not dropped, so the guest cart can still bring it back
+    let req = test::TestRequest::post()
+        .uri(&format!("/restore-cart/{}", guest_line))
+        .cookie(guest_cookie)
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"]["qty"], 2.0);
+}
+
+async fn idempotency_key_replays_the_first_response(store: Data<dyn Store>) {
+    let app = app!(store);
+    let token = login!(app, "jane@example.com");
+
+    for _ in 0..2 {
+        let req = test::TestRequest::post()
+            .uri("/cart-create")
+            .insert_header((header::AUTHORIZATION, token.as_str()))
+            .insert_header(("Idempotency-Key", "add-apple"))
+            .set_json(json!({"product_name": "Apple", "price": 2.0, "qty": 1.0}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/all-cart")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"][0]["qty"], 1.0);
+}
+
+//needs TEST_MONGOURI, the mongo tests are ignored unless run with --ignored. It has to be a replica set since the cart writes run in transactions.
+//Every test gets a collection prefix of its own in the cart-test database
+async fn mongo_repo() -> Arc<MongoRepo> {
+    let uri = std::env::var("TEST_MONGOURI").expect("TEST_MONGOURI is not set");
+
+    let config = MongoConfig {
+        db_name: "cart-test".to_owned(),
+        collection_prefix: format!("t{}_", ObjectId::new().to_hex()),
+        ..MongoConfig::new(uri)
+    };
+    let payments = Arc::new(FakePaymentProvider::new("secret", Duration::from_secs(0)));
+
+    let repo = MongoRepo::connect(config, payments).await.unwrap();
+    repo.sync_indexes(false).await.unwrap();
+    Arc::new(repo)
+}
+
+//every product the suite puts in a cart
+const SUITE_PRODUCTS: [&str; 6] = ["Apple", "Pear", "Plum", "A", "B", "C"];
+
+//the Mongo store only adds products it keeps stock for, the other stores do not track stock
+async fn mongo_store() -> Data<dyn Store> {
+    let repo = mongo_repo().await;
+
+    for sku in SUITE_PRODUCTS {
+        repo.set_inventory(sku, None, 1000.0, None).await.unwrap();
+    }
+
+    Data::from(repo as Arc<dyn Store>)
+}
+
+async fn cart_is_cleared_or_filtered(store: Data<dyn Store>) {
+    let app = app!(store);
+    let token = login!(app, "jane@example.com");
+
+    add_line!(app, token, "Apple", 2.0, 1.0);
+    add_line!(app, token, "Pear", 3.0, 1.0);
+    add_line!(app, token, "Plum", 1.0, 1.0);
+
+    //without a filter the route would clear the cart, that is left to DELETE /cart
+    let req = test::TestRequest::delete()
+        .uri("/cart/items")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::delete()
+        .uri("/cart/items?product_name=Pear")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"]["deleted"], 1);
+
+    let req = test::TestRequest::delete()
+        .uri("/cart")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"]["deleted"], 2);
+
+    let req = test::TestRequest::get()
+        .uri("/all-cart")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"], json!([]));
+}
+
+async fn idempotency_key_is_not_shared_between_new_visitors(store: Data<dyn Store>) {
+    let app = app!(store);
+
+    let mut cookies = Vec::new();
+
+    //two visitors without a cart send the same key, each has to get a guest cart of its own
+    for _ in 0..2 {
+        let req = test::TestRequest::post()
+            .uri("/cart-create")
+            .insert_header(("Idempotency-Key", "add-apple"))
+            .set_json(json!({"product_name": "Apple", "price": 2.0, "qty": 1.0}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get("Idempotent-Replayed").is_none());
+
+        let cookie = resp.response().cookies().find(|cookie| cookie.name() == "cart").unwrap().into_owned();
+        cookies.push(cookie.value().to_owned());
+    }
+
+    assert_ne!(cookies[0], cookies[1]);
+}
+
+#[actix_web::test]
+#[ignore = "needs TEST_MONGOURI, the inventory routes only run on MongoDB"]
+async fn inventory_is_set_by_admins_only() {
+    let repo = mongo_repo().await;
+    let store = Data::from(repo.clone() as Arc<dyn Store>);
+    let app = test::init_service(
+        App::new()
+            .app_data(store)
+            .app_data(Data::from(repo))
+            .configure(user_api::config)
+            .configure(inventory_api::config),
+    )
+    .await;
+    let token = login!(app, "jane@example.com");
+
+    let req = test::TestRequest::put()
+        .uri("/inventory/apple")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .set_json(json!({"stock": 100.0, "price": 0.01}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::put()
+        .uri("/inventory/apple")
+        .set_json(json!({"stock": 100.0, "price": 0.01}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+//the cart routes of the MongoDB store, the batch, the history, the notices and the sharing included
+macro_rules! mongo_app {
+    ($repo:expr) => {
+        test::init_service(
+            App::new()
+                .app_data(Data::from($repo.clone() as Arc<dyn Store>))
+                .app_data(Data::from($repo.clone()))
+                .configure(user_api::config)
+                .configure(cart_api::config)
+                .configure(share_api::config),
+        )
+        .await
+    };
+}
+
+#[actix_web::test]
+#[ignore = "needs TEST_MONGOURI, the batch only runs on MongoDB"]
+async fn cart_batch_is_applied_all_or_nothing() {
+    let repo = mongo_repo().await;
+    for sku in SUITE_PRODUCTS {
+        repo.set_inventory(sku, None, 1000.0, None).await.unwrap();
+    }
+    let app = mongo_app!(repo);
+    let token = login!(app, "jane@example.com");
+
+    let line = add_line!(app, token, "Apple", 2.0, 1.0);
+    let id = line["_id"]["$oid"].as_str().unwrap();
+
+    //the last operation fails, so the add and the update before it are rolled back
+    let req = test::TestRequest::post()
+        .uri("/cart/batch")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .set_json(json!({"operations": [
+            {"op": "add", "product_name": "Pear", "price": 3.0, "qty": 1.0},
+            {"op": "update", "id": id, "qty": 4.0},
+            {"op": "remove", "id": ObjectId::new().to_hex()}
+        ]}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::get()
+        .uri("/all-cart")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"].as_array().unwrap().len(), 1);
+    assert_eq!(body["result"][0]["qty"], 1.0);
+
+    let req = test::TestRequest::post()
+        .uri("/cart/batch")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .set_json(json!({"operations": [
+            {"op": "add", "product_name": "Pear", "price": 3.0, "qty": 1.0},
+            {"op": "update", "id": id, "qty": 4.0}
+        ]}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let outcomes: Vec<&str> = body["result"]["operations"].as_array().unwrap().iter().map(|result| result["outcome"].as_str().unwrap()).collect();
+    assert_eq!(outcomes, ["added", "updated"]);
+    assert_eq!(body["result"]["cart"].as_array().unwrap().len(), 2);
+}
+
+#[actix_web::test]
+#[ignore = "needs TEST_MONGOURI, the catalog only runs on MongoDB"]
+async fn price_change_is_acknowledged_before_it_applies() {
+    let repo = mongo_repo().await;
+    repo.set_inventory("Apple", None, 1000.0, Some(2.0)).await.unwrap();
+    let app = mongo_app!(repo);
+    let token = login!(app, "jane@example.com");
+
+    add_line!(app, token, "Apple", 2.0, 1.0);
+    repo.set_inventory("Apple", None, 1000.0, Some(3.0)).await.unwrap();
+
+    let req = test::TestRequest::get()
+        .uri("/all-cart")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let notice = &body["notices"][0];
+    assert_eq!(notice["kind"], "price_changed");
+    assert_eq!(notice["old_price"], 2.0);
+    assert_eq!(notice["new_price"], 3.0);
+
+    //the line keeps its price until the notice is accepted
+    assert_eq!(body["result"][0]["price"], 2.0);
+
+    let req = test::TestRequest::post()
+        .uri("/cart/acknowledge")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .set_json(json!({"notices": [notice["id"]]}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"][0]["price"], 3.0);
+    assert_eq!(body["notices"], json!([]));
+}
+
+#[actix_web::test]
+#[ignore = "needs TEST_MONGOURI, sharing only runs on MongoDB"]
+async fn shared_cart_is_read_by_viewers_and_changed_by_editors() {
+    let repo = mongo_repo().await;
+    for sku in SUITE_PRODUCTS {
+        repo.set_inventory(sku, None, 1000.0, None).await.unwrap();
+    }
+    let app = mongo_app!(repo);
+    let owner = login!(app, "jane@example.com");
+    let viewer = login!(app, "john@example.com");
+    let editor = login!(app, "mary@example.com");
+
+    let line = add_line!(app, owner, "Apple", 2.0, 1.0);
+    let owner_id = line["_uid"].as_str().unwrap().to_owned();
+
+    for (email, role) in [("john@example.com", "viewer"), ("mary@example.com", "editor")] {
+        let req = test::TestRequest::post()
+            .uri("/cart/share/invite")
+            .insert_header((header::AUTHORIZATION, owner.as_str()))
+            .set_json(json!({"email": email, "role": role}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/all-cart")
+        .insert_header((header::AUTHORIZATION, viewer.as_str()))
+        .insert_header((cart_api::SHARED_CART_HEADER, owner_id.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"].as_array().unwrap().len(), 1);
+
+    let req = test::TestRequest::post()
+        .uri("/cart-create")
+        .insert_header((header::AUTHORIZATION, viewer.as_str()))
+        .insert_header((cart_api::SHARED_CART_HEADER, owner_id.as_str()))
+        .set_json(json!({"product_name": "Pear", "price": 3.0, "qty": 1.0}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    //the line lands in the cart of the owner and records who added it
+    let req = test::TestRequest::post()
+        .uri("/cart-create")
+        .insert_header((header::AUTHORIZATION, editor.as_str()))
+        .insert_header((cart_api::SHARED_CART_HEADER, owner_id.as_str()))
+        .set_json(json!({"product_name": "Pear", "price": 3.0, "qty": 1.0}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["result"]["_uid"], owner_id.as_str());
+    assert_ne!(body["result"]["addedBy"], owner_id.as_str());
+
+    //nobody else gets in
+    let stranger = login!(app, "jim@example.com");
+    let req = test::TestRequest::get()
+        .uri("/all-cart")
+        .insert_header((header::AUTHORIZATION, stranger.as_str()))
+        .insert_header((cart_api::SHARED_CART_HEADER, owner_id.as_str()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+#[ignore = "needs TEST_MONGOURI, the history only runs on MongoDB"]
+async fn cart_history_records_every_change() {
+    let repo = mongo_repo().await;
+    for sku in SUITE_PRODUCTS {
+        repo.set_inventory(sku, None, 1000.0, None).await.unwrap();
+    }
+    let app = mongo_app!(repo);
+    let token = login!(app, "jane@example.com");
+
+    let line = add_line!(app, token, "Apple", 2.0, 1.0);
+    let id = line["_id"]["$oid"].as_str().unwrap();
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/update-cart/{}", id))
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .set_json(json!({"qty": 2.0}))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/delete-cart/{}", id))
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::get()
+        .uri("/cart/history")
+        .insert_header((header::AUTHORIZATION, token.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let events = body["result"].as_array().unwrap();
+
+    //newest first, each with the line before and after the change
+    let actions: Vec<&str> = events.iter().map(|event| event["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["remove", "update", "add"]);
+    assert_eq!(events[1]["before"]["qty"], 1.0);
+    assert_eq!(events[1]["after"]["qty"], 2.0);
+    assert_eq!(events[0]["after"], Value::Null);
+    assert_eq!(events[0]["actor"], line["_uid"]);
+}
+
+//the batch, the history and the notices are not served by the memory and SQL stores
+#[actix_web::test]
+async fn mongo_only_cart_routes_answer_not_implemented() {
+    let store = memory_store().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(store)
+            .configure(user_api::config)
+            .configure(cart_api::store_config)
+            .configure(cart_api::unsupported_config),
+    )
+    .await;
+    let token = login!(app, "jane@example.com");
+
+    for req in [
+        test::TestRequest::post().uri("/cart/batch").set_json(json!({"operations": []})),
+        test::TestRequest::post().uri("/cart/acknowledge").set_json(json!({"notices": []})),
+        test::TestRequest::get().uri("/cart/history"),
+    ] {
+        let req = req.insert_header((header::AUTHORIZATION, token.as_str())).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_IMPLEMENTED);
+    }
+}
+
+#[actix_web::test]
+async fn sql_store_keeps_decimal_amounts() {
+    let store = sqlite_store().await;
+    let app = app!(store);
+    let token = login!(app, "jane@example.com");
+
+    //worked out in floats the total would be 0.30000000000000004
+    let line = add_line!(app, token, "Apple", 0.1, 3.0);
+    assert_eq!(line["_total"], 0.3);
+
+    let line = add_line!(app, token, "Pear", 1.005, 1.0);
+    assert_eq!(line["price"], 1.01);
+}
+
+//runs every test named here against the store the given function builds, the attributes
+//go on every test so a store that needs a server can be ignored by default
+macro_rules! conformance {
+    ($backend:ident, $store:path, $attrs:tt, [$($test:ident),* $(,)?]) => {
+        mod $backend {
+            $(conformance!(@test $store, $attrs, $test);)*
+        }
+    };
+    (@test $store:path, [$(#[$attr:meta])*], $test:ident) => {
+        #[actix_web::test]
+        $(#[$attr])*
+        async fn $test() {
+            super::$test($store().await).await;
+        }
+    };
+}
+
+//the tests every store has to pass
+macro_rules! conformance_suite {
+    ($(#[$attr:meta])* $backend:ident, $store:path) => {
+        conformance!($backend, $store, [$(#[$attr])*], [
+            register_rejects_duplicate_email,
+            login_rejects_wrong_password,
+            login_checks_the_password_case,
+            cart_lines_are_created_merged_and_listed,
+            cart_listing_pages_through_the_cursor,
+            update_checks_if_match,
+            update_rejects_non_positive_quantities,
+            delete_removes_the_line,
+            cart_is_cleared_or_filtered,
+            deleted_line_can_be_restored,
+            carts_are_private_to_their_owner,
+            guest_cart_is_merged_on_register,
+            failed_register_leaves_the_guest_cart_alone,
+            merged_guest_line_can_be_restored,
+            idempotency_key_replays_the_first_response,
+            idempotency_key_is_not_shared_between_new_visitors,
+        ]);
+    };
+}
+
+conformance_suite!(memory, super::memory_store);
+conformance_suite!(sqlite, super::sqlite_store);
+conformance_suite!(#[ignore = "needs TEST_POSTGRES_URL"] postgres, super::postgres_store);
+conformance_suite!(#[ignore = "needs TEST_MONGOURI"] mongo, super::mongo_store);
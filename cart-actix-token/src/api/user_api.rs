@@ -2,10 +2,12 @@
 
 ////----------------------  START - Initial routes ----------------------------- ////
 
-use actix_web::{HttpResponse, Responder, get, web::{self, Data, Json}, post};
+use actix_web::{ResponseError, HttpResponse, Responder, get, web::{self, Data, Json}, post, HttpRequest, cookie::{time::Duration as ActixWebDuration, Cookie}};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{encode, Header, EncodingKey};
 use serde_json::json;
 
-use crate::{repository::mongodb_repo::MongoRepo, model::user_model::{User, LoginUserSchema}};
+use crate::{error::AppError, repository::{store::Store, guest_cart_repo::{expired_guest_cart_cookie, GUEST_CART_COOKIE}}, model::{user_model::{User, LoginUserSchema, TokenClaims}, response_model::ErrorResponse}};
 
 
 //route handler function
@@ -23,29 +25,88 @@ pub async fn initial() -> impl Responder {
 
 //user register handler function
 #[post("/user-create")]
-pub async fn register_user(db: Data<MongoRepo>, new_user: Json<User>) -> HttpResponse {
+pub async fn register_user(req: HttpRequest, db: Data<dyn Store>, new_user: Json<User>) -> HttpResponse {
     let data = User {
         id: None,
         name: new_user.name.to_owned(),
         password: new_user.password.to_owned(),
-        email: new_user.email.to_owned(),
+        //stored normalized, migration 1 brought the older users in line
+        email: new_user.email.trim().to_lowercase(),
         created_at: None
     };
 
-    match db.register_user(data).await {
+    let guest_cart = req.cookie(GUEST_CART_COOKIE);
+
+    match db.register_user(data, guest_cart.as_ref().map(|c| c.value())).await {
+        Ok(_) if guest_cart.is_some() => HttpResponse::Ok()
+            .cookie(expired_guest_cart_cookie())
+            .json(json!({"status" : "success", "message" : "Registration Successfull"})),
         Ok(_) => HttpResponse::Ok().json(json!({"status" : "success", "message" : "Registration Successfull"})),
-        Err(error) =>  HttpResponse::ExpectationFailed().json(json!({"status" : "failed", "message" : error}))
+        Err(error) =>  error.error_response()
     }
 }
 
-//user login handler function
+//user login handler function, a guest cart is merged into the user cart
 #[post("/user-login")]
-pub async fn login_user(user: web::Json<LoginUserSchema>, db: Data<MongoRepo>) -> HttpResponse {
-    
-    let user_details = db.login_user(user.into_inner());
-    
-    user_details.await
+pub async fn login_user(req: HttpRequest, user: web::Json<LoginUserSchema>, db: Data<dyn Store>) -> HttpResponse {
+
+    let guest_cart = req.cookie(GUEST_CART_COOKIE);
+
+    let user = match db.find_by_email_pwd(&user.email, &user.password).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return HttpResponse::BadRequest()
+            .json(ErrorResponse{
+                status: false,
+                message: "Invalid username or password".to_owned()
+            }),
+        Err(error) => return error.error_response(),
+    };
+
+    let jwt_secret = "secret".to_owned();
+
+    let id = match user.id {
+        Some(id) => id,
+        None => return AppError::Internal("User has no id".to_owned()).error_response(),
+    };
+
+    let now = Utc::now();
+    let iat = now.timestamp() as usize;
+
+    let exp = (now + Duration::minutes(60)).timestamp() as usize;
+    let claims: TokenClaims = TokenClaims {
+        sub: id.to_string(),
+        exp,
+        iat,
+    };
+
+    let token = match encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(jwt_secret.as_ref()),
+    ) {
+        Ok(token) => token,
+        Err(error) => return AppError::from(error).error_response(),
+    };
+
+    let cookie = Cookie::build("token", token.to_owned())
+        .path("/")
+        .max_age(ActixWebDuration::new(60 * 60, 0))
+        .http_only(true)
+        .finish();
+
+    if let Some(guest_cart) = guest_cart {
+        return match db.merge_guest_cart(guest_cart.value(), &id.to_string()).await {
+            Ok(merged) => HttpResponse::Ok()
+                .cookie(cookie)
+                .cookie(expired_guest_cart_cookie())
+                .json(json!({"status" :  "success", "token": token, "cart": merged})),
+            Err(error) => error.error_response(),
+        };
+    }
 
+    HttpResponse::Ok()
+        .cookie(cookie)
+        .json(json!({"status" :  "success", "token": token}))
 }
 
 ////----------------------  END - User routes ----------------------------- ////
@@ -0,0 +1,138 @@
+            //// _____________ Wishlist Api____________  ////
+
+use actix_web::{ResponseError, HttpResponse, get, post, delete, web::{self, Data, Json}, HttpRequest};
+use serde_json::json;
+
+use crate::{repository::mongodb_repo::MongoRepo, middleware::auth::{bearer_token, missing_token}, model::{cart_model::Cart, wishlist_model::CreateWishlist}};
+
+
+////----------------------  START - Wishlist routes ----------------------------- ////
+
+//handler to list the saved-for-later list and the wishlists
+#[get("/all-lists")]
+pub async fn get_all_lists(req: HttpRequest, db: Data<MongoRepo>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.list_saved_lists(&token).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to create a wishlist
+#[post("/wishlist-create")]
+pub async fn create_wishlist(req: HttpRequest, db: Data<MongoRepo>, data: Json<CreateWishlist>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.create_wishlist(&token, data.into_inner().name).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to delete a wishlist
+#[delete("/delete-wishlist/{id}")]
+pub async fn delete_wishlist(req: HttpRequest, db: Data<MongoRepo>, id: web::Path<String>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.delete_wishlist(&token, id.into_inner()).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to add an item straight to a list
+#[post("/list-add/{list_id}")]
+pub async fn add_to_list(req: HttpRequest, db: Data<MongoRepo>, list_id: web::Path<String>, item: Json<Cart>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.add_to_list(&token, list_id.into_inner(), item.into_inner()).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to remove an item from a list
+#[delete("/delete-list-item/{list_id}/{item_id}")]
+pub async fn delete_list_item(req: HttpRequest, db: Data<MongoRepo>, path: web::Path<(String, String)>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+    let (list_id, item_id) = path.into_inner();
+
+    match db.delete_list_item(&token, list_id, item_id).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to move a cart item to the saved-for-later list
+#[post("/save-for-later/{cart_id}")]
+pub async fn save_for_later(req: HttpRequest, db: Data<MongoRepo>, cart_id: web::Path<String>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+
+    match db.move_to_list(&token, cart_id.into_inner(), None).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to move a cart item to a wishlist
+#[post("/move-to-list/{cart_id}/{list_id}")]
+pub async fn move_to_list(req: HttpRequest, db: Data<MongoRepo>, path: web::Path<(String, String)>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+    let (cart_id, list_id) = path.into_inner();
+
+    match db.move_to_list(&token, cart_id, Some(list_id)).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+//handler to move a list item back into the cart
+#[post("/move-to-cart/{list_id}/{item_id}")]
+pub async fn move_to_cart(req: HttpRequest, db: Data<MongoRepo>, path: web::Path<(String, String)>) -> HttpResponse {
+    let token = match bearer_token(&req) {
+        Some(token) => token,
+        None => return missing_token(),
+    };
+    let (list_id, item_id) = path.into_inner();
+
+    match db.move_to_cart(&token, list_id, item_id).await {
+        Ok(result) => HttpResponse::Ok().json(json!({"status" : "success", "result" : result})),
+        Err(error) =>  error.error_response(),
+    }
+}
+
+////----------------------  END - Wishlist routes ----------------------------- ////
+
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_all_lists)
+    .service(create_wishlist)
+    .service(delete_wishlist)
+    .service(add_to_list)
+    .service(delete_list_item)
+    .service(save_for_later)
+    .service(move_to_list)
+    .service(move_to_cart);
+}
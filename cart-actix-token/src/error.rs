@@ -0,0 +1,159 @@
+use std::fmt;
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::{Serialize, Serializer};
+use serde_json::json;
+
+use crate::model::response_model::ErrorResponse;
+
+//Error of a repository or store operation, each kind answers with its own HTTP status
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    NotFound(String),
+    //missing, invalid or expired token
+    Unauthorized(String),
+    //signed in but not allowed to do this
+    Forbidden(String),
+    //the request itself is wrong, bad ids included
+    Validation(String),
+    //the request clashes with the current state, e.g. not enough stock or a duplicate
+    Conflict(String),
+    //the payment provider declined or failed
+    Payment(String),
+    //the database failed, the details are logged and not sent to the client
+    Database(String),
+    //the database gave up on a transaction and asked for it to be run again
+    Transient(String),
+    //anything else that went wrong on the server, e.g. a token that could not be signed
+    Internal(String),
+}
+
+impl AppError {
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::Validation(message)
+            | AppError::Conflict(message)
+            | AppError::Payment(message)
+            | AppError::Database(message)
+            | AppError::Transient(message)
+            | AppError::Internal(message) => message,
+        }
+    }
+
+    //same kind of error with another message, e.g. to say where it happened
+    pub fn with_message(self, message: String) -> Self {
+        match self {
+            AppError::NotFound(_) => AppError::NotFound(message),
+            AppError::Unauthorized(_) => AppError::Unauthorized(message),
+            AppError::Forbidden(_) => AppError::Forbidden(message),
+            AppError::Validation(_) => AppError::Validation(message),
+            AppError::Conflict(_) => AppError::Conflict(message),
+            AppError::Payment(_) => AppError::Payment(message),
+            AppError::Database(_) => AppError::Database(message),
+            AppError::Transient(_) => AppError::Transient(message),
+            AppError::Internal(_) => AppError::Internal(message),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+//serialized like ErrorResponse, so response bodies keep their shape
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ErrorResponse {
+            message: self.message().to_owned(),
+            status: false,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Payment(_) => StatusCode::PAYMENT_REQUIRED,
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Transient(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(json!({"status" : "failed", "message" : self}))
+    }
+}
+
+impl From<mongodb::error::Error> for AppError {
+    fn from(error: mongodb::error::Error) -> Self {
+        eprintln!("Database error: {:?}", error);
+
+        if error.contains_label(mongodb::error::TRANSIENT_TRANSACTION_ERROR) {
+            return AppError::Transient("Database busy, try again".to_owned());
+        }
+        AppError::Database("Database error".to_owned())
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for AppError {
+    fn from(error: jsonwebtoken::errors::Error) -> Self {
+        eprintln!("Token error: {:?}", error);
+        AppError::Internal("Could not sign the token".to_owned())
+    }
+}
+
+impl From<mongodb::bson::oid::Error> for AppError {
+    fn from(_: mongodb::bson::oid::Error) -> Self {
+        AppError::Validation("Invalid id".to_owned())
+    }
+}
+
+impl From<mongodb::bson::ser::Error> for AppError {
+    fn from(error: mongodb::bson::ser::Error) -> Self {
+        eprintln!("Serialization error: {:?}", error);
+        AppError::Database("Database error".to_owned())
+    }
+}
+
+impl From<mongodb::bson::de::Error> for AppError {
+    fn from(error: mongodb::bson::de::Error) -> Self {
+        eprintln!("Deserialization error: {:?}", error);
+        AppError::Database("Database error".to_owned())
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        eprintln!("Database error: {:?}", error);
+
+        //serialization failures and deadlocks in PostgreSQL, a locked database in SQLite
+        let retryable = match &error {
+            sqlx::Error::Database(error) => matches!(error.code().as_deref(), Some("40001" | "40P01" | "5" | "6")),
+            sqlx::Error::PoolTimedOut => true,
+            _ => false,
+        };
+
+        if retryable {
+            return AppError::Transient("Database busy, try again".to_owned());
+        }
+        AppError::Database("Database error".to_owned())
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for AppError {
+    fn from(error: sqlx::migrate::MigrateError) -> Self {
+        eprintln!("Migration error: {:?}", error);
+        AppError::Database("Database migration failed".to_owned())
+    }
+}
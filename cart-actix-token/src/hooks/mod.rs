@@ -0,0 +1,25 @@
+use async_trait::async_trait;
+
+use crate::model::cart_model::AbandonedCart;
+
+//Callback fired once for every cart the classifier newly marks as abandoned
+#[async_trait]
+pub trait AbandonedCartHook: Send + Sync + std::fmt::Debug {
+    async fn on_abandoned(&self, cart: &AbandonedCart);
+}
+
+//Default hook, writes abandoned carts to the log
+#[derive(Debug)]
+pub struct LogAbandonedCartHook;
+
+#[async_trait]
+impl AbandonedCartHook for LogAbandonedCartHook {
+    async fn on_abandoned(&self, cart: &AbandonedCart) {
+        println!(
+            "🛒 Cart of {} abandoned with {} items worth {}",
+            cart.email.as_deref().unwrap_or(&cart.user_id),
+            cart.items,
+            cart.total
+        );
+    }
+}
@@ -1,45 +1,251 @@
+use std::{sync::Arc, time::Duration};
 
 use actix_cors::Cors;
 use actix_web::{web::Data, HttpServer, App, http::header};
 
-use crate::{repository::mongodb_repo::MongoRepo};
+use crate::{repository::{mongodb_repo::MongoRepo, memory_store::MemoryStore, sql_store::SqlStore, store::Store}, payment::fake_provider::FakePaymentProvider, outbox::sink_from_env};
 
 
+mod error;
 mod model;
 mod api;
+mod hooks;
 mod middleware;
+mod migrations;
+mod outbox;
+mod payment;
 mod repository;
 
-use api::{user_api, cart_api};
+use api::{user_api, cart_api, inventory_api, order_api, payment_api, product_api, share_api, admin_api, wishlist_api};
 
 
+fn cors() -> Cors {
+    Cors::default()
+        .allowed_origin("http://localhost:3000")
+        .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
+        .allowed_headers(vec![
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::ACCEPT,
+            header::IF_MATCH,
+            header::HeaderName::from_static("idempotency-key"),
+            header::HeaderName::from_static("shared-cart"),
+        ])
+        //the frontend reads the version of a line from it and sends it back in If-Match
+        .expose_headers(vec![header::ETAG, header::HeaderName::from_static("idempotent-replayed")])
+        .supports_credentials()
+}
+
+async fn run_migrations(db: &MongoRepo, dry_run: bool) -> std::io::Result<()> {
+    let outcomes = db
+        .run_migrations(dry_run)
+        .await
+        .map_err(|error| std::io::Error::other(error.to_string()))?;
+
+    if outcomes.is_empty() {
+        println!("📦 No pending migrations");
+    }
+
+    for outcome in outcomes {
+        let verb = if outcome.applied { "Applied" } else { "Would apply" };
+        println!("📦 {} migration {} {} ({} documents)", verb, outcome.version, outcome.name, outcome.affected);
+    }
+
+    Ok(())
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()>{
     if std::env::var_os("RUST_LOG").is_none() {
         std::env::set_var("RUST_LOG", "actix_web=info");
     }
     env_logger::init();
+    dotenv::dotenv().ok();
+
+    //STORE=memory serves the user and cart routes without a database, for local demos. The catalog,
+    //inventory, orders, payments, wishlists, shares and admin routes are only served with MongoDB
+    if std::env::var("STORE").is_ok_and(|store| store == "memory") {
+        let store: Data<dyn Store> = Data::from(Arc::new(MemoryStore::default()) as Arc<dyn Store>);
+
+        println!("🚀 Server started with the in-memory store");
+
+        return HttpServer::new(move || {
+            App::new()
+                .app_data(store.clone())
+                .wrap(cors())
+                .configure(user_api::config)
+                .configure(cart_api::store_config)
+                .configure(cart_api::unsupported_config)
+            })
+            .bind(("127.0.0.1", 8060))?
+            .run()
+            .await;
+    }
+
+    //STORE=sql serves the user and cart routes from SQLite or PostgreSQL, DATABASE_URL says which
+    if std::env::var("STORE").is_ok_and(|store| store == "sql") {
+        let url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite://cart.db?mode=rwc".to_owned());
+        let sql_store = Arc::new(SqlStore::connect(&url).await.map_err(|error| std::io::Error::other(error.to_string()))?);
+        let store: Data<dyn Store> = Data::from(sql_store.clone() as Arc<dyn Store>);
+
+        //there are no TTL indexes, expired guest items, deleted items and idempotency keys are purged here
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(Duration::from_secs(60 * 60));
+            loop {
+                interval.tick().await;
+                match sql_store.purge_expired().await {
+                    Ok(0) => {},
+                    Ok(purged) => println!("🗑  Purged {} expired rows", purged),
+                    Err(error) => eprintln!("Error purging expired rows: {:?}", error),
+                }
+            }
+        });
+
+        println!("🚀 Server started with the SQL store");
+
+        return HttpServer::new(move || {
+            App::new()
+                .app_data(store.clone())
+                .wrap(cors())
+                .configure(user_api::config)
+                .configure(cart_api::store_config)
+                .configure(cart_api::unsupported_config)
+            })
+            .bind(("127.0.0.1", 8060))?
+            .run()
+            .await;
+    }
+
+    //PAYMENT_PROVIDER picks the gateway, the fake one is the only one so far
+    let fake_payments = match std::env::var("PAYMENT_PROVIDER").unwrap_or_else(|_| "fake".to_owned()).as_str() {
+        "fake" => true,
+        other => return Err(std::io::Error::other(format!("PAYMENT_PROVIDER {} is not supported, the available provider is fake", other))),
+    };
+
+    //the webhook route is always mounted, a known secret would let anyone sign a payment as captured
+    let webhook_secret = std::env::var("PAYMENT_WEBHOOK_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .ok_or_else(|| std::io::Error::other("PAYMENT_WEBHOOK_SECRET must be set"))?;
+    let webhook_delay = std::env::var("FAKE_WEBHOOK_DELAY_SECONDS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(5);
+    let payments = Arc::new(FakePaymentProvider::new(&webhook_secret, Duration::from_secs(webhook_delay)));
+    let payments_data = Data::from(payments.clone());
+
+    //a missing setting or an unreachable server stops here, before the port is bound
+    let db = MongoRepo::init(payments.clone())
+        .await
+        .map_err(|error| std::io::Error::other(format!("MongoDB store: {}", error)))?;
+
+    //`migrate` runs the pending migrations and exits, `migrate --dry-run` only lists what they would change
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|command| command == "migrate") {
+        let dry_run = args.iter().any(|arg| arg == "--dry-run");
+        return run_migrations(&db, dry_run).await;
+    }
+
+    //MIGRATE_ON_START=true brings the database up to date before serving
+    if std::env::var("MIGRATE_ON_START").is_ok_and(|migrate| migrate == "true") {
+        run_migrations(&db, false).await?;
+    }
+
+    db.sync_indexes_from_env()
+        .await
+        .map_err(|error| std::io::Error::other(format!("MongoDB indexes: {}", error)))?;
 
-    let db = MongoRepo::init().await;
     let db_data = Data::new(db);
+    let store_data: Data<dyn Store> = Data::from(db_data.clone().into_inner() as Arc<dyn Store>);
+
+    //periodically hand the stock of timed out reservations back
+    let sweeper = db_data.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            match sweeper.release_expired_reservations().await {
+                Ok(0) => {},
+                Ok(released) => println!("🔓 Released {} expired reservations", released),
+                Err(error) => eprintln!("Error releasing reservations: {:?}", error),
+            }
+        }
+    });
+
+    //mark idle carts as abandoned and notify the hook about each of them
+    let classifier = db_data.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(10 * 60));
+        loop {
+            interval.tick().await;
+            if let Err(error) = classifier.classify_abandoned_carts().await {
+                eprintln!("Error classifying abandoned carts: {:?}", error);
+            }
+        }
+    });
+
+    //hard-delete the cart items that were removed longer ago than the retention
+    let purger = db_data.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            match purger.purge_deleted_carts().await {
+                Ok(0) => {},
+                Ok(purged) => println!("🗑  Purged {} deleted cart items", purged),
+                Err(error) => eprintln!("Error purging deleted cart items: {:?}", error),
+            }
+        }
+    });
+
+    //deliver the outbox events to the configured sink, in the order they were written
+    if let Some(sink) = sink_from_env().map_err(|error| std::io::Error::other(error.to_string()))? {
+        println!("📣 Relaying domain events to the {} sink", sink.name());
+
+        let outbox = db_data.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval = actix_web::rt::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                if let Err(error) = outbox.relay_outbox(sink.as_ref()).await {
+                    eprintln!("Error relaying domain events: {:?}", error);
+                }
+            }
+        });
+    }
+
+    //deliver the webhooks the fake provider scheduled once their delay has passed
+    let relay = db_data.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            for (payload, signature) in payments.due_webhooks() {
+                if let Err(err) = relay.handle_payment_webhook(&payload, &signature).await {
+                    eprintln!("Error delivering webhook: {:?}", err);
+                }
+            }
+        }
+    });
 
     println!("🚀 Server started successfully");
 
     HttpServer::new(move || {
-        let cors = Cors::default()
-            .allowed_origin("http://localhost:3000")
-            .allowed_methods(vec!["GET", "POST"])
-            .allowed_headers(vec![
-                header::CONTENT_TYPE,
-                header::AUTHORIZATION,
-                header::ACCEPT,
-            ])
-            .supports_credentials();
-
         App::new()
             .app_data(db_data.clone())
+            .app_data(store_data.clone())
+            .app_data(payments_data.clone())
+            .wrap(cors())
             .configure(user_api::config)
             .configure(cart_api::config)
+            .configure(inventory_api::config)
+            .configure(product_api::config)
+            .configure(order_api::config)
+            .configure(payment_api::config)
+            .configure(|cfg| if fake_payments { payment_api::fake_config(cfg) })
+            .configure(wishlist_api::config)
+            .configure(share_api::config)
+            .configure(admin_api::config)
     
         })
         .bind(("127.0.0.1", 8060))?
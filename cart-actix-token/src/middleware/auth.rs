@@ -1,9 +1,24 @@
-use crate::model::user_model::TokenClaims;
+use crate::model::{response_model::ErrorResponse, user_model::TokenClaims};
 use actix_web::error::ErrorUnauthorized;
-use actix_web::{dev, Error, FromRequest, HttpRequest};
+use actix_web::{dev, Error, FromRequest, HttpRequest, HttpResponse};
 use futures::future::{err, ok, Ready};
 use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
 
+//bearer token of the Authorization header, None when the header is missing or malformed
+pub fn bearer_token(req: &HttpRequest) -> Option<String> {
+    let auth = req.headers().get("Authorization")?;
+    let split: Vec<&str> = auth.to_str().ok()?.split("Bearer").collect();
+    split.get(1).map(|token| token.trim().to_owned())
+}
+
+//answer of a route that needs a token and got none
+pub fn missing_token() -> HttpResponse {
+    HttpResponse::Unauthorized().json(ErrorResponse {
+        status: false,
+        message: "Missing token".to_owned(),
+    })
+}
+
 pub struct AuthorizationService;
 
 impl FromRequest for AuthorizationService {
@@ -12,18 +27,14 @@ impl FromRequest for AuthorizationService {
     
 
     fn from_request(_req: &HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
-        let _auth = _req.headers().get("Authorization");
-
         let secret_key = "secret".to_owned();
 
-        match _auth {
-            Some(_) => {
-                let _split: Vec<&str> = _auth.unwrap().to_str().unwrap().split("Bearer").collect();
-                let token = _split[1].trim();
+        match bearer_token(_req) {
+            Some(token) => {
                 let _var = secret_key;
                 let key = _var.as_bytes();
                 match decode::<TokenClaims>(
-                    token,
+                    &token,
                     &DecodingKey::from_secret(key),
                     &Validation::new(Algorithm::HS256),
                 ) {
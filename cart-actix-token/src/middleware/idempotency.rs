@@ -0,0 +1,110 @@
+use std::{collections::BTreeMap, future::Future};
+
+use actix_web::{
+    body::{to_bytes, BoxBody},
+    http::{header, StatusCode},
+    HttpRequest, HttpResponse, ResponseError};
+use serde::Serialize;
+
+use crate::{repository::store::Store, model::response_model::ErrorResponse};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+//headers of the first response that a replay has to carry as well. Set-Cookie is never among them,
+//a replay must not hand the cookies of one client to another
+const REPLAYED_HEADERS: [header::HeaderName; 1] = [header::ETAG];
+
+fn idempotency_error(status: StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status).json(ErrorResponse {
+        status: false,
+        message: message.to_owned(),
+    })
+}
+
+//runs the handler once per Idempotency-Key and owner, repeats get the stored first response.
+//Requests without the header, and requests without a cart to scope the key to, run as usual
+pub async fn idempotent<B, F, Fut>(req: &HttpRequest, db: &dyn Store, token: Option<&str>, body: &B, handler: F) -> HttpResponse
+where
+    B: Serialize,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = HttpResponse>,
+{
+    let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(key) => match key.to_str().map(str::trim) {
+            Ok(key) if !key.is_empty() && key.len() <= 255 => key.to_owned(),
+            _ => return idempotency_error(StatusCode::BAD_REQUEST, "Invalid Idempotency-Key"),
+        },
+        None => return handler().await,
+    };
+
+    //a visitor without a token has no cart yet, so nothing tells two of them apart and a shared scope
+    //would replay the response of one to the other
+    let owner = match token {
+        Some(token) => match db.cart_owner(token).await {
+            Ok(Some(owner)) => owner,
+            //let the handler report the bad token, there is nothing to scope the key to
+            _ => return handler().await,
+        },
+        None => return handler().await,
+    };
+
+    let request = format!(
+        "{} {} {}",
+        req.method(),
+        req.uri(),
+        serde_json::to_string(body).unwrap_or_default()
+    );
+
+    let claimed = match db.claim_idempotency_key(&key, &owner, &request).await {
+        Ok(claimed) => claimed,
+        Err(error) => return error.error_response(),
+    };
+
+    if let Some(record) = claimed {
+        if record.request != request {
+            return idempotency_error(StatusCode::UNPROCESSABLE_ENTITY, "Idempotency-Key was already used for a different request");
+        }
+
+        return match record.status.and_then(|status| StatusCode::from_u16(status).ok()) {
+            Some(status) => {
+                let mut replay = HttpResponse::build(status);
+                for (name, value) in &record.headers {
+                    replay.append_header((name.as_str(), value.as_str()));
+                }
+                replay
+                    .insert_header(("Idempotent-Replayed", "true"))
+                    .content_type("application/json")
+                    .body(record.body.unwrap_or_default())
+            },
+            None => idempotency_error(StatusCode::CONFLICT, "A request with this Idempotency-Key is still in progress"),
+        };
+    }
+
+    let response = handler().await;
+
+    //server errors are not stored, the client may retry them with the same key
+    if response.status().is_server_error() {
+        if let Err(error) = db.release_idempotency_key(&key, &owner).await {
+            eprintln!("Error releasing idempotency key: {:?}", error);
+        }
+        return response;
+    }
+
+    let status = response.status();
+    let headers: BTreeMap<String, String> = REPLAYED_HEADERS
+        .iter()
+        .filter_map(|name| {
+            let value = response.headers().get(name)?.to_str().ok()?;
+            Some((name.to_string(), value.to_owned()))
+        })
+        .collect();
+
+    let (response, body) = response.into_parts();
+    let bytes = to_bytes(body).await.unwrap_or_default();
+
+    if let Err(error) = db.complete_idempotency_key(&key, &owner, status.as_u16(), headers, String::from_utf8_lossy(&bytes).into_owned()).await {
+        eprintln!("Error storing idempotency key: {:?}", error);
+    }
+
+    response.set_body(BoxBody::new(bytes))
+}
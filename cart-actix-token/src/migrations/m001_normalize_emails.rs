@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use mongodb::bson::{doc, Bson};
+
+use crate::error::AppError;
+
+use super::{Migration, MigrationContext};
+
+//Stores emails trimmed and in lower case, the way register saves them now
+pub struct NormalizeEmails;
+
+//accounts whose emails only differ in case or surrounding whitespace, they would end up with the same
+//email and the unique index could not be built. Which one to keep is for a person to decide
+async fn duplicate_accounts(ctx: &MigrationContext<'_>) -> Result<Vec<String>, AppError> {
+    let pipeline = vec![
+        doc! {"$group": {"_id": {"$toLower": {"$trim": {"input": "$email"}}}, "ids": {"$push": "$_id"}, "count": {"$sum": 1}}},
+        doc! {"$match": {"count": {"$gt": 1}, "_id": {"$ne": null}}},
+        doc! {"$sort": {"_id": 1}},
+    ];
+
+    let mut cursor = ctx.collection("User").aggregate(pipeline, None).await?;
+    let mut duplicates = Vec::new();
+
+    while let Some(group) = cursor.next().await {
+        let group = group?;
+        let email = group.get_str("_id").unwrap_or_default().to_owned();
+        let ids: Vec<String> = group
+            .get_array("ids")
+            .map(|ids| ids.iter().map(|id| match id {
+                Bson::ObjectId(id) => id.to_hex(),
+                id => id.to_string(),
+            }).collect())
+            .unwrap_or_default();
+
+        duplicates.push(format!("{} (users {})", email, ids.join(", ")));
+    }
+
+    Ok(duplicates)
+}
+
+#[async_trait]
+impl Migration for NormalizeEmails {
+    fn version(&self) -> i64 {
+        1
+    }
+
+    fn name(&self) -> &'static str {
+        "normalize_emails"
+    }
+
+    async fn up(&self, ctx: &MigrationContext<'_>) -> Result<u64, AppError> {
+        let duplicates = duplicate_accounts(ctx).await?;
+        if !duplicates.is_empty() {
+            return Err(AppError::Conflict(format!(
+                "{} emails belong to more than one account once case and whitespace are ignored, merge or rename these accounts and run the migration again: {}",
+                duplicates.len(),
+                duplicates.join("; ")
+            )));
+        }
+
+        let users = ctx.collection("User");
+
+        //an upper case letter or surrounding whitespace
+        let filter = doc! {"email": {"$regex": "[A-Z]|^\\s|\\s$"}};
+
+        if ctx.dry_run {
+            return Ok(users.count_documents(filter, None).await?);
+        }
+
+        let updated = users
+            .update_many(filter, vec![doc! {"$set": {"email": {"$toLower": {"$trim": {"input": "$email"}}}}}], None)
+            .await?;
+
+        Ok(updated.modified_count)
+    }
+}
@@ -0,0 +1,40 @@
+mod m001_normalize_emails;
+
+use async_trait::async_trait;
+use mongodb::{bson::Document, Collection, Database};
+
+use crate::error::AppError;
+
+//What a migration works on
+pub struct MigrationContext<'a> {
+    pub db: &'a Database,
+    //prefix of the collection names of this deployment, collection() adds it
+    pub collection_prefix: &'a str,
+    //count what would change instead of changing it
+    pub dry_run: bool,
+}
+
+impl MigrationContext<'_> {
+    pub fn collection(&self, name: &str) -> Collection<Document> {
+        self.db.collection(&format!("{}{}", self.collection_prefix, name))
+    }
+}
+
+//Versioned change of the stored documents, each version runs once and is recorded in _migrations
+#[async_trait]
+pub trait Migration: Send + Sync {
+    //migrations run in ascending version order, a version is never reused
+    fn version(&self) -> i64;
+
+    fn name(&self) -> &'static str;
+
+    //returns how many documents changed, or would change on a dry run
+    async fn up(&self, ctx: &MigrationContext<'_>) -> Result<u64, AppError>;
+}
+
+//every migration of the app, new ones are added at the end
+pub fn all() -> Vec<Box<dyn Migration>> {
+    vec![
+        Box::new(m001_normalize_emails::NormalizeEmails),
+    ]
+}
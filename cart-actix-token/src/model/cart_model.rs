@@ -1,6 +1,11 @@
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 use chrono::prelude::*;
+use std::collections::BTreeMap;
+
+use crate::error::AppError;
+
+use super::date_format::optional_bson_datetime;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Cart {
@@ -9,13 +14,52 @@ pub struct Cart {
     #[serde(rename = "_uid", skip_serializing_if = "Option::is_none")]
     pub user_id: Option<String>,
     pub product_name: String,
+    //variant the line refers to, lines without one are priced and stocked by product name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sku: Option<String>,
+    //option values of the variant, e.g. size and color
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<BTreeMap<String, String>>,
+    //free-form customizations such as engraving text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<BTreeMap<String, String>>,
     pub price: f64,
     pub qty: f64,
     #[serde(rename = "_total", skip_serializing_if = "Option::is_none")]
     pub total: Option<f64>,
-    #[serde(rename = "createdAt", skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "createdAt", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
     pub created_at: Option<DateTime<Utc>>,
+    //set on guest cart items only, the TTL index drops them once it passes
+    #[serde(rename = "expiresAt", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    //last change anywhere in the cart, inactive carts are dropped by the TTL index on it
+    #[serde(rename = "updatedAt", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    //set by the abandoned-cart classifier, cleared by the next change
+    #[serde(rename = "abandonedAt", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub abandoned_at: Option<DateTime<Utc>>,
+    //bumped by every change of the line, sent as the ETag and checked against If-Match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i64>,
+    //user or guest who put the line into the cart, shared carts have several
+    #[serde(rename = "addedBy", default, skip_serializing_if = "Option::is_none")]
+    pub added_by: Option<String>,
+    //set when the line is removed, it can be restored for a while and is purged after the retention
+    #[serde(rename = "deletedAt", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+
+}
 
+impl Cart {
+    //inventory key the line draws its stock from
+    pub fn stock_key(&self) -> &str {
+        self.sku.as_deref().unwrap_or(&self.product_name)
+    }
+
+    //lines written before versioning count as version 0
+    pub fn version(&self) -> i64 {
+        self.version.unwrap_or(0)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,3 +67,221 @@ pub struct UpdateCart {
     pub qty: f64
 }
 
+//bulk delete filter, every given criterion has to match
+#[derive(Debug, Deserialize, Clone)]
+pub struct CartDeleteFilter {
+    pub product_name: Option<String>,
+    pub sku: Option<String>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub created_after: Option<DateTime<Utc>>,
+}
+
+impl CartDeleteFilter {
+    //no criterion given, such a filter would match the whole cart
+    pub fn is_empty(&self) -> bool {
+        self.product_name.is_none() && self.sku.is_none() && self.created_before.is_none() && self.created_after.is_none()
+    }
+
+    //whether the line meets every given criterion, for the stores that filter in code
+    pub fn matches(&self, line: &Cart) -> bool {
+        self.product_name.as_ref().is_none_or(|name| *name == line.product_name)
+            && self.sku.as_ref().is_none_or(|sku| line.sku.as_ref() == Some(sku))
+            && self.created_before.is_none_or(|before| line.created_at.is_some_and(|at| at < before))
+            && self.created_after.is_none_or(|after| line.created_at.is_some_and(|at| at >= after))
+    }
+}
+
+//Field a cart listing is sorted by
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CartSort {
+    #[default]
+    CreatedAt,
+    Price,
+    Total,
+}
+
+impl CartSort {
+    //name of the sorted field in the cart collection
+    pub fn field(&self) -> &'static str {
+        match self {
+            CartSort::CreatedAt => "createdAt",
+            CartSort::Price => "price",
+            CartSort::Total => "_total",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+//cart listing query, the cursor is the next_cursor of the previous page
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct CartQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub sort: CartSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    //part of the product name, case insensitive
+    pub product_name: Option<String>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+}
+
+//Pagination metadata of a listing
+#[derive(Debug, Serialize, Clone)]
+pub struct PageInfo {
+    pub limit: i64,
+    pub has_more: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+//One page of cart lines
+#[derive(Debug, Serialize, Clone)]
+pub struct CartPage {
+    pub items: Vec<Cart>,
+    pub page: PageInfo,
+}
+
+//One operation of a cart batch
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum CartOperation {
+    Add(Box<Cart>),
+    Update { id: String, qty: f64 },
+    Remove { id: String },
+}
+
+//cart batch schema, the operations are applied in order, all or nothing
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CartBatch {
+    pub operations: Vec<CartOperation>,
+}
+
+//Outcome of one operation of a cart batch
+#[derive(Debug, Serialize, Clone)]
+pub struct CartOperationResult {
+    pub index: usize,
+    //added, merged, updated or removed
+    pub outcome: String,
+    pub line: Cart,
+}
+
+//Outcome of a cart batch with the cart as it stands afterwards
+#[derive(Debug, Serialize, Clone)]
+pub struct CartBatchResult {
+    pub operations: Vec<CartOperationResult>,
+    pub cart: Vec<Cart>,
+}
+
+//Kind of change found when a cart line is checked against the catalog
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NoticeKind {
+    PriceChanged,
+    InsufficientStock,
+    OutOfStock,
+    Discontinued,
+}
+
+impl NoticeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoticeKind::PriceChanged => "price_changed",
+            NoticeKind::InsufficientStock => "insufficient_stock",
+            NoticeKind::OutOfStock => "out_of_stock",
+            NoticeKind::Discontinued => "discontinued",
+        }
+    }
+}
+
+//Change of a cart line since it was added, it has to be acknowledged before checkout
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CartNotice {
+    //line, kind and new value, a notice for a newer change gets a new id
+    pub id: String,
+    pub cart_id: ObjectId,
+    pub product_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sku: Option<String>,
+    pub kind: NoticeKind,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available: Option<f64>,
+}
+
+//acknowledge schema, the ids of the notices the user accepts
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AcknowledgeNotices {
+    pub notices: Vec<String>,
+}
+
+//Failed conditional write on a cart line, stale carries the current state of the line
+#[derive(Debug)]
+pub enum CartWriteError {
+    Failed(AppError),
+    Stale(Box<Cart>),
+}
+
+impl From<AppError> for CartWriteError {
+    fn from(error: AppError) -> Self {
+        CartWriteError::Failed(error)
+    }
+}
+
+impl From<mongodb::error::Error> for CartWriteError {
+    fn from(error: mongodb::error::Error) -> Self {
+        CartWriteError::Failed(error.into())
+    }
+}
+
+impl From<sqlx::Error> for CartWriteError {
+    fn from(error: sqlx::Error) -> Self {
+        CartWriteError::Failed(error.into())
+    }
+}
+
+impl From<mongodb::bson::oid::Error> for CartWriteError {
+    fn from(error: mongodb::bson::oid::Error) -> Self {
+        CartWriteError::Failed(error.into())
+    }
+}
+
+//Outcome of folding a guest cart into a user cart
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CartMergeResult {
+    //guest items summed into an existing line
+    pub merged: u64,
+    //guest items moved over as new lines
+    pub moved: u64,
+    pub conflicts: Vec<String>,
+}
+
+//Abandoned cart summary, one per user
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AbandonedCart {
+    #[serde(rename = "_id")]
+    pub user_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub items: i64,
+    pub total: f64,
+    #[serde(rename = "updatedAt", default, with = "optional_bson_datetime")]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(rename = "abandonedAt", default, with = "optional_bson_datetime")]
+    pub abandoned_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,31 @@
+//Optional timestamp stored as a BSON date, so TTL indexes and range queries work on it,
+//while JSON responses keep the usual RFC 3339 string
+pub mod optional_bson_datetime {
+    use chrono::{DateTime, Utc};
+    use mongodb::bson::{Bson, DateTime as BsonDateTime};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(date) if serializer.is_human_readable() => serializer.serialize_some(date),
+            Some(date) => serializer.serialize_some(&BsonDateTime::from_chrono(*date)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        if deserializer.is_human_readable() {
+            Option::<DateTime<Utc>>::deserialize(deserializer)
+        } else {
+            //documents written before the field became a BSON date hold an RFC 3339 string
+            match Option::<Bson>::deserialize(deserializer)? {
+                Some(Bson::DateTime(date)) => Ok(Some(date.to_chrono())),
+                Some(Bson::String(text)) => DateTime::parse_from_rfc3339(&text)
+                    .map(|date| Some(date.with_timezone(&Utc)))
+                    .map_err(D::Error::custom),
+                Some(Bson::Null) | None => Ok(None),
+                Some(other) => Err(D::Error::custom(format!("expected a date, found {}", other))),
+            }
+        }
+    }
+}
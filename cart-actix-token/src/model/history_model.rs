@@ -0,0 +1,63 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use chrono::prelude::*;
+
+use super::{cart_model::Cart, date_format::optional_bson_datetime};
+
+//Kind of change recorded in the cart history
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CartAction {
+    Add,
+    Update,
+    Remove,
+    Clear,
+    Restore,
+}
+
+//Append-only record of one change to a cart, never updated once written
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CartEvent {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    //owner of the cart that changed
+    #[serde(rename = "_uid")]
+    pub owner: String,
+    //user or guest who made the change
+    pub actor: String,
+    pub action: CartAction,
+    #[serde(rename = "_cid", skip_serializing_if = "Option::is_none")]
+    pub cart_id: Option<ObjectId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Cart>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Cart>,
+    //lines removed by a clear or bulk delete
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<Cart>,
+    //the retention TTL index works on it
+    #[serde(rename = "createdAt", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl CartEvent {
+    pub fn new(owner: &str, actor: &str, action: CartAction, before: Option<Cart>, after: Option<Cart>) -> Self {
+        CartEvent {
+            id: None,
+            owner: owner.to_owned(),
+            actor: actor.to_owned(),
+            action,
+            cart_id: after.as_ref().or(before.as_ref()).and_then(|line| line.id),
+            before,
+            after,
+            removed: Vec::new(),
+            created_at: Some(Utc::now()),
+        }
+    }
+}
+
+//history retention schema, no days keeps the history forever
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryRetention {
+    pub days: Option<i64>,
+}
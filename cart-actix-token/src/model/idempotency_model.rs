@@ -0,0 +1,35 @@
+use std::collections::BTreeMap;
+
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use chrono::prelude::*;
+
+use super::date_format::optional_bson_datetime;
+
+//how long a claim waits for the first response before a retry may take it over, the handler may
+//have been dropped or its response may have failed to store
+pub const IDEMPOTENCY_LEASE_SECONDS: i64 = 60;
+
+//First response to a request carrying an Idempotency-Key, replayed for repeats of that key
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IdempotencyRecord {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub key: String,
+    //user or guest cart the key belongs to
+    #[serde(rename = "_uid")]
+    pub owner: String,
+    //method, path and body of the first request, a repeat has to match it
+    pub request: String,
+    //unset while the first request is still running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    //the TTL index drops the record once the replay window has passed, a retry taking over an
+    //unfinished claim starts it again
+    #[serde(rename = "createdAt", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
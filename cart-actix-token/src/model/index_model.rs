@@ -0,0 +1,88 @@
+use mongodb::bson::{Bson, Document};
+use serde::Serialize;
+
+//Index the app expects on a collection, synced at startup
+#[derive(Debug, Clone)]
+pub struct IndexSpec {
+    pub collection: String,
+    pub keys: Document,
+    pub unique: bool,
+    //compares strings ignoring case, queries have to use the same collation to hit the index
+    pub case_insensitive: bool,
+    //TTL in seconds, documents are removed once the indexed date is that old
+    pub expire_after: Option<u64>,
+}
+
+impl IndexSpec {
+    pub fn new(collection: &str, keys: Document) -> Self {
+        IndexSpec {
+            collection: collection.to_owned(),
+            keys,
+            unique: false,
+            case_insensitive: false,
+            expire_after: None,
+        }
+    }
+
+    pub fn unique(self) -> Self {
+        IndexSpec { unique: true, ..self }
+    }
+
+    pub fn case_insensitive(self) -> Self {
+        IndexSpec { case_insensitive: true, ..self }
+    }
+
+    pub fn expire_after(self, seconds: u64) -> Self {
+        IndexSpec { expire_after: Some(seconds), ..self }
+    }
+
+    //the name MongoDB gives the index by default, e.g. _uid_1_createdAt_-1
+    pub fn name(&self) -> String {
+        self.keys
+            .iter()
+            .map(|(field, direction)| match direction {
+                Bson::Int32(direction) => format!("{}_{}", field, direction),
+                Bson::Int64(direction) => format!("{}_{}", field, direction),
+                Bson::String(kind) => format!("{}_{}", field, kind),
+                other => format!("{}_{}", field, other),
+            })
+            .collect::<Vec<String>>()
+            .join("_")
+    }
+}
+
+//What the sync does, or would do, with an index
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexAction {
+    //declared but missing
+    Create,
+    //only the TTL differs, changed in place
+    UpdateTtl,
+    //unique or collation differ, dropped and built again
+    Rebuild,
+    //on the collection but not declared, left in place
+    Unmanaged,
+}
+
+impl IndexAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IndexAction::Create => "create",
+            IndexAction::UpdateTtl => "update_ttl",
+            IndexAction::Rebuild => "rebuild",
+            IndexAction::Unmanaged => "unmanaged",
+        }
+    }
+}
+
+//Difference between a declared index and the one on the collection
+#[derive(Debug, Serialize, Clone)]
+pub struct IndexChange {
+    pub collection: String,
+    pub index: String,
+    pub action: IndexAction,
+    pub detail: String,
+    //false when only reported
+    pub applied: bool,
+}
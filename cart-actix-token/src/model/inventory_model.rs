@@ -0,0 +1,42 @@
+use mongodb::bson::{oid::ObjectId, serde_helpers::chrono_datetime_as_bson_datetime};
+use serde::{Deserialize, Serialize};
+use chrono::prelude::*;
+
+//Inventory structure, one document per SKU, products without variants use their name as SKU
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Inventory {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub sku: String,
+    pub product_name: String,
+    //units on hand
+    pub stock: f64,
+    //units on hand minus units held by active reservations
+    pub available: f64,
+    //current catalog price, items moved back into a cart are priced with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+}
+
+//inventory update schema
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateInventory {
+    pub product_name: Option<String>,
+    pub stock: f64,
+    pub price: Option<f64>
+}
+
+//Soft reservation holding stock for a cart item until it expires
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Reservation {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(rename = "_uid")]
+    pub user_id: String,
+    #[serde(rename = "_cid")]
+    pub cart_id: ObjectId,
+    pub sku: String,
+    pub qty: f64,
+    #[serde(rename = "expiresAt", with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
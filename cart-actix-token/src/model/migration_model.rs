@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use chrono::prelude::*;
+
+use super::date_format::optional_bson_datetime;
+
+//Migration that ran against the database, one document per version in _migrations
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AppliedMigration {
+    #[serde(rename = "_id")]
+    pub version: i64,
+    pub name: String,
+    //documents the migration changed
+    pub affected: u64,
+    #[serde(rename = "appliedAt", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+//Lock held while migrations run, so two instances never run them at once
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MigrationLock {
+    #[serde(rename = "_id")]
+    pub id: String,
+    //the run holding the lock, only it may release it
+    pub owner: String,
+    //a lock older than the timeout belongs to a run that died, the next run takes it over
+    #[serde(rename = "lockedAt", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub locked_at: Option<DateTime<Utc>>,
+}
+
+//Outcome of one pending migration, a dry run only counts what it would change
+#[derive(Debug, Serialize, Clone)]
+pub struct MigrationOutcome {
+    pub version: i64,
+    pub name: String,
+    pub affected: u64,
+    pub applied: bool,
+}
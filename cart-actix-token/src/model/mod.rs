@@ -1,3 +1,17 @@
 pub mod cart_model;
+pub mod date_format;
+pub mod history_model;
+pub mod idempotency_model;
+pub mod index_model;
+pub mod migration_model;
+pub mod inventory_model;
+pub mod order_model;
+pub mod outbox_model;
+pub mod payment_model;
+pub mod product_model;
 pub mod response_model;
-pub mod user_model;
\ No newline at end of file
+pub mod share_model;
+pub mod user_model;
+pub mod wishlist_model;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use chrono::prelude::*;
+
+use super::payment_model::Payment;
+
+//Order lifecycle states
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderStatus {
+    Pending,
+    Paid,
+    Fulfilled,
+    Cancelled,
+    Refunded,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Fulfilled => "fulfilled",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Refunded => "refunded",
+        }
+    }
+
+    //allowed moves of the order state machine, cancelled and refunded are final
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        matches!(
+            (self, next),
+            (OrderStatus::Pending, OrderStatus::Paid)
+                | (OrderStatus::Pending, OrderStatus::Cancelled)
+                | (OrderStatus::Paid, OrderStatus::Fulfilled)
+                | (OrderStatus::Paid, OrderStatus::Refunded)
+                | (OrderStatus::Fulfilled, OrderStatus::Refunded)
+        )
+    }
+}
+
+//Postal address structure
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Address {
+    pub name: String,
+    pub line1: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line2: Option<String>,
+    pub city: String,
+    pub postal_code: String,
+    pub country: String,
+}
+
+//Order line, a snapshot of a cart item at checkout
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrderLine {
+    pub product_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sku: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<BTreeMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<BTreeMap<String, String>>,
+    pub price: f64,
+    pub qty: f64,
+    pub discount: f64,
+    pub total: f64,
+}
+
+impl OrderLine {
+    //key of the inventory record the line was taken from
+    pub fn stock_key(&self) -> &str {
+        self.sku.as_deref().unwrap_or(&self.product_name)
+    }
+}
+
+//Order structure, immutable apart from its status
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Order {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(rename = "_uid")]
+    pub user_id: String,
+    pub items: Vec<OrderLine>,
+    pub subtotal: f64,
+    pub discount: f64,
+    pub tax_rate: f64,
+    pub tax: f64,
+    pub total: f64,
+    pub shipping_address: Address,
+    pub billing_address: Address,
+    pub status: OrderStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment: Option<Payment>,
+    #[serde(rename = "createdAt", skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+//checkout request schema
+#[derive(Debug, Deserialize)]
+pub struct CheckoutSchema {
+    pub shipping_address: Address,
+    //defaults to the shipping address
+    pub billing_address: Option<Address>,
+}
@@ -0,0 +1,119 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use chrono::prelude::*;
+
+use super::{cart_model::Cart, date_format::optional_bson_datetime, history_model::{CartAction, CartEvent}};
+
+//Change other services are told about, written to the outbox with the change itself
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum DomainEvent {
+    UserRegistered {
+        user_id: String,
+        email: String,
+        name: String,
+    },
+    //a line added to the cart, before is set when it was summed into an existing line
+    CartItemAdded {
+        owner: String,
+        actor: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        before: Option<Cart>,
+        line: Cart,
+    },
+    CartItemUpdated {
+        owner: String,
+        actor: String,
+        before: Cart,
+        line: Cart,
+    },
+    CartItemRemoved {
+        owner: String,
+        actor: String,
+        line: Cart,
+    },
+    CartItemRestored {
+        owner: String,
+        actor: String,
+        line: Cart,
+    },
+    //lines removed together by a clear or bulk delete
+    CartCleared {
+        owner: String,
+        actor: String,
+        lines: Vec<Cart>,
+    },
+    //the cart was turned into an order and emptied
+    OrderPlaced {
+        user_id: String,
+        order_id: ObjectId,
+        total: f64,
+    },
+}
+
+impl DomainEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DomainEvent::UserRegistered { .. } => "UserRegistered",
+            DomainEvent::CartItemAdded { .. } => "CartItemAdded",
+            DomainEvent::CartItemUpdated { .. } => "CartItemUpdated",
+            DomainEvent::CartItemRemoved { .. } => "CartItemRemoved",
+            DomainEvent::CartItemRestored { .. } => "CartItemRestored",
+            DomainEvent::CartCleared { .. } => "CartCleared",
+            DomainEvent::OrderPlaced { .. } => "OrderPlaced",
+        }
+    }
+}
+
+//every change in the cart history is published, the event is built from the same record
+impl From<&CartEvent> for DomainEvent {
+    fn from(event: &CartEvent) -> Self {
+        let owner = event.owner.to_owned();
+        let actor = event.actor.to_owned();
+        let before = event.before.clone();
+        let after = event.after.clone();
+
+        match (event.action, before, after) {
+            (CartAction::Add, before, Some(line)) => DomainEvent::CartItemAdded { owner, actor, before, line },
+            (CartAction::Update, Some(before), Some(line)) => DomainEvent::CartItemUpdated { owner, actor, before, line },
+            (CartAction::Restore, _, Some(line)) => DomainEvent::CartItemRestored { owner, actor, line },
+            (CartAction::Remove, Some(line), _) => DomainEvent::CartItemRemoved { owner, actor, line },
+            _ => DomainEvent::CartCleared { owner, actor, lines: event.removed.clone() },
+        }
+    }
+}
+
+//Domain event waiting in the outbox, the relay hands it to the sink in the order it was written
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OutboxMessage {
+    //also the key consumers drop repeated deliveries by
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub event: DomainEvent,
+    #[serde(rename = "occurredAt", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub occurred_at: Option<DateTime<Utc>>,
+    //set once the sink took the event, the retention TTL index works on it
+    #[serde(rename = "deliveredAt", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub delivered_at: Option<DateTime<Utc>>,
+    //a relay delivering the event holds it until then, a failed delivery holds it until the retry
+    #[serde(rename = "claimedUntil", default, with = "optional_bson_datetime", skip_serializing_if = "Option::is_none")]
+    pub claimed_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub attempts: i64,
+    #[serde(rename = "lastError", default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl OutboxMessage {
+    pub fn new(event: DomainEvent) -> Self {
+        OutboxMessage {
+            id: None,
+            event,
+            occurred_at: Some(Utc::now()),
+            delivered_at: None,
+            claimed_until: None,
+            attempts: 0,
+            last_error: None,
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+//Payment states reported by a provider
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    //waiting for the provider to report back through a webhook
+    Pending,
+    //the customer has to complete a challenge such as 3-D Secure
+    RequiresAction,
+    Authorized,
+    Captured,
+    Voided,
+    Refunded,
+    Declined,
+}
+
+//Payment attached to an order
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Payment {
+    pub provider: String,
+    pub payment_id: String,
+    pub amount: f64,
+    pub status: PaymentStatus,
+}
+
+//Result of a provider call
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PaymentResult {
+    pub payment_id: String,
+    pub status: PaymentStatus,
+    //where the customer completes a pending challenge
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+//Verified webhook notification
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebhookEvent {
+    pub payment_id: String,
+    pub status: PaymentStatus,
+}
+
+//pay order request schema
+#[derive(Debug, Deserialize)]
+pub struct PayOrderSchema {
+    pub payment_method: String,
+}
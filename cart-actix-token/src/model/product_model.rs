@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use chrono::prelude::*;
+
+//Option axis of a product, e.g. size with S, M and L
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OptionAxis {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+//Variant of a product, one value per axis, its price and stock live in the inventory under its SKU
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Variant {
+    pub sku: String,
+    pub options: BTreeMap<String, String>,
+}
+
+//Priced customization, setting the option on a cart line adds the surcharge to its price
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Customization {
+    pub name: String,
+    pub price: f64,
+}
+
+//Product structure
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Product {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub axes: Vec<OptionAxis>,
+    pub variants: Vec<Variant>,
+    #[serde(default)]
+    pub customizations: Vec<Customization>,
+    //discontinued products stay in the catalog but can no longer be bought
+    #[serde(default = "active_default")]
+    pub active: bool,
+    #[serde(rename = "createdAt", skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+fn active_default() -> bool {
+    true
+}
+
+//variant create schema, price and stock go to the inventory
+#[derive(Debug, Deserialize)]
+pub struct VariantSchema {
+    pub sku: String,
+    pub options: BTreeMap<String, String>,
+    pub price: f64,
+    pub stock: f64,
+}
+
+//product create schema
+#[derive(Debug, Deserialize)]
+pub struct ProductSchema {
+    pub name: String,
+    pub axes: Vec<OptionAxis>,
+    pub variants: Vec<VariantSchema>,
+    #[serde(default)]
+    pub customizations: Vec<Customization>,
+}
+
+//Variant as shown to shoppers, joined with its inventory record
+#[derive(Debug, Serialize, Clone)]
+pub struct VariantView {
+    pub sku: String,
+    pub options: BTreeMap<String, String>,
+    pub price: Option<f64>,
+    pub available: f64,
+}
+
+//Product as shown to shoppers
+#[derive(Debug, Serialize, Clone)]
+pub struct ProductView {
+    #[serde(rename = "_id")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub axes: Vec<OptionAxis>,
+    pub variants: Vec<VariantView>,
+    pub customizations: Vec<Customization>,
+    pub active: bool,
+}
+
+//product status schema
+#[derive(Debug, Deserialize)]
+pub struct ProductStatus {
+    pub active: bool,
+}
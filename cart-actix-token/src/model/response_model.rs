@@ -5,11 +5,4 @@ use serde::{Deserialize, Serialize};
 pub struct ErrorResponse {
     pub message: String,
     pub status: bool
-}
-
-//Success response structure
-#[derive(Serialize, Deserialize, Debug)]
-pub struct SuccessResponse {
-    pub message: String,
-    pub status: bool
 }
\ No newline at end of file
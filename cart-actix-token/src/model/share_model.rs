@@ -0,0 +1,92 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use chrono::prelude::*;
+
+use crate::error::AppError;
+
+//Role of a user on a cart
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareRole {
+    Owner,
+    Editor,
+    Viewer,
+}
+
+impl ShareRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShareRole::Owner => "owner",
+            ShareRole::Editor => "editor",
+            ShareRole::Viewer => "viewer",
+        }
+    }
+}
+
+//Membership of a user in the cart of another, by invited email until the user joins
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CartShare {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    //owner of the shared cart
+    #[serde(rename = "_uid")]
+    pub owner: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub role: ShareRole,
+    #[serde(rename = "createdAt", skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+//invite schema
+#[derive(Debug, Deserialize)]
+pub struct InviteSchema {
+    pub email: String,
+    pub role: ShareRole,
+}
+
+//share link schema
+#[derive(Debug, Deserialize)]
+pub struct ShareLinkSchema {
+    pub role: ShareRole,
+}
+
+//Claims of a signed share link
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShareClaims {
+    //owner of the shared cart
+    pub sub: String,
+    pub role: ShareRole,
+    pub iat: usize,
+    pub exp: usize,
+}
+
+//Resolved access of a caller to a cart, the owner is the `_uid` of its lines
+#[derive(Debug, Clone)]
+pub struct CartAccess {
+    pub owner: String,
+    //user or guest doing the operation
+    pub actor: String,
+    pub role: ShareRole,
+}
+
+impl CartAccess {
+    //access of a user or guest to their own cart
+    pub fn own(user_id: String) -> Self {
+        CartAccess {
+            owner: user_id.to_owned(),
+            actor: user_id,
+            role: ShareRole::Owner,
+        }
+    }
+
+    //viewers may only read the cart
+    pub fn ensure_editor(&self) -> Result<(), AppError> {
+        match self.role {
+            ShareRole::Owner | ShareRole::Editor => Ok(()),
+            ShareRole::Viewer => Err(AppError::Forbidden("Viewers cannot change this cart".to_owned())),
+        }
+    }
+}
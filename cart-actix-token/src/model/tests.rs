@@ -0,0 +1,37 @@
+//unit tests of the model rules
+
+use super::order_model::OrderStatus;
+
+const STATUSES: [OrderStatus; 5] = [
+    OrderStatus::Pending,
+    OrderStatus::Paid,
+    OrderStatus::Fulfilled,
+    OrderStatus::Cancelled,
+    OrderStatus::Refunded,
+];
+
+#[test]
+fn order_moves_only_along_the_state_machine() {
+    let allowed = [
+        (OrderStatus::Pending, OrderStatus::Paid),
+        (OrderStatus::Pending, OrderStatus::Cancelled),
+        (OrderStatus::Paid, OrderStatus::Fulfilled),
+        (OrderStatus::Paid, OrderStatus::Refunded),
+        (OrderStatus::Fulfilled, OrderStatus::Refunded),
+    ];
+
+    //every other pair is rejected, staying on the same status included
+    for from in STATUSES {
+        for to in STATUSES {
+            assert_eq!(from.can_transition_to(to), allowed.contains(&(from, to)), "{} to {}", from.as_str(), to.as_str());
+        }
+    }
+}
+
+#[test]
+fn cancelled_and_refunded_orders_are_final() {
+    for to in STATUSES {
+        assert!(!OrderStatus::Cancelled.can_transition_to(to));
+        assert!(!OrderStatus::Refunded.can_transition_to(to));
+    }
+}
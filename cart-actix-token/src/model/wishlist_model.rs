@@ -0,0 +1,44 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use chrono::prelude::*;
+
+use super::cart_model::Cart;
+
+//Kind of a list kept next to the cart
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListKind {
+    //the single list every user has for items moved out of the cart
+    SavedForLater,
+    //named lists the user creates
+    Wishlist,
+}
+
+impl ListKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListKind::SavedForLater => "saved_for_later",
+            ListKind::Wishlist => "wishlist",
+        }
+    }
+}
+
+//Saved-for-later list or wishlist, items keep the cart item shape and id
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedList {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(rename = "_uid")]
+    pub user_id: String,
+    pub name: String,
+    pub kind: ListKind,
+    pub items: Vec<Cart>,
+    #[serde(rename = "createdAt", skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+//wishlist create schema
+#[derive(Debug, Deserialize)]
+pub struct CreateWishlist {
+    pub name: String,
+}
@@ -0,0 +1,40 @@
+use std::{fs::OpenOptions, io::Write, path::PathBuf};
+
+use async_trait::async_trait;
+
+use crate::error::AppError;
+use crate::model::outbox_model::OutboxMessage;
+
+use super::{envelope, EventSink};
+
+//Sink appending every event as one line of JSON to a file
+#[derive(Debug)]
+pub struct NdjsonFileSink {
+    path: PathBuf,
+}
+
+impl NdjsonFileSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        NdjsonFileSink { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl EventSink for NdjsonFileSink {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn deliver(&self, message: &OutboxMessage) -> Result<(), AppError> {
+        let mut line = envelope(message).to_string();
+        line.push('\n');
+
+        //the line goes out in one write, a reader never sees half of it
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|error| AppError::Internal(format!("Error writing {}: {}", self.path.display(), error)))
+    }
+}
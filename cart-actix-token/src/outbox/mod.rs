@@ -0,0 +1,54 @@
+pub mod file_sink;
+pub mod webhook_sink;
+
+use std::{env, sync::Arc};
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::model::outbox_model::OutboxMessage;
+
+use self::{file_sink::NdjsonFileSink, webhook_sink::WebhookSink};
+
+//Destination the outbox relay delivers domain events to. Delivery is at least once,
+//a sink may see an event again after a crash and consumers drop repeats by its id
+#[async_trait]
+pub trait EventSink: Send + Sync + std::fmt::Debug {
+    //name used in the relay logs
+    fn name(&self) -> &'static str;
+
+    //hand one event over, an error makes the relay try it again later
+    async fn deliver(&self, message: &OutboxMessage) -> Result<(), AppError>;
+}
+
+//JSON every sink sends for an event
+pub fn envelope(message: &OutboxMessage) -> Value {
+    json!({
+        "id": message.id.map(|id| id.to_hex()),
+        "type": message.event.name(),
+        "occurredAt": message.occurred_at,
+        "event": message.event,
+    })
+}
+
+//sink chosen by OUTBOX_SINK: `file` appends to OUTBOX_FILE, `webhook` posts to OUTBOX_WEBHOOK_URL.
+//Without one the relay does not run and the events wait in the outbox
+pub fn sink_from_env() -> Result<Option<Arc<dyn EventSink>>, AppError> {
+    let sink = env::var("OUTBOX_SINK").unwrap_or_default().to_lowercase();
+
+    match sink.as_str() {
+        "" | "off" => Ok(None),
+        "file" => {
+            let path = env::var("OUTBOX_FILE").unwrap_or_else(|_| "outbox.ndjson".to_owned());
+            Ok(Some(Arc::new(NdjsonFileSink::new(path))))
+        },
+        "webhook" => {
+            let url = env::var("OUTBOX_WEBHOOK_URL")
+                .map_err(|_| AppError::Validation("OUTBOX_WEBHOOK_URL is required for the webhook sink".to_owned()))?;
+            let secret = env::var("OUTBOX_WEBHOOK_SECRET").unwrap_or_else(|_| "secret".to_owned());
+            Ok(Some(Arc::new(WebhookSink::new(url, &secret)?)))
+        },
+        other => Err(AppError::Validation(format!("Unknown OUTBOX_SINK {}, expected file, webhook or off", other))),
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::model::outbox_model::OutboxMessage;
+
+use super::{envelope, EventSink};
+
+pub const SIGNATURE_HEADER: &str = "X-Outbox-Signature";
+
+//Signature claims, like the payment webhooks the signature is a JWT over the exact body
+#[derive(Debug, Serialize, Deserialize)]
+struct SignatureClaims {
+    payload: String,
+}
+
+//Sink posting every event as JSON to a URL, any status other than 2xx counts as failed
+#[derive(Debug)]
+pub struct WebhookSink {
+    url: String,
+    secret: String,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(url: String, secret: &str) -> Result<Self, AppError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|error| AppError::Internal(format!("Error building the webhook client: {}", error)))?;
+
+        Ok(WebhookSink {
+            url,
+            secret: secret.to_owned(),
+            client,
+        })
+    }
+
+    //sign a body so the receiver can tell it came from this service
+    fn sign(&self, payload: &str) -> Result<String, AppError> {
+        let claims = SignatureClaims { payload: payload.to_owned() };
+
+        Ok(encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.secret.as_bytes()),
+        )?)
+    }
+}
+
+#[async_trait]
+impl EventSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn deliver(&self, message: &OutboxMessage) -> Result<(), AppError> {
+        let body = envelope(message).to_string();
+        let signature = self.sign(&body)?;
+
+        let response = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .body(body)
+            .send()
+            .await
+            .map_err(|error| AppError::Transient(format!("Error posting to {}: {}", self.url, error)))?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(AppError::Transient(format!("{} answered {}", self.url, status))),
+        }
+    }
+}
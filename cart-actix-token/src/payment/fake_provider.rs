@@ -0,0 +1,256 @@
+use std::{collections::HashMap, sync::Mutex, time::{Duration, Instant}};
+
+use async_trait::async_trait;
+use jsonwebtoken::{encode, decode, Header, EncodingKey, DecodingKey, Validation, Algorithm};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::model::{payment_model::{PaymentResult, PaymentStatus, WebhookEvent}};
+
+use super::PaymentProvider;
+
+//payment methods understood by the fake provider, anything else is approved
+pub const METHOD_DECLINE: &str = "fake_decline";
+pub const METHOD_3DS: &str = "fake_3ds";
+pub const METHOD_3DS_FAIL: &str = "fake_3ds_fail";
+pub const METHOD_DELAYED: &str = "fake_delayed";
+
+//Webhook signature claims, the signature is a JWT over the exact body
+#[derive(Debug, Serialize, Deserialize)]
+struct WebhookClaims {
+    payload: String,
+}
+
+#[derive(Debug)]
+struct FakePayment {
+    method: String,
+    amount: f64,
+    refunded: f64,
+    status: PaymentStatus,
+}
+
+#[derive(Debug)]
+struct QueuedWebhook {
+    deliver_at: Instant,
+    event: WebhookEvent,
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    next_id: u64,
+    payments: HashMap<String, FakePayment>,
+    webhooks: Vec<QueuedWebhook>,
+}
+
+//In-process provider with deterministic outcomes chosen by the payment method
+#[derive(Debug)]
+pub struct FakePaymentProvider {
+    secret: String,
+    webhook_delay: Duration,
+    state: Mutex<FakeState>,
+}
+
+impl FakePaymentProvider {
+    pub fn new(secret: &str, webhook_delay: Duration) -> Self {
+        FakePaymentProvider {
+            secret: secret.to_owned(),
+            webhook_delay,
+            state: Mutex::new(FakeState::default()),
+        }
+    }
+
+    //sign a webhook body the way verify_webhook expects it
+    pub fn sign(&self, payload: &str) -> String {
+        let claims = WebhookClaims { payload: payload.to_owned() };
+
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    //complete the 3-D Secure challenge of a payment, the outcome arrives later as a webhook
+    pub fn confirm(&self, payment_id: &str) -> Result<PaymentResult, AppError> {
+        let mut state = self.state.lock().unwrap();
+        let deliver_at = Instant::now() + self.webhook_delay;
+
+        let payment = match state.payments.get_mut(payment_id) {
+            Some(payment) if payment.status == PaymentStatus::RequiresAction => payment,
+            Some(_) => return Err(fake_error("Payment has no pending challenge")),
+            None => return Err(fake_error("Payment not found")),
+        };
+
+        let outcome = if payment.method == METHOD_3DS_FAIL {
+            PaymentStatus::Declined
+        } else {
+            PaymentStatus::Authorized
+        };
+        payment.status = PaymentStatus::Pending;
+
+        state.webhooks.push(QueuedWebhook {
+            deliver_at,
+            event: WebhookEvent { payment_id: payment_id.to_owned(), status: outcome },
+        });
+
+        Ok(result(payment_id, PaymentStatus::Pending))
+    }
+
+    //take the webhooks whose delay has passed as signed (payload, signature) pairs
+    pub fn due_webhooks(&self) -> Vec<(String, String)> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        let (due, waiting): (Vec<_>, Vec<_>) = state
+            .webhooks
+            .drain(..)
+            .partition(|webhook| webhook.deliver_at <= now);
+        state.webhooks = waiting;
+
+        due.into_iter()
+            .map(|webhook| {
+                if let Some(payment) = state.payments.get_mut(&webhook.event.payment_id) {
+                    payment.status = webhook.event.status;
+                }
+                let payload = serde_json::to_string(&webhook.event).unwrap();
+                let signature = self.sign(&payload);
+                (payload, signature)
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for FakePaymentProvider {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    async fn authorize(&self, _reference: &str, amount: f64, payment_method: &str) -> Result<PaymentResult, AppError> {
+        if amount <= 0.0 {
+            return Err(fake_error("Amount must be greater than zero"));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.next_id += 1;
+        let payment_id = format!("fake_pay_{}", state.next_id);
+
+        let status = match payment_method {
+            METHOD_DECLINE => PaymentStatus::Declined,
+            METHOD_3DS | METHOD_3DS_FAIL => PaymentStatus::RequiresAction,
+            METHOD_DELAYED => {
+                state.webhooks.push(QueuedWebhook {
+                    deliver_at: Instant::now() + self.webhook_delay,
+                    event: WebhookEvent { payment_id: payment_id.to_owned(), status: PaymentStatus::Authorized },
+                });
+                PaymentStatus::Pending
+            },
+            _ => PaymentStatus::Authorized,
+        };
+
+        state.payments.insert(payment_id.to_owned(), FakePayment {
+            method: payment_method.to_owned(),
+            amount,
+            refunded: 0.0,
+            status,
+        });
+
+        let mut response = result(&payment_id, status);
+        match status {
+            PaymentStatus::Declined => response.message = Some("Card declined".to_owned()),
+            PaymentStatus::RequiresAction => response.action_url = Some(format!("/fake-payments/{}/confirm", payment_id)),
+            _ => {}
+        }
+
+        Ok(response)
+    }
+
+    async fn capture(&self, payment_id: &str) -> Result<PaymentResult, AppError> {
+        self.move_payment(payment_id, &[PaymentStatus::Authorized], PaymentStatus::Captured)
+    }
+
+    async fn void(&self, payment_id: &str) -> Result<PaymentResult, AppError> {
+        let voided = self.move_payment(
+            payment_id,
+            &[PaymentStatus::Authorized, PaymentStatus::Pending, PaymentStatus::RequiresAction],
+            PaymentStatus::Voided,
+        )?;
+
+        //a voided payment must not be resurrected by a late webhook
+        let mut state = self.state.lock().unwrap();
+        state.webhooks.retain(|webhook| webhook.event.payment_id != payment_id);
+
+        Ok(voided)
+    }
+
+    async fn refund(&self, payment_id: &str, amount: f64) -> Result<PaymentResult, AppError> {
+        let mut state = self.state.lock().unwrap();
+
+        let payment = match state.payments.get_mut(payment_id) {
+            Some(payment) => payment,
+            None => return Err(fake_error("Payment not found")),
+        };
+
+        if !matches!(payment.status, PaymentStatus::Captured | PaymentStatus::Refunded) {
+            return Err(fake_error("Only captured payments can be refunded"));
+        }
+
+        if amount <= 0.0 || payment.refunded + amount > payment.amount {
+            return Err(fake_error("Refund exceeds the captured amount"));
+        }
+
+        payment.refunded += amount;
+        payment.status = PaymentStatus::Refunded;
+
+        Ok(result(payment_id, PaymentStatus::Refunded))
+    }
+
+    fn verify_webhook(&self, payload: &str, signature: &str) -> Result<WebhookEvent, AppError> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.required_spec_claims.clear();
+        validation.validate_exp = false;
+
+        let claims = decode::<WebhookClaims>(
+            signature,
+            &DecodingKey::from_secret(self.secret.as_bytes()),
+            &validation,
+        )
+        .map_err(|_| fake_error("Invalid webhook signature"))?
+        .claims;
+
+        if claims.payload != payload {
+            return Err(fake_error("Invalid webhook signature"));
+        }
+
+        serde_json::from_str(payload).map_err(|_| fake_error("Invalid webhook payload"))
+    }
+}
+
+impl FakePaymentProvider {
+    fn move_payment(&self, payment_id: &str, from: &[PaymentStatus], to: PaymentStatus) -> Result<PaymentResult, AppError> {
+        let mut state = self.state.lock().unwrap();
+
+        match state.payments.get_mut(payment_id) {
+            Some(payment) if from.contains(&payment.status) => {
+                payment.status = to;
+                Ok(result(payment_id, to))
+            },
+            Some(payment) => Err(fake_error(&format!("Payment is {:?}", payment.status))),
+            None => Err(fake_error("Payment not found")),
+        }
+    }
+}
+
+fn result(payment_id: &str, status: PaymentStatus) -> PaymentResult {
+    PaymentResult {
+        payment_id: payment_id.to_owned(),
+        status,
+        action_url: None,
+        message: None,
+    }
+}
+
+fn fake_error(message: &str) -> AppError {
+    AppError::Payment(message.to_owned())
+}
@@ -0,0 +1,31 @@
+pub mod fake_provider;
+
+#[cfg(test)]
+mod tests;
+
+use async_trait::async_trait;
+
+use crate::error::AppError;
+use crate::model::{payment_model::{PaymentResult, WebhookEvent}};
+
+//Payment gateway operations, orders only move on what the provider reports back
+#[async_trait]
+pub trait PaymentProvider: Send + Sync + std::fmt::Debug {
+    //name stored on the order so payments can be traced back to their gateway
+    fn name(&self) -> &'static str;
+
+    //reserve the amount on the payment method without collecting it
+    async fn authorize(&self, reference: &str, amount: f64, payment_method: &str) -> Result<PaymentResult, AppError>;
+
+    //collect a previously authorized amount
+    async fn capture(&self, payment_id: &str) -> Result<PaymentResult, AppError>;
+
+    //drop an authorization that was never captured
+    async fn void(&self, payment_id: &str) -> Result<PaymentResult, AppError>;
+
+    //give back a captured amount
+    async fn refund(&self, payment_id: &str, amount: f64) -> Result<PaymentResult, AppError>;
+
+    //check the signature of a webhook body and decode the event it carries
+    fn verify_webhook(&self, payload: &str, signature: &str) -> Result<WebhookEvent, AppError>;
+}
@@ -0,0 +1,123 @@
+//unit tests of the fake provider, every outcome is picked by the payment method
+
+use std::time::Duration;
+
+use crate::model::payment_model::PaymentStatus;
+
+use super::{fake_provider::{FakePaymentProvider, METHOD_3DS, METHOD_3DS_FAIL, METHOD_DECLINE, METHOD_DELAYED}, PaymentProvider};
+
+fn provider() -> FakePaymentProvider {
+    FakePaymentProvider::new("secret", Duration::from_secs(0))
+}
+
+//delivers the due webhooks through verify_webhook, returns the reported statuses
+fn delivered(payments: &FakePaymentProvider) -> Vec<PaymentStatus> {
+    payments
+        .due_webhooks()
+        .iter()
+        .map(|(payload, signature)| payments.verify_webhook(payload, signature).unwrap().status)
+        .collect()
+}
+
+#[actix_web::test]
+async fn decline_method_is_declined() {
+    let payments = provider();
+
+    let result = payments.authorize("order", 10.0, METHOD_DECLINE).await.unwrap();
+    assert_eq!(result.status, PaymentStatus::Declined);
+    assert!(result.message.is_some());
+
+    //a declined payment cannot be captured
+    assert!(payments.capture(&result.payment_id).await.is_err());
+}
+
+#[actix_web::test]
+async fn challenge_is_confirmed_through_a_webhook() {
+    let payments = provider();
+
+    let result = payments.authorize("order", 10.0, METHOD_3DS).await.unwrap();
+    assert_eq!(result.status, PaymentStatus::RequiresAction);
+    assert_eq!(result.action_url, Some(format!("/fake-payments/{}/confirm", result.payment_id)));
+
+    let confirmed = payments.confirm(&result.payment_id).unwrap();
+    assert_eq!(confirmed.status, PaymentStatus::Pending);
+    assert_eq!(delivered(&payments), [PaymentStatus::Authorized]);
+
+    //the challenge was answered, there is nothing left to confirm
+    assert!(payments.confirm(&result.payment_id).is_err());
+}
+
+#[actix_web::test]
+async fn failed_challenge_is_declined() {
+    let payments = provider();
+
+    let result = payments.authorize("order", 10.0, METHOD_3DS_FAIL).await.unwrap();
+    payments.confirm(&result.payment_id).unwrap();
+
+    assert_eq!(delivered(&payments), [PaymentStatus::Declined]);
+}
+
+#[actix_web::test]
+async fn delayed_webhook_waits_for_its_delay() {
+    let payments = FakePaymentProvider::new("secret", Duration::from_secs(60));
+
+    let result = payments.authorize("order", 10.0, METHOD_DELAYED).await.unwrap();
+    assert_eq!(result.status, PaymentStatus::Pending);
+    assert!(payments.due_webhooks().is_empty());
+
+    let payments = provider();
+
+    let result = payments.authorize("order", 10.0, METHOD_DELAYED).await.unwrap();
+    assert_eq!(delivered(&payments), [PaymentStatus::Authorized]);
+    assert_eq!(payments.capture(&result.payment_id).await.unwrap().status, PaymentStatus::Captured);
+}
+
+#[actix_web::test]
+async fn void_drops_the_pending_webhook() {
+    let payments = provider();
+
+    let result = payments.authorize("order", 10.0, METHOD_DELAYED).await.unwrap();
+    let voided = payments.void(&result.payment_id).await.unwrap();
+    assert_eq!(voided.status, PaymentStatus::Voided);
+
+    //a late webhook must not bring the payment back
+    assert!(payments.due_webhooks().is_empty());
+    assert!(payments.capture(&result.payment_id).await.is_err());
+}
+
+#[actix_web::test]
+async fn refund_is_limited_to_the_captured_amount() {
+    let payments = provider();
+
+    let result = payments.authorize("order", 10.0, "card").await.unwrap();
+    assert_eq!(result.status, PaymentStatus::Authorized);
+
+    //nothing was collected yet
+    assert!(payments.refund(&result.payment_id, 5.0).await.is_err());
+
+    payments.capture(&result.payment_id).await.unwrap();
+
+    let refunded = payments.refund(&result.payment_id, 6.0).await.unwrap();
+    assert_eq!(refunded.status, PaymentStatus::Refunded);
+    assert!(payments.refund(&result.payment_id, 5.0).await.is_err());
+    assert!(payments.refund(&result.payment_id, 4.0).await.is_ok());
+}
+
+#[actix_web::test]
+async fn webhook_with_a_bad_signature_is_rejected() {
+    let payments = provider();
+
+    let payload = r#"{"payment_id":"fake_pay_1","status":"captured"}"#;
+    let signature = payments.sign(payload);
+    assert!(payments.verify_webhook(payload, &signature).is_ok());
+
+    //the signature covers the exact body
+    let forged = r#"{"payment_id":"fake_pay_2","status":"captured"}"#;
+    assert!(payments.verify_webhook(forged, &signature).is_err());
+
+    //a signature made with another secret
+    let other = FakePaymentProvider::new("other", Duration::from_secs(0));
+    assert!(payments.verify_webhook(payload, &other.sign(payload)).is_err());
+
+    assert!(payments.verify_webhook(payload, "not-a-signature").is_err());
+}
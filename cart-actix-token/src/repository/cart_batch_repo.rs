@@ -0,0 +1,198 @@
+use chrono::Utc;
+use mongodb::{
+    ClientSession,
+    bson::{doc, oid::ObjectId, DateTime as BsonDateTime}};
+
+use crate::error::AppError;
+use crate::model::{history_model::{CartAction, CartEvent}, share_model::CartAccess, cart_model::{Cart, CartBatch, CartBatchResult, CartOperation, CartOperationResult}};
+
+use super::{guest_cart_repo::is_guest_cart, mongodb_repo::MongoRepo, soft_delete_repo::{not_deleted, soft_delete}};
+
+//a failed operation rolls the whole batch back, the message says which one it was
+fn failed_operation(index: usize, error: AppError) -> AppError {
+    let message = format!("Operation {}: {}, no changes were applied", index, error.message());
+    error.with_message(message)
+}
+
+fn line_not_found() -> AppError {
+    AppError::NotFound("Todo Not found".to_owned())
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Cart batch handler function ----------------------------- ////
+
+    //handler to apply a list of add, update and remove operations to the cart in one transaction
+    pub async fn apply_cart_batch(&self, access: &CartAccess, batch: CartBatch) -> Result<CartBatchResult, AppError> {
+        access.ensure_editor()?;
+
+        if batch.operations.is_empty() {
+            return Err(AppError::Validation("Batch has no operations".to_owned()));
+        }
+
+        //price added lines up front, catalog reads need no isolation
+        let mut operations = Vec::new();
+        for (index, operation) in batch.operations.into_iter().enumerate() {
+            match operation {
+                CartOperation::Add(line) => {
+                    let line = self
+                        .resolve_line(*line)
+                        .await
+                        .map_err(|error| failed_operation(index, error))?;
+                    operations.push(CartOperation::Add(Box::new(line)));
+                },
+                operation => operations.push(operation),
+            }
+        }
+
+        let results = self
+            .with_transaction(&(access, operations.as_slice()), |repo, session, &(access, operations)| {
+                Box::pin(repo.apply_cart_operations(session, access, operations))
+            })
+            .await?;
+
+        self.touch_cart(&access.owner).await?;
+
+        Ok(CartBatchResult {
+            operations: results,
+            cart: self.list_all_carts_by_user(access).await?,
+        })
+    }
+
+    async fn apply_cart_operations(&self, session: &mut ClientSession, access: &CartAccess, operations: &[CartOperation]) -> Result<Vec<CartOperationResult>, AppError> {
+        let mut results = Vec::new();
+
+        for (index, operation) in operations.iter().enumerate() {
+            let result = self
+                .apply_cart_operation(session, access, index, operation.clone())
+                .await
+                .map_err(|error| failed_operation(index, error))?;
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+
+    async fn apply_cart_operation(&self, session: &mut ClientSession, access: &CartAccess, index: usize, operation: CartOperation) -> Result<CartOperationResult, AppError> {
+        let user_id = access.owner.as_str();
+
+        match operation {
+            CartOperation::Add(line) => {
+                let line = *line;
+                if line.qty <= 0.0 {
+                    return Err(AppError::Validation("Quantity must be greater than zero".to_owned()));
+                }
+
+                //lines added earlier in the batch are visible through the session
+                let existing = self
+                    .cart_col
+                    .find_one_with_session(self.line_filter(user_id, &line), None, session)
+                    .await?;
+
+                match existing {
+                    Some(existing) => {
+                        let qty = existing.qty + line.qty;
+                        let line = self.set_line_qty(session, existing.clone(), qty, line.price).await?;
+
+                        self.record_cart_event_with_session(CartEvent::new(user_id, &access.actor, CartAction::Add, Some(existing), Some(line.clone())), session).await?;
+
+                        Ok(CartOperationResult { index, outcome: "merged".to_owned(), line })
+                    },
+                    None => {
+                        let data = Cart {
+                            id: Some(ObjectId::new()),
+                            user_id: Some(user_id.to_owned()),
+                            total: Some(line.price * line.qty),
+                            created_at: Some(Utc::now()),
+                            expires_at: is_guest_cart(user_id).then(|| Utc::now() + self.guest_cart_ttl),
+                            updated_at: Some(Utc::now()),
+                            abandoned_at: None,
+                            version: Some(1),
+                            added_by: Some(access.actor.to_owned()),
+                            deleted_at: None,
+                            ..line
+                        };
+
+                        self.hold_line_stock(session, &data, data.qty).await?;
+
+                        self.cart_col
+                            .insert_one_with_session(&data, None, session)
+                            .await?;
+
+                        self.record_cart_event_with_session(CartEvent::new(user_id, &access.actor, CartAction::Add, None, Some(data.clone())), session).await?;
+
+                        Ok(CartOperationResult { index, outcome: "added".to_owned(), line: data })
+                    },
+                }
+            },
+            CartOperation::Update { id, qty } => {
+                let cart_id = ObjectId::parse_str(id).map_err(|_| line_not_found())?;
+
+                let existing = self
+                    .cart_col
+                    .find_one_with_session(doc! {"_id": cart_id, "_uid": user_id, "deletedAt": not_deleted()}, None, session)
+                    .await?
+                    .ok_or_else(line_not_found)?;
+
+                let price = existing.price;
+                let line = self.set_line_qty(session, existing.clone(), qty, price).await?;
+
+                self.record_cart_event_with_session(CartEvent::new(user_id, &access.actor, CartAction::Update, Some(existing), Some(line.clone())), session).await?;
+
+                Ok(CartOperationResult { index, outcome: "updated".to_owned(), line })
+            },
+            CartOperation::Remove { id } => {
+                let cart_id = ObjectId::parse_str(id).map_err(|_| line_not_found())?;
+
+                let line = self
+                    .cart_col
+                    .find_one_and_update_with_session(doc! {"_id": cart_id, "_uid": user_id, "deletedAt": not_deleted()}, soft_delete(), None, session)
+                    .await?
+                    .ok_or_else(line_not_found)?;
+
+                self.hold_line_stock(session, &line, 0.0).await?;
+
+                self.record_cart_event_with_session(CartEvent::new(user_id, &access.actor, CartAction::Remove, Some(line.clone()), None), session).await?;
+
+                Ok(CartOperationResult { index, outcome: "removed".to_owned(), line })
+            },
+        }
+    }
+
+    //handler to set the quantity and price of a line inside the session
+    async fn set_line_qty(&self, session: &mut ClientSession, line: Cart, qty: f64, price: f64) -> Result<Cart, AppError> {
+        if qty <= 0.0 {
+            return Err(AppError::Validation("Quantity must be greater than zero".to_owned()));
+        }
+
+        self.hold_line_stock(session, &line, qty).await?;
+
+        let mut fields = doc! {
+            "qty": qty,
+            "price": price,
+            "_total": price * qty
+        };
+
+        //guest items live on for another full period after every change
+        let expires_at = line.expires_at.map(|_| Utc::now() + self.guest_cart_ttl);
+        if let Some(expires_at) = expires_at {
+            fields.insert("expiresAt", BsonDateTime::from_chrono(expires_at));
+        }
+
+        self.cart_col
+            .update_one_with_session(doc! {"_id": line.id}, doc! {"$set": fields, "$inc": {"version": 1}}, None, session)
+            .await?;
+
+        Ok(Cart {
+            qty,
+            price,
+            total: Some(price * qty),
+            expires_at,
+            version: Some(line.version() + 1),
+            ..line
+        })
+    }
+
+    ////----------------------  END - Cart batch handler function ----------------------------- ////
+
+}
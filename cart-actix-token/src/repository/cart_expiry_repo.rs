@@ -0,0 +1,154 @@
+use chrono::Utc;
+use futures::StreamExt;
+use mongodb::{
+    ClientSession,
+    bson::{doc, from_document, Document, Regex, DateTime as BsonDateTime}};
+
+use crate::error::AppError;
+use crate::model::cart_model::AbandonedCart;
+
+use super::{mongodb_repo::MongoRepo, soft_delete_repo::not_deleted};
+
+//guest carts have nobody to follow up with, the classifier skips them
+fn registered_carts() -> Document {
+    doc! {"$not": Regex { pattern: "^guest:".to_owned(), options: String::new() }}
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Cart expiry handler function ----------------------------- ////
+
+    //handler to mark the whole cart of a user as active, keeping it away from the TTL and the classifier
+    pub async fn touch_cart(&self, user_id: &str) -> Result<(), AppError> {
+        self.cart_col
+            .update_many(
+                doc! {"_uid": user_id},
+                doc! {"$set": {"updatedAt": BsonDateTime::now()}, "$unset": {"abandonedAt": ""}},
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    //same as touch_cart, inside the transaction of the session
+    pub async fn touch_cart_with_session(&self, user_id: &str, session: &mut ClientSession) -> Result<(), AppError> {
+        self.cart_col
+            .update_many_with_session(
+                doc! {"_uid": user_id},
+                doc! {"$set": {"updatedAt": BsonDateTime::now()}, "$unset": {"abandonedAt": ""}},
+                None,
+                session,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    //handler to mark carts idle longer than the abandoned threshold but younger than the TTL,
+    //every newly marked cart is passed to the abandoned cart hook
+    pub async fn classify_abandoned_carts(&self) -> Result<u64, AppError> {
+        let now = Utc::now();
+        let idle_cutoff = BsonDateTime::from_chrono(now - self.abandoned_after);
+        let ttl_cutoff = BsonDateTime::from_chrono(now - self.cart_ttl);
+
+        let pipeline = vec![
+            doc! {"$match": {"_uid": registered_carts(), "abandonedAt": {"$exists": false}, "deletedAt": not_deleted()}},
+            doc! {"$group": {
+                "_id": "$_uid",
+                "updatedAt": {"$max": "$updatedAt"},
+                "items": {"$sum": 1},
+                "total": {"$sum": "$_total"}
+            }},
+            doc! {"$match": {"updatedAt": {"$lt": idle_cutoff, "$gt": ttl_cutoff}}},
+        ];
+
+        let mut idle = self
+            .cart_col
+            .aggregate(pipeline, None)
+            .await?;
+
+        let mut marked = 0;
+
+        while let Some(doc) = idle.next().await {
+            match doc.map(from_document::<AbandonedCart>) {
+                Ok(Ok(mut cart)) => {
+                    //only lines still idle are marked, a change in between keeps the cart active
+                    let result = self
+                        .cart_col
+                        .update_many(
+                            doc! {"_uid": &cart.user_id, "abandonedAt": {"$exists": false}, "deletedAt": not_deleted(), "updatedAt": {"$lt": idle_cutoff}},
+                            doc! {"$set": {"abandonedAt": BsonDateTime::from_chrono(now)}},
+                            None,
+                        )
+                        .await?;
+
+                    if result.modified_count > 0 {
+                        if let Some(user) = self.find_user_by_id(&cart.user_id).await? {
+                            cart.email = Some(user.email);
+                            cart.name = Some(user.name);
+                        }
+                        cart.abandoned_at = Some(now);
+
+                        self.abandoned_hook.on_abandoned(&cart).await;
+                        marked += 1;
+                    }
+                },
+                Ok(Err(err)) => eprintln!("Error reading cart: {:?}", err),
+                Err(err) => eprintln!("Error finding cart: {:?}", err),
+            }
+        }
+
+        Ok(marked)
+    }
+
+    //handler to list the carts currently marked as abandoned
+    pub async fn abandoned_cart_report(&self, token: &str) -> Result<Vec<AbandonedCart>, AppError> {
+        self.validate_admin(token).await?;
+
+        let pipeline = vec![
+            doc! {"$match": {"abandonedAt": {"$exists": true}, "deletedAt": not_deleted()}},
+            doc! {"$group": {
+                "_id": "$_uid",
+                "updatedAt": {"$max": "$updatedAt"},
+                "abandonedAt": {"$max": "$abandonedAt"},
+                "items": {"$sum": 1},
+                "total": {"$sum": "$_total"}
+            }},
+            doc! {"$lookup": {
+                "from": self.u_col.name(),
+                "let": {"uid": {"$convert": {"input": "$_id", "to": "objectId", "onError": null}}},
+                "pipeline": [
+                    {"$match": {"$expr": {"$eq": ["$_id", "$$uid"]}}},
+                    {"$project": {"email": 1, "name": 1}}
+                ],
+                "as": "user"
+            }},
+            doc! {"$set": {
+                "email": {"$first": "$user.email"},
+                "name": {"$first": "$user.name"}
+            }},
+            doc! {"$sort": {"abandonedAt": -1}},
+        ];
+
+        let mut report_doc = self
+            .cart_col
+            .aggregate(pipeline, None)
+            .await?;
+
+        let mut report = Vec::new();
+
+        while let Some(doc) = report_doc.next().await {
+            match doc.map(from_document::<AbandonedCart>) {
+                Ok(Ok(data)) => report.push(data),
+                Ok(Err(err)) => eprintln!("Error reading cart: {:?}", err),
+                Err(err) => eprintln!("Error finding cart: {:?}", err),
+            }
+        }
+
+        Ok(report)
+    }
+
+    ////----------------------  END - Cart expiry handler function ----------------------------- ////
+
+}
@@ -0,0 +1,162 @@
+use futures::StreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, DateTime as BsonDateTime, Document},
+    options::FindOptions};
+
+use crate::error::AppError;
+use crate::model::{share_model::CartAccess, cart_model::{Cart, CartPage, CartQuery, CartSort, PageInfo, SortOrder}};
+
+use super::{mongodb_repo::MongoRepo, soft_delete_repo::not_deleted};
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+fn invalid_query(message: &str) -> AppError {
+    AppError::Validation(message.to_owned())
+}
+
+//value of the sorted field of a line, as stored
+pub(super) fn sort_value(line: &Cart, sort: CartSort) -> Bson {
+    match sort {
+        CartSort::CreatedAt => line.created_at.map(|at| Bson::DateTime(BsonDateTime::from_chrono(at))).unwrap_or(Bson::Null),
+        CartSort::Price => Bson::Double(line.price),
+        CartSort::Total => line.total.map(Bson::Double).unwrap_or(Bson::Null),
+    }
+}
+
+//the cursor is the sort value and id of the last line of a page, hex encoded so clients treat it as opaque
+pub(super) fn encode_cursor(value: Bson, id: ObjectId) -> String {
+    serde_json::json!([value.into_relaxed_extjson(), id.to_hex()])
+        .to_string()
+        .bytes()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub(super) fn decode_cursor(cursor: &str) -> Option<(Bson, ObjectId)> {
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        //an odd trailing digit has no pair and fails the decoding
+        .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    let (value, id): (serde_json::Value, String) = serde_json::from_slice(&bytes).ok()?;
+
+    Some((Bson::try_from(value).ok()?, ObjectId::parse_str(id).ok()?))
+}
+
+//page size asked for, checked against the allowed range
+pub(super) fn page_limit(query: &CartQuery) -> Result<i64, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(invalid_query(&format!("Limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+    Ok(limit)
+}
+
+pub(super) fn invalid_cursor() -> AppError {
+    invalid_query("Invalid cursor")
+}
+
+//lines after the cursor in the listing order, the id breaks ties between equal sort values
+fn after_cursor(field: &str, order: SortOrder, value: Bson, id: ObjectId) -> Document {
+    let (past, id_past) = match order {
+        SortOrder::Asc => ("$gt", doc! {"$gt": id}),
+        SortOrder::Desc => ("$lt", doc! {"$lt": id}),
+    };
+
+    //missing values sort first, comparisons never match them so they are spelled out
+    match (value, order) {
+        (Bson::Null, SortOrder::Asc) => doc! {"$or": [{field: null, "_id": id_past}, {field: {"$ne": null}}]},
+        (Bson::Null, SortOrder::Desc) => doc! {field: null, "_id": id_past},
+        (value, SortOrder::Asc) => doc! {"$or": [{field: {past: &value}}, {field: &value, "_id": id_past}]},
+        (value, SortOrder::Desc) => doc! {"$or": [{field: {past: &value}}, {field: &value, "_id": id_past}, {field: null}]},
+    }
+}
+
+//escape a user given text for use inside a regex
+fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Cart listing handler function ----------------------------- ////
+
+    //handler to list one page of the cart, filtered and sorted, pages follow each other through the cursor
+    pub async fn list_cart_page(&self, access: &CartAccess, query: CartQuery) -> Result<CartPage, AppError> {
+        let limit = page_limit(&query)?;
+
+        let mut filters = vec![doc! {"_uid": &access.owner, "deletedAt": not_deleted()}];
+
+        if let Some(product_name) = query.product_name.as_deref().map(str::trim).filter(|name| !name.is_empty()) {
+            filters.push(doc! {"product_name": {"$regex": escape_regex(product_name), "$options": "i"}});
+        }
+
+        let mut price = doc! {};
+        if let Some(min_price) = query.min_price {
+            price.insert("$gte", min_price);
+        }
+        if let Some(max_price) = query.max_price {
+            price.insert("$lte", max_price);
+        }
+        if !price.is_empty() {
+            filters.push(doc! {"price": price});
+        }
+
+        let field = query.sort.field();
+
+        if let Some(cursor) = &query.cursor {
+            let (value, id) = decode_cursor(cursor).ok_or_else(invalid_cursor)?;
+            filters.push(after_cursor(field, query.order, value, id));
+        }
+
+        let direction = match query.order {
+            SortOrder::Asc => 1,
+            SortOrder::Desc => -1,
+        };
+
+        //one extra line tells whether another page follows
+        let options = FindOptions::builder()
+            .sort(doc! {field: direction, "_id": direction})
+            .limit(limit + 1)
+            .build();
+
+        let mut cart_doc = self
+            .cart_col
+            .find(doc! {"$and": filters}, options)
+            .await?;
+
+        let mut items = Vec::new();
+
+        while let Some(doc) = cart_doc.next().await {
+            match doc {
+                Ok(data) => items.push(data),
+                Err(err) => eprintln!("Error finding cart: {:?}", err),
+            }
+        }
+
+        let has_more = items.len() as i64 > limit;
+        items.truncate(limit as usize);
+
+        let next_cursor = match items.last() {
+            Some(last) if has_more => last.id.map(|id| encode_cursor(sort_value(last, query.sort), id)),
+            _ => None,
+        };
+
+        Ok(CartPage {
+            items,
+            page: PageInfo { limit, has_more, next_cursor },
+        })
+    }
+
+    ////----------------------  END - Cart listing handler function ----------------------------- ////
+
+}
@@ -0,0 +1,209 @@
+use actix_web::cookie::{time::Duration as ActixWebDuration, Cookie};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{encode, Header, EncodingKey, decode, DecodingKey, Validation, Algorithm};
+use mongodb::{
+    ClientSession,
+    bson::{doc, oid::ObjectId}};
+
+use crate::error::AppError;
+use crate::model::{history_model::{CartAction, CartEvent}, user_model::TokenClaims, cart_model::{Cart, CartMergeResult}};
+
+use super::{mongodb_repo::MongoRepo, revalidation_repo::updated_line, soft_delete_repo::{not_deleted, soft_delete}};
+
+//name of the cookie carrying the signed guest cart token
+pub const GUEST_CART_COOKIE: &str = "cart";
+
+//cart owner ids of guest carts start with this prefix, user ids are plain object ids
+const GUEST_PREFIX: &str = "guest:";
+
+//whether a cart owner id belongs to a guest cart
+pub fn is_guest_cart(owner: &str) -> bool {
+    owner.starts_with(GUEST_PREFIX)
+}
+
+//signed token of a new guest cart, valid for the given period
+pub fn issue_guest_token(ttl: Duration) -> String {
+    let jwt_secret = "secret".to_owned();
+
+    let now = Utc::now();
+    let claims = TokenClaims {
+        sub: format!("{}{}", GUEST_PREFIX, ObjectId::new().to_hex()),
+        iat: now.timestamp() as usize,
+        exp: (now + ttl).timestamp() as usize,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(jwt_secret.as_ref()),
+    )
+    .unwrap()
+}
+
+//cookie that hands a guest cart token to the browser
+pub fn guest_cart_cookie(token: &str, ttl: Duration) -> Cookie<'static> {
+    Cookie::build(GUEST_CART_COOKIE, token.to_owned())
+        .path("/")
+        .max_age(ActixWebDuration::seconds(ttl.num_seconds()))
+        .http_only(true)
+        .finish()
+}
+
+//cookie that removes the guest cart token once the cart was merged
+pub fn expired_guest_cart_cookie() -> Cookie<'static> {
+    let mut cookie = Cookie::build(GUEST_CART_COOKIE, "").path("/").finish();
+    cookie.make_removal();
+    cookie
+}
+
+//guest cart id carried by a token, None for user tokens and invalid or expired tokens
+pub fn guest_cart_id(token: &str) -> Option<String> {
+    let secret_key = "secret".to_owned();
+
+    decode::<TokenClaims>(
+        token,
+        &DecodingKey::from_secret(secret_key.as_bytes()),
+        &Validation::new(Algorithm::HS256),
+    )
+    .ok()
+    .map(|decoded| decoded.claims.sub)
+    .filter(|sub| is_guest_cart(sub))
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Guest cart handler function ----------------------------- ////
+
+    //handler to resolve who owns the cart a token points at, a registered user or a guest cart
+    pub async fn cart_owner(&self, token: &str) -> Result<Option<String>, AppError> {
+        if let Some(guest_id) = guest_cart_id(token) {
+            return Ok(Some(guest_id));
+        }
+
+        let user = self.validate_user(token).await?;
+        Ok(user.and_then(|x| x.id).map(|id| id.to_string()))
+    }
+
+    //handler to fold a guest cart into the cart of a user, matching products have their quantities summed.
+    //The lines and their stock move in one transaction, a failure leaves both carts as they were
+    pub async fn merge_guest_cart(&self, guest_token: &str, user_id: &str) -> Result<CartMergeResult, AppError> {
+        let guest_id = match guest_cart_id(guest_token) {
+            Some(guest_id) => guest_id,
+            None => return Ok(CartMergeResult::default()),
+        };
+
+        self.with_transaction(&(guest_id.as_str(), user_id), |repo, session, &(guest_id, user_id)| {
+            Box::pin(repo.merge_guest_lines(session, guest_id, user_id))
+        })
+        .await
+    }
+
+    //moves the lines of a guest cart to the user on the session
+    pub(super) async fn merge_guest_lines(&self, session: &mut ClientSession, guest_id: &str, user_id: &str) -> Result<CartMergeResult, AppError> {
+        let mut result = CartMergeResult::default();
+
+        let mut guest_doc = self
+            .cart_col
+            .find_with_session(doc! {"_uid": guest_id, "deletedAt": not_deleted()}, None, session)
+            .await?;
+
+        let mut guest_items = Vec::new();
+
+        while let Some(doc) = guest_doc.next(session).await {
+            guest_items.push(doc?);
+        }
+
+        for item in guest_items {
+            let item_id = item.id.ok_or_else(|| AppError::Internal("Cart line has no id".to_owned()))?;
+
+            let existing = self
+                .cart_col
+                .find_one_with_session(self.line_filter(user_id, &item), None, session)
+                .await?;
+
+            match existing {
+                Some(line) => {
+                    //hand the guest hold back first so the user line can take those units over
+                    self.hold_line_stock(session, &item, 0.0).await?;
+
+                    //a shortage is reported and the merge goes on, anything else rolls it back
+                    match self.merge_into_line(session, &line, &item).await {
+                        Ok(merged) => {
+                            if merged.qty < line.qty + item.qty {
+                                result.conflicts.push(format!("{}: kept {} of {} requested, not enough stock", item.product_name, merged.qty, line.qty + item.qty));
+                            } else {
+                                result.merged += 1;
+                            }
+
+                            self.record_cart_event_with_session(CartEvent::new(user_id, user_id, CartAction::Add, Some(line), Some(merged)), session).await?;
+                        },
+                        Err(error @ (AppError::Conflict(_) | AppError::NotFound(_))) => result.conflicts.push(format!("{}: {}", item.product_name, error.message())),
+                        Err(error) => return Err(error),
+                    }
+
+                    self.cart_col
+                        .update_one_with_session(doc! {"_id": item_id, "deletedAt": not_deleted()}, soft_delete(), None, session)
+                        .await?;
+
+                    self.record_cart_event_with_session(CartEvent::new(guest_id, user_id, CartAction::Remove, Some(item), None), session).await?;
+                },
+                None => {
+                    let moved = self
+                        .cart_col
+                        .find_one_and_update_with_session(
+                            doc! {"_id": item_id},
+                            doc! {"$set": {"_uid": user_id}, "$unset": {"expiresAt": ""}, "$inc": {"version": 1}},
+                            updated_line(),
+                            session,
+                        )
+                        .await?;
+
+                    self.reservation_col
+                        .update_one_with_session(doc! {"_cid": item_id}, doc! {"$set": {"_uid": user_id}}, None, session)
+                        .await?;
+
+                    //the line leaves the guest cart and arrives in the user cart, each history tells its side
+                    self.record_cart_event_with_session(CartEvent::new(guest_id, user_id, CartAction::Remove, Some(item), None), session).await?;
+                    self.record_cart_event_with_session(CartEvent::new(user_id, user_id, CartAction::Add, None, moved), session).await?;
+
+                    result.moved += 1;
+                }
+            }
+        }
+
+        self.touch_cart_with_session(user_id, session).await?;
+
+        Ok(result)
+    }
+
+    //sum a guest item into the matching user line on the session, falling back to the larger single quantity when stock is short
+    async fn merge_into_line(&self, session: &mut ClientSession, line: &Cart, item: &Cart) -> Result<Cart, AppError> {
+        //the line added last carries the price the customer saw most recently
+        let price = if item.created_at > line.created_at { item.price } else { line.price };
+
+        let wanted = line.qty + item.qty;
+
+        let qty = match self.hold_line_stock(session, line, wanted).await {
+            Ok(()) => wanted,
+            Err(AppError::Conflict(_)) => {
+                let qty = line.qty.max(item.qty);
+                self.hold_line_stock(session, line, qty).await?;
+                qty
+            },
+            Err(error) => return Err(error),
+        };
+
+        self.cart_col
+            .find_one_and_update_with_session(
+                doc! {"_id": line.id, "deletedAt": not_deleted()},
+                doc! {"$set": {"qty": qty, "price": price, "_total": price * qty}, "$inc": {"version": 1}},
+                updated_line(),
+                session,
+            )
+            .await?
+            .ok_or_else(|| AppError::NotFound("Todo Not found".to_owned()))
+    }
+
+    ////----------------------  END - Guest cart handler function ----------------------------- ////
+
+}
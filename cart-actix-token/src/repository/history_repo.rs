@@ -0,0 +1,135 @@
+use futures::StreamExt;
+use mongodb::{
+    ClientSession,
+    IndexModel,
+    bson::doc,
+    options::{FindOptions, IndexOptions}};
+
+use crate::error::AppError;
+use crate::model::{history_model::{CartEvent, HistoryRetention}, outbox_model::DomainEvent, share_model::CartAccess};
+
+use super::mongodb_repo::MongoRepo;
+
+//name of the single field TTL index that enforces the retention policy
+const RETENTION_INDEX: &str = "createdAt_1";
+
+impl MongoRepo {
+
+    ////----------------------  START - Cart history handler function ----------------------------- ////
+
+    //handler to append a change to the cart history inside a transaction, it commits or aborts with the change.
+    //The change is published to the outbox in the same transaction
+    pub async fn record_cart_event_with_session(&self, event: CartEvent, session: &mut ClientSession) -> Result<(), AppError> {
+        let published = DomainEvent::from(&event);
+
+        self.history_col
+            .insert_one_with_session(event, None, session)
+            .await?;
+
+        self.publish_with_session(published, session).await
+    }
+
+    async fn find_cart_events(&self, owner: &str) -> Result<Vec<CartEvent>, AppError> {
+        let options = FindOptions::builder()
+            .sort(doc! {"createdAt": -1})
+            .build();
+
+        let mut history_doc = self
+            .history_col
+            .find(doc! {"_uid": owner}, options)
+            .await?;
+
+        let mut history_vec = Vec::new();
+
+        while let Some(doc) = history_doc.next().await {
+            match doc {
+                Ok(data) => history_vec.push(data),
+                Err(err) => eprintln!("Error finding cart history: {:?}", err),
+            }
+        }
+
+        Ok(history_vec)
+    }
+
+    //handler to list the history of a cart, newest change first
+    pub async fn cart_history(&self, access: &CartAccess) -> Result<Vec<CartEvent>, AppError> {
+        self.find_cart_events(&access.owner).await
+    }
+
+    //handler to list the history of the cart of any user, admins only
+    pub async fn admin_cart_history(&self, token: &str, user_id: &str) -> Result<Vec<CartEvent>, AppError> {
+        self.validate_admin(token).await?;
+        self.find_cart_events(user_id).await
+    }
+
+    //handler to read the retention policy from the TTL index
+    pub async fn history_retention(&self, token: &str) -> Result<HistoryRetention, AppError> {
+        self.validate_admin(token).await?;
+
+        let mut indexes = self
+            .history_col
+            .list_indexes(None)
+            .await?;
+
+        while let Some(Ok(index)) = indexes.next().await {
+            let ttl = index
+                .options
+                .filter(|options| options.name.as_deref() == Some(RETENTION_INDEX))
+                .and_then(|options| options.expire_after);
+
+            if let Some(ttl) = ttl {
+                return Ok(HistoryRetention { days: Some((ttl.as_secs() / 86400) as i64) });
+            }
+        }
+
+        Ok(HistoryRetention { days: None })
+    }
+
+    //handler to set how many days history is kept, none keeps it forever
+    pub async fn set_history_retention(&self, token: &str, days: Option<i64>) -> Result<HistoryRetention, AppError> {
+        self.validate_admin(token).await?;
+
+        match days {
+            Some(days) if days <= 0 => {
+                return Err(AppError::Validation("Retention must be at least one day".to_owned()));
+            },
+            Some(days) => {
+                let seconds = days * 86400;
+
+                //adjust the TTL in place, build the index when there is none yet
+                let adjusted = self
+                    .client
+                    .database(self.history_col.namespace().db.as_str())
+                    .run_command(doc! {
+                        "collMod": self.history_col.name(),
+                        "index": {"name": RETENTION_INDEX, "expireAfterSeconds": seconds}
+                    }, None)
+                    .await;
+
+                if adjusted.is_err() {
+                    self.history_col
+                        .create_index(
+                            IndexModel::builder()
+                                .keys(doc! {"createdAt": 1})
+                                .options(IndexOptions::builder()
+                                    .name(RETENTION_INDEX.to_owned())
+                                    .expire_after(std::time::Duration::from_secs(seconds as u64))
+                                    .build())
+                                .build(),
+                            None,
+                        )
+                        .await?;
+                }
+            },
+            None => {
+                //no index means nothing to drop, the history is already kept forever
+                let _ = self.history_col.drop_index(RETENTION_INDEX, None).await;
+            },
+        }
+
+        Ok(HistoryRetention { days })
+    }
+
+    ////----------------------  END - Cart history handler function ----------------------------- ////
+
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, Utc};
+use mongodb::{bson::{doc, DateTime as BsonDateTime}, error::{ErrorKind, WriteFailure}};
+
+use crate::{error::AppError, model::idempotency_model::{IdempotencyRecord, IDEMPOTENCY_LEASE_SECONDS}};
+
+use super::mongodb_repo::MongoRepo;
+
+//duplicate key on the unique key and owner index, the key was seen before
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    matches!(error.kind.as_ref(), ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == 11000)
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Idempotency handler function ----------------------------- ////
+
+    //handler to claim an idempotency key for a request, returns the earlier record when the key was already used
+    pub async fn claim_idempotency_key(&self, key: &str, owner: &str, request: &str) -> Result<Option<IdempotencyRecord>, AppError> {
+        let now = Utc::now();
+        let data = IdempotencyRecord {
+            id: None,
+            key: key.to_owned(),
+            owner: owner.to_owned(),
+            request: request.to_owned(),
+            status: None,
+            headers: BTreeMap::new(),
+            body: None,
+            created_at: Some(now),
+        };
+
+        match self.idempotency_col.insert_one(data, None).await {
+            Ok(_) => return Ok(None),
+            Err(error) if is_duplicate_key(&error) => {},
+            Err(error) => return Err(error.into()),
+        }
+
+        //a claim that never got its response is taken over once its lease ran out
+        let lease = BsonDateTime::from_chrono(now - Duration::seconds(IDEMPOTENCY_LEASE_SECONDS));
+        let taken_over = self
+            .idempotency_col
+            .update_one(
+                doc! {"key": key, "_uid": owner, "status": {"$exists": false}, "createdAt": {"$lt": lease}},
+                doc! {"$set": {"request": request, "createdAt": BsonDateTime::from_chrono(now)}},
+                None,
+            )
+            .await?;
+
+        if taken_over.modified_count == 1 {
+            return Ok(None);
+        }
+
+        Ok(self
+            .idempotency_col
+            .find_one(doc! {"key": key, "_uid": owner}, None)
+            .await?)
+    }
+
+    //handler to store the response of a claimed key for replay
+    pub async fn complete_idempotency_key(&self, key: &str, owner: &str, status: u16, headers: BTreeMap<String, String>, body: String) -> Result<(), AppError> {
+        self.idempotency_col
+            .update_one(
+                doc! {"key": key, "_uid": owner},
+                doc! {"$set": {"status": status as i32, "headers": mongodb::bson::to_bson(&headers)?, "body": body}},
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    //handler to give a key up again, the request failed on our side and may be retried
+    pub async fn release_idempotency_key(&self, key: &str, owner: &str) -> Result<(), AppError> {
+        self.idempotency_col
+            .delete_one(doc! {"key": key, "_uid": owner, "status": {"$exists": false}}, None)
+            .await?;
+
+        Ok(())
+    }
+
+    ////----------------------  END - Idempotency handler function ----------------------------- ////
+
+}
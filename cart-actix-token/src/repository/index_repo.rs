@@ -0,0 +1,229 @@
+use futures::StreamExt;
+use mongodb::{
+    Collection,
+    IndexModel,
+    bson::{doc, Document},
+    error::{Error, ErrorKind, WriteFailure},
+    options::{Collation, CollationStrength, IndexOptions}};
+
+use crate::error::AppError;
+use crate::model::index_model::{IndexAction, IndexChange, IndexSpec};
+
+use super::mongodb_repo::MongoRepo;
+
+//collation of case-insensitive indexes, strength 2 ignores case but not accents
+pub(super) fn case_insensitive() -> Collation {
+    Collation::builder()
+        .locale("en".to_owned())
+        .strength(CollationStrength::Secondary)
+        .build()
+}
+
+//whether a write failed on a unique index
+pub(super) fn is_duplicate_key(error: &Error) -> bool {
+    matches!(&*error.kind, ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == 11000)
+}
+
+//whether a unique index could not be built because documents already hold the same value
+fn is_duplicate_build(error: &Error) -> bool {
+    matches!(&*error.kind, ErrorKind::Command(e) if e.code == 11000)
+}
+
+fn index_model(spec: &IndexSpec) -> IndexModel {
+    let mut options = IndexOptions::default();
+    options.name = Some(spec.name());
+    options.unique = spec.unique.then_some(true);
+    options.collation = spec.case_insensitive.then(case_insensitive);
+    options.expire_after = spec.expire_after.map(std::time::Duration::from_secs);
+
+    IndexModel::builder()
+        .keys(spec.keys.clone())
+        .options(options)
+        .build()
+}
+
+fn index_name(index: &IndexModel) -> String {
+    index
+        .options
+        .as_ref()
+        .and_then(|options| options.name.to_owned())
+        .unwrap_or_default()
+}
+
+//what has to change for the index on the collection to match the declared one
+fn index_difference(spec: &IndexSpec, index: &IndexModel) -> Option<(IndexAction, String)> {
+    let options = index.options.clone().unwrap_or_default();
+
+    let unique = options.unique.unwrap_or(false);
+    if unique != spec.unique {
+        return Some((IndexAction::Rebuild, format!("unique is {}, declared {}", unique, spec.unique)));
+    }
+
+    let case_insensitive = options
+        .collation
+        .as_ref()
+        .is_some_and(|collation| collation.locale == "en" && matches!(collation.strength, Some(CollationStrength::Secondary)));
+    if case_insensitive != spec.case_insensitive {
+        return Some((IndexAction::Rebuild, format!("case insensitive is {}, declared {}", case_insensitive, spec.case_insensitive)));
+    }
+
+    let expire_after = options.expire_after.map(|ttl| ttl.as_secs());
+    match (expire_after, spec.expire_after) {
+        (Some(current), Some(declared)) if current != declared => {
+            Some((IndexAction::UpdateTtl, format!("TTL is {}s, declared {}s", current, declared)))
+        },
+        (current, declared) if current.is_some() != declared.is_some() => {
+            Some((IndexAction::Rebuild, format!("TTL is {:?}, declared {:?}", current, declared)))
+        },
+        _ => None,
+    }
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Index handler function ----------------------------- ////
+
+    //indexes the collections need, the TTLs follow the configuration
+    pub fn declared_indexes(&self) -> Vec<IndexSpec> {
+        let cart = self.cart_col.name();
+        let idempotency = self.idempotency_col.name();
+
+        vec![
+            //register relies on it, two sign ups with the same email differing in case cannot both win
+            IndexSpec::new(self.u_col.name(), doc! {"email": 1}).unique().case_insensitive(),
+            IndexSpec::new(cart, doc! {"_uid": 1, "createdAt": 1}),
+            //guest items carry expiresAt, the TTL index removes them once it passes
+            IndexSpec::new(cart, doc! {"expiresAt": 1}).expire_after(0),
+            //every change touches updatedAt on all lines of the cart, so the whole cart expires together
+            IndexSpec::new(cart, doc! {"updatedAt": 1}).expire_after(self.cart_ttl.num_seconds() as u64),
+            //restore and the purge look removed lines up by when they were removed
+            IndexSpec::new(cart, doc! {"deletedAt": 1}),
+            IndexSpec::new(self.order_col.name(), doc! {"_uid": 1, "createdAt": 1}),
+            IndexSpec::new(self.list_col.name(), doc! {"_uid": 1}),
+            IndexSpec::new(self.share_col.name(), doc! {"_uid": 1}),
+            IndexSpec::new(self.reservation_col.name(), doc! {"_cid": 1}),
+            //history is read per cart, newest first
+            IndexSpec::new(self.history_col.name(), doc! {"_uid": 1, "createdAt": -1}),
+            //a key belongs to one user or guest cart
+            IndexSpec::new(idempotency, doc! {"key": 1, "_uid": 1}).unique(),
+            IndexSpec::new(idempotency, doc! {"createdAt": 1}).expire_after(self.idempotency_ttl.num_seconds() as u64),
+            //the relay looks for undelivered events, delivered ones are dropped after the retention
+            IndexSpec::new(self.outbox_col.name(), doc! {"deliveredAt": 1}).expire_after(self.outbox_retention.num_seconds() as u64),
+        ]
+    }
+
+    //handler to compare the declared indexes with the ones on the collections,
+    //the differences are applied unless report_only is set. Undeclared indexes are only reported
+    pub async fn sync_indexes(&self, report_only: bool) -> Result<Vec<IndexChange>, AppError> {
+        let declared = self.declared_indexes();
+
+        let mut collections: Vec<&str> = Vec::new();
+        for spec in &declared {
+            if !collections.contains(&spec.collection.as_str()) {
+                collections.push(&spec.collection);
+            }
+        }
+
+        let mut changes = Vec::new();
+
+        for collection in collections {
+            let col = self.db.collection::<Document>(collection);
+            let existing = self.list_index_models(&col).await?;
+            let specs: Vec<&IndexSpec> = declared.iter().filter(|spec| spec.collection == collection).collect();
+
+            for spec in &specs {
+                let name = spec.name();
+                let difference = match existing.iter().find(|index| index_name(index) == name) {
+                    Some(index) => index_difference(spec, index),
+                    None => Some((IndexAction::Create, "missing".to_owned())),
+                };
+
+                if let Some((action, mut detail)) = difference {
+                    let mut applied = false;
+
+                    //duplicates in the data are reported, the server keeps running without the index
+                    if !report_only {
+                        match self.apply_index_change(&col, spec, action).await {
+                            Ok(()) => applied = true,
+                            Err(error) if is_duplicate_build(&error) => {
+                                detail = format!("{}, cannot be built while documents share a value: {}", detail, error);
+                            },
+                            Err(error) => return Err(error.into()),
+                        }
+                    }
+
+                    changes.push(IndexChange {
+                        collection: collection.to_owned(),
+                        index: name,
+                        action,
+                        detail,
+                        applied,
+                    });
+                }
+            }
+
+            for index in &existing {
+                let name = index_name(index);
+                if name != "_id_" && !specs.iter().any(|spec| spec.name() == name) {
+                    changes.push(IndexChange {
+                        collection: collection.to_owned(),
+                        index: name,
+                        action: IndexAction::Unmanaged,
+                        detail: "not declared, left in place".to_owned(),
+                        applied: false,
+                    });
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+
+    //handler to report the index differences to an admin without applying them
+    pub async fn index_report(&self, token: &str) -> Result<Vec<IndexChange>, AppError> {
+        self.validate_admin(token).await?;
+        self.sync_indexes(true).await
+    }
+
+    async fn list_index_models(&self, col: &Collection<Document>) -> Result<Vec<IndexModel>, AppError> {
+        let mut cursor = match col.list_indexes(None).await {
+            Ok(cursor) => cursor,
+            //the collection is created with its first index
+            Err(error) if matches!(&*error.kind, ErrorKind::Command(e) if e.code == 26) => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut indexes = Vec::new();
+        while let Some(index) = cursor.next().await {
+            indexes.push(index?);
+        }
+
+        Ok(indexes)
+    }
+
+    async fn apply_index_change(&self, col: &Collection<Document>, spec: &IndexSpec, action: IndexAction) -> Result<(), Error> {
+        match action {
+            IndexAction::Create => {
+                col.create_index(index_model(spec), None).await?;
+            },
+            IndexAction::UpdateTtl => {
+                self.db
+                    .run_command(doc! {
+                        "collMod": col.name(),
+                        "index": {"name": spec.name(), "expireAfterSeconds": spec.expire_after.unwrap_or_default() as i64}
+                    }, None)
+                    .await?;
+            },
+            IndexAction::Rebuild => {
+                col.drop_index(spec.name(), None).await?;
+                col.create_index(index_model(spec), None).await?;
+            },
+            IndexAction::Unmanaged => {},
+        }
+
+        Ok(())
+    }
+
+    ////----------------------  END - Index handler function ----------------------------- ////
+
+}
@@ -0,0 +1,338 @@
+use chrono::Utc;
+use futures::StreamExt;
+use mongodb::{
+    ClientSession,
+    bson::{doc, oid::ObjectId, DateTime as BsonDateTime}};
+
+use crate::error::AppError;
+use crate::model::{cart_model::Cart, inventory_model::{Inventory, Reservation}};
+
+use super::{mongodb_repo::MongoRepo, revalidation_repo::updated_line, transaction_repo::find_one_on};
+
+impl MongoRepo {
+
+    ////----------------------  START - Inventory handler function ----------------------------- ////
+
+    //handler to find the stock record of a SKU
+    pub async fn find_inventory(&self, sku: &str) -> Result<Option<Inventory>, AppError> {
+        let inventory = self
+            .inventory_col
+            .find_one(doc! {"sku": sku}, None)
+            .await?;
+
+        Ok(inventory)
+    }
+
+    //handler to set the stock on hand and optionally the price of a SKU, units held by reservations stay held
+    pub async fn set_inventory(&self, sku: &str, product_name: Option<&str>, stock: f64, price: Option<f64>) -> Result<Inventory, AppError> {
+        if stock < 0.0 {
+            return Err(AppError::Validation("Stock cannot be negative".to_owned()));
+        }
+
+        if price.is_some_and(|price| price < 0.0) {
+            return Err(AppError::Validation("Price cannot be negative".to_owned()));
+        }
+
+        //shift available by the same delta as stock in one atomic pipeline update
+        let mut fields = doc! {
+            "available": {"$add": ["$available", {"$subtract": [stock, "$stock"]}]},
+            "stock": stock
+        };
+        if let Some(price) = price {
+            fields.insert("price", price);
+        }
+        if let Some(product_name) = product_name {
+            fields.insert("product_name", product_name);
+        }
+        let pipeline = vec![doc! {"$set": fields}];
+
+        let updated = self
+            .inventory_col
+            .find_one_and_update(doc! {"sku": sku}, pipeline, updated_line())
+            .await?;
+
+        match updated {
+            Some(inventory) => Ok(inventory),
+            None => {
+                let data = Inventory {
+                    id: None,
+                    sku: sku.to_owned(),
+                    product_name: product_name.unwrap_or(sku).to_owned(),
+                    stock,
+                    available: stock,
+                    price,
+                };
+
+                let inserted = self
+                    .inventory_col
+                    .insert_one(data.clone(), None)
+                    .await?;
+
+                Ok(Inventory { id: inserted.inserted_id.as_object_id(), ..data })
+            }
+        }
+    }
+
+    //handler to find the current catalog price of a SKU, read on the session when one is given
+    pub(super) async fn current_price_on(&self, session: Option<&mut ClientSession>, sku: &str) -> Option<f64> {
+        find_one_on(&self.inventory_col, doc! {"sku": sku}, session)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|inventory| inventory.price)
+    }
+
+    //handler to check that enough unreserved stock exists for the requested quantity
+    pub async fn check_stock(&self, sku: &str, qty: f64) -> Result<(), AppError> {
+        if qty <= 0.0 {
+            return Err(AppError::Validation("Quantity must be greater than zero".to_owned()));
+        }
+
+        match self.find_inventory(sku).await? {
+            Some(inventory) if inventory.available >= qty => Ok(()),
+            Some(inventory) => Err(AppError::Conflict(format!("Insufficient stock for {}: {} available", sku, inventory.available.max(0.0)))),
+            None => Err(AppError::NotFound("Product not found in inventory".to_owned())),
+        }
+    }
+
+    //handler to take units out of the available stock, the conditional decrement never oversells
+    pub async fn reserve_stock(&self, sku: &str, qty: f64) -> Result<(), AppError> {
+        if qty <= 0.0 {
+            return Err(AppError::Validation("Quantity must be greater than zero".to_owned()));
+        }
+
+        let reserved = self
+            .inventory_col
+            .find_one_and_update(
+                doc! {"sku": sku, "available": {"$gte": qty}},
+                doc! {"$inc": {"available": -qty}},
+                None,
+            )
+            .await?;
+
+        match reserved {
+            Some(_) => Ok(()),
+            //nothing matched, report whether the product is unknown or just short
+            None => self.check_stock(sku, qty).await,
+        }
+    }
+
+    //handler to give units back to the available stock
+    pub async fn release_stock(&self, sku: &str, qty: f64) -> Result<(), AppError> {
+        self.inventory_col
+            .update_one(
+                doc! {"sku": sku},
+                doc! {"$inc": {"available": qty}},
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    ////----------------------  END - Inventory handler function ----------------------------- ////
+
+
+
+    ////----------------------  START - Reservation handler function ----------------------------- ////
+
+    //handler to record the hold for a cart item whose stock was already reserved
+    pub async fn create_reservation(&self, user_id: &str, cart_id: ObjectId, sku: &str, qty: f64) -> Result<(), AppError> {
+        if let Some(ttl) = self.reservation_ttl {
+            let data = Reservation {
+                id: None,
+                user_id: user_id.to_owned(),
+                cart_id,
+                sku: sku.to_owned(),
+                qty,
+                expires_at: Utc::now() + ttl,
+            };
+
+            self.reservation_col
+                .insert_one(data, None)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    //handler to move the hold of a cart item to its new quantity
+    pub async fn adjust_reservation(&self, cart: &Cart, new_qty: f64) -> Result<(), AppError> {
+        let ttl = match self.reservation_ttl {
+            Some(ttl) => ttl,
+            None => return self.check_stock(cart.stock_key(), new_qty).await,
+        };
+
+        if new_qty <= 0.0 {
+            return self.check_stock(cart.stock_key(), new_qty).await;
+        }
+
+        let cart_id = cart.id.ok_or_else(|| AppError::Internal("Cart line has no id".to_owned()))?;
+        let user_id = cart.user_id.to_owned().unwrap_or_default();
+
+        let current = self
+            .reservation_col
+            .find_one(doc! {"_cid": cart_id}, None)
+            .await?;
+
+        let held = current.as_ref().map(|r| r.qty).unwrap_or(0.0);
+        let delta = new_qty - held;
+
+        if delta > 0.0 {
+            self.reserve_stock(cart.stock_key(), delta).await?;
+        }
+
+        let refreshed = match current {
+            Some(_) => self
+                .reservation_col
+                .find_one_and_update(
+                    doc! {"_cid": cart_id},
+                    doc! {
+                        "$inc": {"qty": delta},
+                        "$set": {"expiresAt": BsonDateTime::from_chrono(Utc::now() + ttl)}
+                    },
+                    None,
+                )
+                .await?,
+            None => None,
+        };
+
+        match refreshed {
+            Some(_) => {
+                if delta < 0.0 {
+                    self.release_stock(cart.stock_key(), -delta).await?;
+                }
+            }
+            None => {
+                //the old hold expired meanwhile and its units went back, hold the full quantity again
+                if current.is_some() {
+                    if delta > 0.0 {
+                        self.release_stock(cart.stock_key(), delta).await?;
+                    }
+                    self.reserve_stock(cart.stock_key(), new_qty).await?;
+                }
+                self.create_reservation(&user_id, cart_id, cart.stock_key(), new_qty).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    //handler to drop the hold of a cart item and return its units
+    pub async fn release_reservation(&self, cart_id: &ObjectId) -> Result<(), AppError> {
+        let reservation = self
+            .reservation_col
+            .find_one_and_delete(doc! {"_cid": cart_id}, None)
+            .await?;
+
+        if let Some(r) = reservation {
+            self.release_stock(&r.sku, r.qty).await?;
+        }
+
+        Ok(())
+    }
+
+    //handler to move the stock held for a line to the given quantity inside the session, zero releases it.
+    //Nothing is written before the stock is taken, so a shortage leaves the transaction usable
+    pub(super) async fn hold_line_stock(&self, session: &mut ClientSession, line: &Cart, qty: f64) -> Result<(), AppError> {
+        let ttl = match self.reservation_ttl {
+            Some(ttl) => ttl,
+            None if qty > 0.0 => return self.check_stock(line.stock_key(), qty).await,
+            None => return Ok(()),
+        };
+
+        let cart_id = line.id.ok_or_else(|| AppError::Internal("Cart line has no id".to_owned()))?;
+
+        let held = self
+            .reservation_col
+            .find_one_with_session(doc! {"_cid": cart_id}, None, session)
+            .await?
+            .map(|r| r.qty)
+            .unwrap_or(0.0);
+
+        let delta = qty - held;
+
+        if delta > 0.0 {
+            let taken = self
+                .inventory_col
+                .find_one_and_update_with_session(
+                    doc! {"sku": line.stock_key(), "available": {"$gte": delta}},
+                    doc! {"$inc": {"available": -delta}},
+                    None,
+                    session,
+                )
+                .await?;
+
+            if taken.is_none() {
+                return Err(AppError::Conflict(format!("Insufficient stock for {}", line.stock_key())));
+            }
+        } else if delta < 0.0 {
+            self.inventory_col
+                .update_one_with_session(
+                    doc! {"sku": line.stock_key()},
+                    doc! {"$inc": {"available": -delta}},
+                    None,
+                    session,
+                )
+                .await?;
+        }
+
+        self.reservation_col
+            .delete_one_with_session(doc! {"_cid": cart_id}, None, session)
+            .await?;
+
+        if qty > 0.0 {
+            let data = Reservation {
+                id: None,
+                user_id: line.user_id.to_owned().unwrap_or_default(),
+                cart_id,
+                sku: line.stock_key().to_owned(),
+                qty,
+                expires_at: Utc::now() + ttl,
+            };
+
+            self.reservation_col
+                .insert_one_with_session(data, None, session)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    //handler to release every reservation past its expiry, returns how many were released
+    pub async fn release_expired_reservations(&self) -> Result<u64, AppError> {
+        let now = BsonDateTime::now();
+
+        let mut expired = self
+            .reservation_col
+            .find(doc! {"expiresAt": {"$lt": now}}, None)
+            .await?;
+
+        let mut released = 0;
+
+        while let Some(doc) = expired.next().await {
+            match doc {
+                Ok(data) => {
+                    //delete before releasing so concurrent sweepers never release twice
+                    let deleted = self
+                        .reservation_col
+                        .find_one_and_delete(doc! {"_id": data.id, "expiresAt": {"$lt": now}}, None)
+                        .await?;
+
+                    if let Some(r) = deleted {
+                        self.release_stock(&r.sku, r.qty).await?;
+                        released += 1;
+                    }
+                },
+                Err(err) => {
+                    eprintln!("Error finding reservation: {:?}", err)
+                },
+            }
+        }
+
+        Ok(released)
+    }
+
+    ////----------------------  END - Reservation handler function ----------------------------- ////
+
+}
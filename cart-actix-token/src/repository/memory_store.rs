@@ -0,0 +1,521 @@
+use std::{cmp::Ordering, collections::BTreeMap, sync::Mutex};
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm};
+use mongodb::bson::{oid::ObjectId, Bson};
+
+use crate::error::AppError;
+use crate::model::{
+    cart_model::{Cart, CartDeleteFilter, CartMergeResult, CartNotice, CartPage, CartQuery, CartWriteError, PageInfo, SortOrder, UpdateCart},
+    idempotency_model::{IdempotencyRecord, IDEMPOTENCY_LEASE_SECONDS},
+    share_model::CartAccess,
+    user_model::{TokenClaims, User}};
+
+use super::{
+    cart_page_repo::{decode_cursor, encode_cursor, invalid_cursor, page_limit, sort_value},
+    guest_cart_repo::{guest_cart_id, is_guest_cart},
+    soft_delete_repo::not_restorable,
+    store::{CartStore, IdempotencyStore, UserStore}};
+
+fn todo_not_found() -> AppError {
+    AppError::NotFound("Todo Not found".to_owned())
+}
+
+//whether two lines are the same variant with the same options, those are merged into one
+pub(super) fn same_line(a: &Cart, b: &Cart) -> bool {
+    let options = |line: &Cart| line.options.clone().filter(|options| !options.is_empty());
+    a.product_name == b.product_name && a.sku == b.sku && options(a) == options(b)
+}
+
+//position of a sort value in the listing, missing values sort first like they do in MongoDB
+fn sort_key(value: &Bson) -> f64 {
+    match value {
+        Bson::DateTime(at) => at.timestamp_millis() as f64,
+        Bson::Double(value) => *value,
+        Bson::Int32(value) => *value as f64,
+        Bson::Int64(value) => *value as f64,
+        _ => f64::NEG_INFINITY,
+    }
+}
+
+//whether the line is still in the cart, deleted lines only wait for a restore or the purge
+fn is_live(line: &Cart) -> bool {
+    line.deleted_at.is_none()
+}
+
+fn same_email(user: &User, email: &str) -> bool {
+    user.email.to_lowercase() == email.to_lowercase()
+}
+
+//folds the guest lines into the cart of the user, matching lines have their quantities summed.
+//A guest line summed into a user line is deleted like any other, so it stays restorable
+fn merge_guest_lines(carts: &mut Vec<Cart>, guest_id: &str, user_id: &str) -> CartMergeResult {
+    let mut result = CartMergeResult::default();
+    let mut removed = Vec::new();
+
+    let (guest_items, mut rest): (Vec<Cart>, Vec<Cart>) = carts
+        .drain(..)
+        .partition(|line| line.user_id.as_deref() == Some(guest_id) && is_live(line));
+
+    for item in guest_items {
+        let existing = rest
+            .iter_mut()
+            .find(|line| line.user_id.as_deref() == Some(user_id) && is_live(line) && same_line(line, &item));
+
+        match existing {
+            Some(line) => {
+                line.qty += item.qty;
+                line.total = Some(line.price * line.qty);
+                line.version = Some(line.version() + 1);
+                result.merged += 1;
+
+                removed.push(Cart {
+                    deleted_at: Some(Utc::now()),
+                    version: Some(item.version() + 1),
+                    ..item
+                });
+            },
+            None => {
+                rest.push(Cart {
+                    user_id: Some(user_id.to_owned()),
+                    expires_at: None,
+                    version: Some(item.version() + 1),
+                    ..item
+                });
+                result.moved += 1;
+            },
+        }
+    }
+
+    rest.extend(removed);
+    *carts = rest;
+    result
+}
+
+//Everything the memory store holds, writes change it as a whole through a transaction
+#[derive(Debug, Clone, Default)]
+struct MemoryState {
+    users: Vec<User>,
+    carts: Vec<Cart>,
+    idempotency: Vec<IdempotencyRecord>,
+}
+
+//Store keeping everything in process memory, for tests and local demos without a database.
+//There is no catalog, stock or sharing here: lines keep the price they were added with.
+//It covers the Store traits, so the user and cart routes of store_config, and nothing beyond them
+pub struct MemoryStore {
+    state: Mutex<MemoryState>,
+    guest_cart_ttl: Duration,
+    restore_window: Duration,
+    deleted_cart_retention: Duration,
+    idempotency_ttl: Duration,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        MemoryStore {
+            state: Mutex::new(MemoryState::default()),
+            guest_cart_ttl: Duration::days(7),
+            restore_window: Duration::minutes(30),
+            deleted_cart_retention: Duration::days(7),
+            idempotency_ttl: Duration::hours(24),
+        }
+    }
+}
+
+impl MemoryStore {
+    //counterpart of MongoRepo::with_transaction: the change works on a copy of the state under the lock
+    //and the copy replaces the state only when the change succeeds, so a failure leaves nothing behind
+    //and nobody sees half of a change
+    fn transaction<T, E>(&self, change: impl FnOnce(&mut MemoryState) -> Result<T, E>) -> Result<T, E> {
+        let mut state = self.state.lock().unwrap();
+        let mut draft = state.clone();
+        let value = change(&mut draft)?;
+
+        //there is no background purge here, deleted lines past the retention go with the next write
+        let cutoff = Utc::now() - self.deleted_cart_retention;
+        draft.carts.retain(|line| line.deleted_at.is_none_or(|at| at > cutoff));
+
+        *state = draft;
+        Ok(value)
+    }
+
+    //marks the live lines of the cart that match as deleted, returns how many
+    fn delete_lines(&self, access: &CartAccess, matches: impl Fn(&Cart) -> bool) -> Result<u64, AppError> {
+        self.transaction(|state| {
+            let mut deleted = 0;
+
+            for line in state.carts.iter_mut() {
+                if line.user_id.as_ref() == Some(&access.owner) && is_live(line) && matches(line) {
+                    line.deleted_at = Some(Utc::now());
+                    line.version = Some(line.version() + 1);
+                    deleted += 1;
+                }
+            }
+
+            Ok(deleted)
+        })
+    }
+}
+
+#[async_trait]
+impl UserStore for MemoryStore {
+    async fn find_by_email_pwd(&self, email: &str, pwd: &str) -> Result<Option<User>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state.users.iter().find(|user| same_email(user, email) && user.password == pwd).cloned())
+    }
+
+    async fn register_user(&self, new_user: User, guest_token: Option<&str>) -> Result<User, AppError> {
+        let user_id = ObjectId::new();
+        let user = User { id: Some(user_id), created_at: None, ..new_user };
+
+        //the email check, the new user and the guest cart merge land together or not at all
+        self.transaction(|state| {
+            if state.users.iter().any(|existing| same_email(existing, &user.email)) {
+                return Err(AppError::Conflict("Email already exists".to_owned()));
+            }
+
+            state.users.push(user.clone());
+
+            if let Some(guest_id) = guest_token.and_then(guest_cart_id) {
+                merge_guest_lines(&mut state.carts, &guest_id, &user_id.to_string());
+            }
+
+            Ok(user)
+        })
+    }
+
+    async fn merge_guest_cart(&self, guest_token: &str, user_id: &str) -> Result<CartMergeResult, AppError> {
+        let guest_id = match guest_cart_id(guest_token) {
+            Some(guest_id) => guest_id,
+            None => return Ok(CartMergeResult::default()),
+        };
+
+        self.transaction(|state| Ok(merge_guest_lines(&mut state.carts, &guest_id, user_id)))
+    }
+}
+
+#[async_trait]
+impl CartStore for MemoryStore {
+    fn guest_cart_ttl(&self) -> Duration {
+        self.guest_cart_ttl
+    }
+
+    async fn cart_owner(&self, token: &str) -> Result<Option<String>, AppError> {
+        if let Some(guest_id) = guest_cart_id(token) {
+            return Ok(Some(guest_id));
+        }
+
+        let secret_key = "secret".to_owned();
+        let claims = decode::<TokenClaims>(
+            token,
+            &DecodingKey::from_secret(secret_key.as_bytes()),
+            &Validation::new(Algorithm::HS256),
+        )
+        .map_err(|_| AppError::Unauthorized("Invalid token".to_owned()))?
+        .claims;
+
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .users
+            .iter()
+            .filter_map(|user| user.id)
+            .find(|id| id.to_string() == claims.sub)
+            .map(|id| id.to_string()))
+    }
+
+    async fn cart_access(&self, token: &str, shared: Option<&str>) -> Result<CartAccess, AppError> {
+        let actor = self.cart_owner(token).await?.ok_or(AppError::Unauthorized("Not found user".to_string()))?;
+
+        //carts are never shared here
+        match shared {
+            Some(owner) if owner != actor => Err(AppError::Forbidden("No access to this cart".to_owned())),
+            _ => Ok(CartAccess::own(actor)),
+        }
+    }
+
+    async fn create_cart(&self, access: &CartAccess, new_cart: Cart) -> Result<Cart, AppError> {
+        access.ensure_editor()?;
+        let user_id = &access.owner;
+
+        if new_cart.qty <= 0.0 {
+            return Err(AppError::Validation("Quantity must be greater than zero".to_owned()));
+        }
+
+        self.transaction(|state| {
+            let carts = &mut state.carts;
+
+            let existing = carts
+                .iter_mut()
+                .find(|line| line.user_id.as_ref() == Some(user_id) && is_live(line) && same_line(line, &new_cart));
+
+            if let Some(line) = existing {
+                line.qty += new_cart.qty;
+                line.price = new_cart.price;
+                line.total = Some(line.price * line.qty);
+                line.updated_at = Some(Utc::now());
+                line.version = Some(line.version() + 1);
+                return Ok(line.clone());
+            }
+
+            let data = Cart {
+                id: Some(ObjectId::new()),
+                user_id: Some(user_id.to_owned()),
+                variant: None,
+                options: new_cart.options.to_owned().filter(|options| !options.is_empty()),
+                total: Some(new_cart.price * new_cart.qty),
+                created_at: Some(Utc::now()),
+                expires_at: is_guest_cart(user_id).then(|| Utc::now() + self.guest_cart_ttl),
+                updated_at: Some(Utc::now()),
+                abandoned_at: None,
+                version: Some(1),
+                added_by: Some(access.actor.to_owned()),
+                deleted_at: None,
+                ..new_cart
+            };
+
+            carts.push(data.clone());
+            Ok(data)
+        })
+    }
+
+    async fn list_all_carts_by_user(&self, access: &CartAccess) -> Result<Vec<Cart>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .carts
+            .iter()
+            .filter(|line| line.user_id.as_ref() == Some(&access.owner) && is_live(line))
+            .cloned()
+            .collect())
+    }
+
+    async fn list_cart_page(&self, access: &CartAccess, query: CartQuery) -> Result<CartPage, AppError> {
+        let limit = page_limit(&query)?;
+
+        let after = match &query.cursor {
+            Some(cursor) => Some(decode_cursor(cursor).ok_or_else(invalid_cursor)?),
+            None => None,
+        };
+
+        let product_name = query
+            .product_name
+            .as_deref()
+            .map(|name| name.trim().to_lowercase())
+            .filter(|name| !name.is_empty());
+
+        let mut items: Vec<Cart> = self
+            .list_all_carts_by_user(access)
+            .await?
+            .into_iter()
+            .filter(|line| product_name.as_ref().is_none_or(|name| line.product_name.to_lowercase().contains(name)))
+            .filter(|line| query.min_price.is_none_or(|min| line.price >= min))
+            .filter(|line| query.max_price.is_none_or(|max| line.price <= max))
+            .collect();
+
+        //listing order of a line, the id breaks ties between equal sort values
+        let position = |value: &Bson, id: &ObjectId, line: &Cart| {
+            let ordering = sort_key(&sort_value(line, query.sort))
+                .total_cmp(&sort_key(value))
+                .then(line.id.unwrap().cmp(id));
+
+            match query.order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        };
+
+        items.sort_by(|a, b| position(&sort_value(b, query.sort), &b.id.unwrap(), a));
+
+        if let Some((value, id)) = after {
+            items.retain(|line| position(&value, &id, line) == Ordering::Greater);
+        }
+
+        let has_more = items.len() as i64 > limit;
+        items.truncate(limit as usize);
+
+        let next_cursor = match items.last() {
+            Some(last) if has_more => last.id.map(|id| encode_cursor(sort_value(last, query.sort), id)),
+            _ => None,
+        };
+
+        Ok(CartPage {
+            items,
+            page: PageInfo { limit, has_more, next_cursor },
+        })
+    }
+
+    async fn update_cart(&self, access: &CartAccess, cart_data: UpdateCart, cart_id: String, if_match: Option<i64>) -> Result<Cart, CartWriteError> {
+        access.ensure_editor()?;
+        let cart_id = ObjectId::parse_str(cart_id).map_err(|_| todo_not_found())?;
+
+        if cart_data.qty <= 0.0 {
+            return Err(CartWriteError::Failed(AppError::Validation("Quantity must be greater than zero".to_owned())));
+        }
+
+        self.transaction(|state| {
+            let carts = &mut state.carts;
+
+            let line = carts
+                .iter_mut()
+                .find(|line| line.id == Some(cart_id) && line.user_id.as_ref() == Some(&access.owner) && is_live(line))
+                .ok_or_else(todo_not_found)?;
+
+            if if_match.is_some_and(|version| version != line.version()) {
+                return Err(CartWriteError::Stale(Box::new(line.clone())));
+            }
+
+            line.qty = cart_data.qty;
+            line.total = Some(line.price * line.qty);
+            line.updated_at = Some(Utc::now());
+            line.version = Some(line.version() + 1);
+
+            //guest items live on for another full period after every change
+            if line.expires_at.is_some() {
+                line.expires_at = Some(Utc::now() + self.guest_cart_ttl);
+            }
+
+            Ok(line.clone())
+        })
+    }
+
+    async fn delete_cart(&self, access: &CartAccess, cart_id: String, if_match: Option<i64>) -> Result<u64, CartWriteError> {
+        access.ensure_editor()?;
+        let cart_id = ObjectId::parse_str(cart_id).map_err(|_| todo_not_found())?;
+
+        self.transaction(|state| {
+            let carts = &mut state.carts;
+
+            let line = carts
+                .iter_mut()
+                .find(|line| line.id == Some(cart_id) && line.user_id.as_ref() == Some(&access.owner) && is_live(line))
+                .ok_or_else(todo_not_found)?;
+
+            if if_match.is_some_and(|version| version != line.version()) {
+                return Err(CartWriteError::Stale(Box::new(line.clone())));
+            }
+
+            line.deleted_at = Some(Utc::now());
+            line.version = Some(line.version() + 1);
+            Ok(1)
+        })
+    }
+
+    async fn restore_cart(&self, access: &CartAccess, cart_id: String) -> Result<Cart, AppError> {
+        access.ensure_editor()?;
+        let cart_id = ObjectId::parse_str(cart_id).map_err(|_| not_restorable())?;
+        let since = Utc::now() - self.restore_window;
+
+        self.transaction(|state| {
+            let carts = &mut state.carts;
+
+            let index = carts
+                .iter()
+                .position(|line| line.id == Some(cart_id) && line.user_id.as_ref() == Some(&access.owner) && line.deleted_at.is_some_and(|at| at >= since))
+                .ok_or_else(not_restorable)?;
+
+            //the same variant added again since the removal keeps its own line, the two are not merged
+            if carts.iter().any(|line| line.user_id.as_ref() == Some(&access.owner) && is_live(line) && same_line(line, &carts[index])) {
+                return Err(AppError::Conflict("The item is already in the cart again".to_owned()));
+            }
+
+            let line = &mut carts[index];
+            line.deleted_at = None;
+            line.updated_at = Some(Utc::now());
+            line.version = Some(line.version() + 1);
+
+            if line.expires_at.is_some() {
+                line.expires_at = Some(Utc::now() + self.guest_cart_ttl);
+            }
+
+            Ok(line.clone())
+        })
+    }
+
+    async fn clear_cart(&self, access: &CartAccess) -> Result<u64, AppError> {
+        access.ensure_editor()?;
+        self.delete_lines(access, |_| true)
+    }
+
+    async fn delete_cart_items(&self, access: &CartAccess, filter: CartDeleteFilter) -> Result<u64, AppError> {
+        access.ensure_editor()?;
+
+        //an empty filter would clear the whole cart, that is what DELETE /cart is for
+        if filter.is_empty() {
+            return Err(AppError::Validation("At least one filter is required".to_owned()));
+        }
+
+        self.delete_lines(access, |line| filter.matches(line))
+    }
+
+    async fn finding_cart(&self, access: &CartAccess, cart_id: &ObjectId) -> Result<Option<Cart>, AppError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .carts
+            .iter()
+            .find(|line| line.id.as_ref() == Some(cart_id) && line.user_id.as_ref() == Some(&access.owner) && is_live(line))
+            .cloned())
+    }
+
+    //without a catalog lines never go stale
+    async fn revalidate_lines(&self, _lines: &[Cart]) -> Result<Vec<CartNotice>, AppError> {
+        Ok(Vec::new())
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MemoryStore {
+    async fn claim_idempotency_key(&self, key: &str, owner: &str, request: &str) -> Result<Option<IdempotencyRecord>, AppError> {
+        let mut state = self.state.lock().unwrap();
+        let records = &mut state.idempotency;
+        let now = Utc::now();
+
+        //records past the replay window are forgotten, like the TTL index and the purge do elsewhere
+        let cutoff = now - self.idempotency_ttl;
+        records.retain(|record| record.created_at.is_some_and(|at| at >= cutoff));
+
+        let lease = now - Duration::seconds(IDEMPOTENCY_LEASE_SECONDS);
+        if let Some(record) = records.iter_mut().find(|record| record.key == key && record.owner == owner) {
+            //a claim that never got its response is taken over once its lease ran out
+            if record.status.is_none() && record.created_at.is_some_and(|at| at < lease) {
+                record.request = request.to_owned();
+                record.created_at = Some(now);
+                return Ok(None);
+            }
+            return Ok(Some(record.clone()));
+        }
+
+        records.push(IdempotencyRecord {
+            id: None,
+            key: key.to_owned(),
+            owner: owner.to_owned(),
+            request: request.to_owned(),
+            status: None,
+            headers: BTreeMap::new(),
+            body: None,
+            created_at: Some(now),
+        });
+
+        Ok(None)
+    }
+
+    async fn complete_idempotency_key(&self, key: &str, owner: &str, status: u16, headers: BTreeMap<String, String>, body: String) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        let records = &mut state.idempotency;
+
+        if let Some(record) = records.iter_mut().find(|record| record.key == key && record.owner == owner) {
+            record.status = Some(status);
+            record.headers = headers;
+            record.body = Some(body);
+        }
+
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, key: &str, owner: &str) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        let records = &mut state.idempotency;
+        records.retain(|record| !(record.key == key && record.owner == owner));
+        Ok(())
+    }
+}
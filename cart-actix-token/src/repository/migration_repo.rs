@@ -0,0 +1,141 @@
+use chrono::{Duration, Utc};
+use futures::StreamExt;
+use mongodb::{
+    Collection,
+    bson::{doc, oid::ObjectId, DateTime as BsonDateTime},
+    options::UpdateOptions};
+
+use crate::error::AppError;
+use crate::migrations::{self, MigrationContext};
+use crate::model::migration_model::{AppliedMigration, MigrationLock, MigrationOutcome};
+
+use super::{index_repo::is_duplicate_key, mongodb_repo::MongoRepo};
+
+const MIGRATION_LOCK: &str = "migrations";
+
+//a run holding the lock longer than this is taken to be dead
+fn lock_timeout() -> Duration {
+    Duration::minutes(10)
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Migration handler function ----------------------------- ////
+
+    fn migration_col(&self) -> Collection<AppliedMigration> {
+        self.db.collection(&format!("{}_migrations", self.collection_prefix))
+    }
+
+    fn migration_lock_col(&self) -> Collection<MigrationLock> {
+        self.db.collection(&format!("{}_migrations_lock", self.collection_prefix))
+    }
+
+    //handler to list the migrations already recorded
+    pub async fn applied_migrations(&self) -> Result<Vec<AppliedMigration>, AppError> {
+        let mut applied_doc = self
+            .migration_col()
+            .find(doc! {}, None)
+            .await?;
+
+        let mut applied = Vec::new();
+        while let Some(doc) = applied_doc.next().await {
+            applied.push(doc?);
+        }
+
+        Ok(applied)
+    }
+
+    //handler to run the pending migrations in version order, under the lock.
+    //A dry run takes no lock and only counts what each pending migration would change
+    pub async fn run_migrations(&self, dry_run: bool) -> Result<Vec<MigrationOutcome>, AppError> {
+        if dry_run {
+            return self.apply_pending_migrations(true).await;
+        }
+
+        let owner = ObjectId::new().to_hex();
+        self.acquire_migration_lock(&owner).await?;
+
+        let outcomes = self.apply_pending_migrations(false).await;
+
+        //a failed run gives the lock up as well, the failed migration is simply pending again
+        if let Err(error) = self.release_migration_lock(&owner).await {
+            eprintln!("Error releasing migration lock: {:?}", error);
+        }
+
+        outcomes
+    }
+
+    async fn apply_pending_migrations(&self, dry_run: bool) -> Result<Vec<MigrationOutcome>, AppError> {
+        let applied: Vec<i64> = self
+            .applied_migrations()
+            .await?
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect();
+
+        let mut pending = migrations::all();
+        pending.retain(|migration| !applied.contains(&migration.version()));
+        pending.sort_by_key(|migration| migration.version());
+
+        let ctx = MigrationContext { db: &self.db, collection_prefix: &self.collection_prefix, dry_run };
+        let mut outcomes = Vec::new();
+
+        for migration in pending {
+            let affected = migration.up(&ctx).await.map_err(|error| {
+                let message = format!("Migration {} {} failed: {}", migration.version(), migration.name(), error);
+                error.with_message(message)
+            })?;
+
+            if !dry_run {
+                self.migration_col()
+                    .insert_one(AppliedMigration {
+                        version: migration.version(),
+                        name: migration.name().to_owned(),
+                        affected,
+                        applied_at: Some(Utc::now()),
+                    }, None)
+                    .await?;
+            }
+
+            outcomes.push(MigrationOutcome {
+                version: migration.version(),
+                name: migration.name().to_owned(),
+                affected,
+                applied: !dry_run,
+            });
+        }
+
+        Ok(outcomes)
+    }
+
+    async fn acquire_migration_lock(&self, owner: &str) -> Result<(), AppError> {
+        let stale = BsonDateTime::from_chrono(Utc::now() - lock_timeout());
+
+        //takes a free or stale lock, while another run holds it the upsert hits the unique _id
+        let taken = self
+            .migration_lock_col()
+            .update_one(
+                doc! {"_id": MIGRATION_LOCK, "lockedAt": {"$lt": stale}},
+                doc! {"$set": {"owner": owner, "lockedAt": BsonDateTime::now()}},
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await;
+
+        match taken {
+            Ok(_) => Ok(()),
+            Err(error) if is_duplicate_key(&error) => Err(AppError::Conflict("Migrations are already running".to_owned())),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn release_migration_lock(&self, owner: &str) -> Result<(), AppError> {
+        self.migration_lock_col()
+            .delete_one(doc! {"_id": MIGRATION_LOCK, "owner": owner}, None)
+            .await?;
+
+        Ok(())
+    }
+
+    ////----------------------  END - Migration handler function ----------------------------- ////
+
+}
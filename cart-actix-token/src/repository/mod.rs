@@ -1 +1,26 @@
-pub mod mongodb_repo;
\ No newline at end of file
+pub mod cart_batch_repo;
+pub mod cart_expiry_repo;
+pub mod cart_page_repo;
+pub mod guest_cart_repo;
+pub mod history_repo;
+pub mod idempotency_repo;
+pub mod index_repo;
+pub mod inventory_repo;
+pub mod memory_store;
+pub mod migration_repo;
+pub mod mongo_config;
+pub mod mongodb_repo;
+pub mod order_repo;
+pub mod outbox_repo;
+pub mod payment_repo;
+pub mod product_repo;
+pub mod revalidation_repo;
+pub mod share_repo;
+pub mod soft_delete_repo;
+pub mod sql_store;
+pub mod store;
+pub mod transaction_repo;
+pub mod wishlist_repo;
+
+#[cfg(test)]
+mod tests;
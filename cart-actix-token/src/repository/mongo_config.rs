@@ -0,0 +1,188 @@
+use std::{env, str::FromStr, time::Duration};
+
+use mongodb::{
+    Client,
+    bson::doc,
+    options::{Acknowledgment, ClientOptions, ReadConcern, WriteConcern}};
+
+use crate::error::AppError;
+
+fn invalid_setting(name: &str, expected: &str) -> AppError {
+    AppError::Validation(format!("{} must be {}", name, expected))
+}
+
+//an unset variable is None, one that does not parse stops the startup instead of being ignored
+fn env_setting<T: FromStr>(name: &str, expected: &str) -> Result<Option<T>, AppError> {
+    match env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value.trim().parse().map(Some).map_err(|_| invalid_setting(name, expected)),
+        _ => Ok(None),
+    }
+}
+
+fn env_millis(name: &str) -> Result<Option<Duration>, AppError> {
+    Ok(env_setting::<u64>(name, "a number of milliseconds")?.map(Duration::from_millis))
+}
+
+fn read_concern(level: &str) -> Result<ReadConcern, AppError> {
+    match level {
+        "local" => Ok(ReadConcern::local()),
+        "majority" => Ok(ReadConcern::majority()),
+        "linearizable" => Ok(ReadConcern::linearizable()),
+        "available" => Ok(ReadConcern::available()),
+        "snapshot" => Ok(ReadConcern::snapshot()),
+        _ => Err(invalid_setting("MONGO_READ_CONCERN", "local, majority, linearizable, available or snapshot")),
+    }
+}
+
+//`majority`, a number of nodes or a tag set name
+fn write_acknowledgment(w: &str) -> Acknowledgment {
+    match w.parse::<u32>() {
+        Ok(nodes) => Acknowledgment::from(nodes),
+        Err(_) => Acknowledgment::from(w.to_owned()),
+    }
+}
+
+//Connection settings of the MongoDB store. Unset ones keep what the URI says, or the driver default
+#[derive(Debug, Clone)]
+pub struct MongoConfig {
+    pub uri: String,
+    pub db_name: String,
+    //put in front of every collection name, so several deployments can share one database
+    pub collection_prefix: String,
+    pub app_name: String,
+    pub min_pool_size: Option<u32>,
+    pub max_pool_size: Option<u32>,
+    pub connect_timeout: Option<Duration>,
+    //how long an operation waits for a usable server, also bounds every startup ping
+    pub server_selection_timeout: Option<Duration>,
+    pub read_concern: Option<ReadConcern>,
+    pub write_concern: Option<WriteConcern>,
+    pub retry_reads: Option<bool>,
+    pub retry_writes: Option<bool>,
+    //pings at startup before giving up, one second apart and doubling
+    pub connect_attempts: u32,
+}
+
+impl MongoConfig {
+    //defaults for the given server
+    pub fn new(uri: impl Into<String>) -> Self {
+        MongoConfig {
+            uri: uri.into(),
+            db_name: "cart-db".to_owned(),
+            collection_prefix: String::new(),
+            app_name: "cart-actix-token".to_owned(),
+            min_pool_size: None,
+            max_pool_size: None,
+            connect_timeout: None,
+            server_selection_timeout: Some(Duration::from_secs(5)),
+            read_concern: None,
+            write_concern: None,
+            retry_reads: None,
+            retry_writes: None,
+            connect_attempts: 3,
+        }
+    }
+
+    //settings from MONGOURI and the MONGO_* variables, MONGOURI is the only required one
+    pub fn from_env() -> Result<Self, AppError> {
+        let uri = env::var("MONGOURI")
+            .ok()
+            .filter(|uri| !uri.trim().is_empty())
+            .ok_or_else(|| AppError::Validation("MONGOURI is not set, e.g. MONGOURI=mongodb://localhost:27017".to_owned()))?;
+
+        let defaults = MongoConfig::new(uri);
+
+        let write_concern = match env::var("MONGO_WRITE_CONCERN").ok().filter(|w| !w.trim().is_empty()) {
+            Some(w) => {
+                let mut write_concern = WriteConcern::default();
+                write_concern.w = Some(write_acknowledgment(w.trim()));
+                write_concern.w_timeout = env_millis("MONGO_WRITE_TIMEOUT_MS")?;
+                write_concern.journal = env_setting("MONGO_WRITE_JOURNAL", "true or false")?;
+                Some(write_concern)
+            },
+            None => None,
+        };
+
+        Ok(MongoConfig {
+            db_name: env::var("MONGO_DB").ok().filter(|name| !name.trim().is_empty()).unwrap_or(defaults.db_name.clone()),
+            collection_prefix: env::var("MONGO_COLLECTION_PREFIX").unwrap_or_default(),
+            app_name: env::var("MONGO_APP_NAME").ok().filter(|name| !name.trim().is_empty()).unwrap_or(defaults.app_name.clone()),
+            min_pool_size: env_setting("MONGO_MIN_POOL_SIZE", "a number")?,
+            max_pool_size: env_setting("MONGO_MAX_POOL_SIZE", "a number")?,
+            connect_timeout: env_millis("MONGO_CONNECT_TIMEOUT_MS")?,
+            server_selection_timeout: env_millis("MONGO_SERVER_SELECTION_TIMEOUT_MS")?.or(defaults.server_selection_timeout),
+            read_concern: env::var("MONGO_READ_CONCERN")
+                .ok()
+                .filter(|level| !level.trim().is_empty())
+                .map(|level| read_concern(&level.trim().to_lowercase()))
+                .transpose()?,
+            write_concern,
+            retry_reads: env_setting("MONGO_RETRY_READS", "true or false")?,
+            retry_writes: env_setting("MONGO_RETRY_WRITES", "true or false")?,
+            connect_attempts: env_setting::<u32>("MONGO_CONNECT_ATTEMPTS", "a number")?
+                .unwrap_or(defaults.connect_attempts)
+                .max(1),
+            ..defaults
+        })
+    }
+
+    //name of a collection with the prefix of this deployment
+    pub fn collection_name(&self, name: &str) -> String {
+        format!("{}{}", self.collection_prefix, name)
+    }
+
+    async fn client_options(&self) -> Result<ClientOptions, AppError> {
+        let mut options = ClientOptions::parse(&self.uri)
+            .await
+            .map_err(|error| AppError::Validation(format!("MONGOURI is not a valid connection string: {}", error)))?;
+
+        options.app_name = Some(self.app_name.to_owned());
+        options.min_pool_size = self.min_pool_size.or(options.min_pool_size);
+        options.max_pool_size = self.max_pool_size.or(options.max_pool_size);
+        options.connect_timeout = self.connect_timeout.or(options.connect_timeout);
+        options.server_selection_timeout = self.server_selection_timeout.or(options.server_selection_timeout);
+        options.read_concern = self.read_concern.clone().or(options.read_concern);
+        options.write_concern = self.write_concern.clone().or(options.write_concern);
+        options.retry_reads = self.retry_reads.or(options.retry_reads);
+        options.retry_writes = self.retry_writes.or(options.retry_writes);
+
+        if let (Some(min), Some(max)) = (options.min_pool_size, options.max_pool_size) {
+            if min > max {
+                return Err(AppError::Validation(format!("MONGO_MIN_POOL_SIZE {} is above MONGO_MAX_POOL_SIZE {}", min, max)));
+            }
+        }
+
+        Ok(options)
+    }
+
+    //builds the client and pings the server, so a wrong address or credentials stop the startup
+    //with the reason instead of failing the first request
+    pub async fn connect(&self) -> Result<Client, AppError> {
+        let options = self.client_options().await?;
+        let hosts = options.hosts.iter().map(|host| host.to_string()).collect::<Vec<String>>().join(",");
+
+        let client = Client::with_options(options)
+            .map_err(|error| AppError::Validation(format!("Invalid MongoDB settings: {}", error)))?;
+
+        let mut delay = Duration::from_secs(1);
+        let mut attempt = 1;
+
+        loop {
+            match client.database(&self.db_name).run_command(doc! {"ping": 1}, None).await {
+                Ok(_) => return Ok(client),
+                Err(error) if attempt >= self.connect_attempts => {
+                    return Err(AppError::Database(format!(
+                        "Could not reach MongoDB at {} after {} attempts: {}",
+                        hosts, attempt, error
+                    )));
+                },
+                Err(error) => {
+                    eprintln!("MongoDB at {} is not reachable yet (attempt {} of {}): {}", hosts, attempt, self.connect_attempts, error);
+                    actix_web::rt::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                },
+            }
+        }
+    }
+}
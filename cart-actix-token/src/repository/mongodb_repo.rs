@@ -1,63 +1,206 @@
-use std::env;
+use std::{env, sync::Arc};
 
-use actix_web::{
-    HttpResponse, 
-    cookie::{time::Duration as ActixWebDuration, Cookie}, post
-};
 use chrono::{Utc, Duration};
-use dotenv::dotenv;
 use futures::StreamExt;
-use jsonwebtoken::{encode, Header, EncodingKey, decode, DecodingKey, Validation, Algorithm};
+use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm};
 use mongodb::{
     Client, 
+    ClientSession, 
     Collection, 
-    results::{InsertOneResult, UpdateResult, DeleteResult}, 
-    bson::{doc, extjson::de::Error, oid::ObjectId}};
-use serde_json::json;
+    Database, 
+    options::{FindOneAndUpdateOptions, FindOneOptions, ReturnDocument},
+    results::{InsertOneResult, UpdateResult}, 
+    bson::{doc, oid::ObjectId, DateTime as BsonDateTime, Document}};
+
+use crate::error::AppError;
+use super::{guest_cart_repo::{guest_cart_id, is_guest_cart}, mongo_config::MongoConfig, index_repo::{case_insensitive, is_duplicate_key}, soft_delete_repo::{not_deleted, soft_delete}};
+use crate::{payment::PaymentProvider, hooks::{AbandonedCartHook, LogAbandonedCartHook}, model::{history_model::{CartAction, CartEvent}, idempotency_model::IdempotencyRecord, share_model::{CartAccess, CartShare}, user_model::{User, TokenClaims}, cart_model::{Cart, CartDeleteFilter, CartWriteError, UpdateCart}, inventory_model::{Inventory, Reservation}, order_model::Order, outbox_model::{DomainEvent, OutboxMessage}, product_model::Product, wishlist_model::SavedList}};
 
-use crate::model::{user_model::{User, LoginUserSchema, TokenClaims}, cart_model::{Cart, UpdateCart}, response_model::ErrorResponse};
 
 #[derive(Debug,Clone)]
 pub struct MongoRepo {
-    u_col: Collection<User>,
-    cart_col: Collection<Cart>
+    pub(super) client: Client,
+    pub(super) db: Database,
+    //put in front of every collection name, migrations name theirs with it too
+    pub(super) collection_prefix: String,
+    pub(super) u_col: Collection<User>,
+    pub(super) cart_col: Collection<Cart>,
+    pub(super) inventory_col: Collection<Inventory>,
+    pub(super) reservation_col: Collection<Reservation>,
+    pub(super) order_col: Collection<Order>,
+    pub(super) list_col: Collection<SavedList>,
+    pub(super) product_col: Collection<Product>,
+    pub(super) idempotency_col: Collection<IdempotencyRecord>,
+    pub(super) share_col: Collection<CartShare>,
+    pub(super) history_col: Collection<CartEvent>,
+    pub(super) outbox_col: Collection<OutboxMessage>,
+    //how long an added item holds its stock, reservations are disabled when None
+    pub(super) reservation_ttl: Option<Duration>,
+    //tax applied to the discounted subtotal at checkout, e.g. 0.08 for 8%
+    pub(super) tax_rate: f64,
+    //how long an untouched guest cart is kept
+    pub(super) guest_cart_ttl: Duration,
+    //how long a cart may sit untouched before the TTL index drops it
+    pub(super) cart_ttl: Duration,
+    //how long an Idempotency-Key is remembered
+    pub(super) idempotency_ttl: Duration,
+    //how long a removed line can still be restored
+    pub(super) restore_window: Duration,
+    //how long a removed line is kept before the purge drops it
+    pub(super) deleted_cart_retention: Duration,
+    //how long a delivered outbox event is kept
+    pub(super) outbox_retention: Duration,
+    //idle time after which a cart counts as abandoned
+    pub(super) abandoned_after: Duration,
+    pub(super) abandoned_hook: Arc<dyn AbandonedCartHook>,
+    //users allowed on the admin routes
+    pub(super) admin_emails: Vec<String>,
+    pub(super) payments: Arc<dyn PaymentProvider>,
 }
 
 impl MongoRepo {
-    pub async fn init() -> Self {
-        dotenv().ok();
-        let uri = match env::var("MONGOURI") {
-            Ok(v) => v.to_string(),
-            Err(_) => format!("Error loading env variable"),
-        };
+    //connects with the settings from the environment
+    pub async fn init(payments: Arc<dyn PaymentProvider>) -> Result<Self, AppError> {
+        MongoRepo::connect(MongoConfig::from_env()?, payments).await
+    }
 
-        let client = Client::with_uri_str(uri).await.unwrap();
-        let db = client.database("cart-db");
-        let u_col: Collection<User> = db.collection("User");
-        let cart_col: Collection<Cart> = db.collection("Cart");
+    pub async fn connect(config: MongoConfig, payments: Arc<dyn PaymentProvider>) -> Result<Self, AppError> {
+        let client = config.connect().await?;
+        let db = client.database(&config.db_name);
+        let u_col: Collection<User> = db.collection(&config.collection_name("User"));
+        let cart_col: Collection<Cart> = db.collection(&config.collection_name("Cart"));
+        let inventory_col: Collection<Inventory> = db.collection(&config.collection_name("Inventory"));
+        let reservation_col: Collection<Reservation> = db.collection(&config.collection_name("Reservation"));
+        let order_col: Collection<Order> = db.collection(&config.collection_name("Order"));
+        let list_col: Collection<SavedList> = db.collection(&config.collection_name("SavedList"));
+        let product_col: Collection<Product> = db.collection(&config.collection_name("Product"));
+        let idempotency_col: Collection<IdempotencyRecord> = db.collection(&config.collection_name("IdempotencyKey"));
+        let share_col: Collection<CartShare> = db.collection(&config.collection_name("CartShare"));
+        let history_col: Collection<CartEvent> = db.collection(&config.collection_name("CartHistory"));
+        let outbox_col: Collection<OutboxMessage> = db.collection(&config.collection_name("Outbox"));
+
+        let reservation_ttl = env::var("RESERVATION_TTL_MINUTES")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|minutes| *minutes > 0)
+            .map(Duration::minutes);
+
+        let guest_cart_ttl = env::var("GUEST_CART_TTL_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(Duration::days)
+            .unwrap_or_else(|| Duration::days(7));
+
+        let cart_ttl = env::var("CART_TTL_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(Duration::days)
+            .unwrap_or_else(|| Duration::days(30));
+
+        let abandoned_after = env::var("ABANDONED_CART_HOURS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(Duration::hours)
+            .unwrap_or_else(|| Duration::hours(24));
+
+        let admin_emails = env::var("ADMIN_EMAILS")
+            .map(|v| v.split(',').map(|email| email.trim().to_lowercase()).filter(|email| !email.is_empty()).collect())
+            .unwrap_or_default();
+
+        let idempotency_ttl = env::var("IDEMPOTENCY_TTL_HOURS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(Duration::hours)
+            .unwrap_or_else(|| Duration::hours(24));
+
+        let restore_window = env::var("CART_RESTORE_WINDOW_MINUTES")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(Duration::minutes)
+            .unwrap_or_else(|| Duration::minutes(30));
+
+        let deleted_cart_retention = env::var("DELETED_CART_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(Duration::days)
+            .unwrap_or_else(|| Duration::days(7));
+
+        let outbox_retention = env::var("OUTBOX_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(Duration::days)
+            .unwrap_or_else(|| Duration::days(7));
+
+        let tax_rate = env::var("TAX_RATE")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(0.0);
+
+
+        println!("✅ Database {} connected successfully", config.db_name);
+
+
+        let repo = MongoRepo { 
+            client,
+            db,
+            collection_prefix: config.collection_prefix.to_owned(),
+            u_col,
+            cart_col,
+            inventory_col,
+            reservation_col,
+            order_col,
+            list_col,
+            product_col,
+            idempotency_col,
+            share_col,
+            history_col,
+            outbox_col,
+            reservation_ttl,
+            tax_rate,
+            guest_cart_ttl,
+            cart_ttl,
+            idempotency_ttl,
+            restore_window,
+            deleted_cart_retention,
+            outbox_retention,
+            abandoned_after,
+            abandoned_hook: Arc::new(LogAbandonedCartHook),
+            admin_emails,
+            payments
+        };
 
+        Ok(repo)
+    } 
 
-        println!("✅ Database connected successfully");
+    //brings the indexes in line with the declared ones, after the migrations since a unique index
+    //cannot be built over data a migration still has to fix.
+    //INDEX_SYNC=report only lists what differs from the declared indexes, off skips the check
+    pub async fn sync_indexes_from_env(&self) -> Result<(), AppError> {
+        let index_sync = env::var("INDEX_SYNC").unwrap_or_default().to_lowercase();
+        if index_sync == "off" {
+            return Ok(());
+        }
 
+        let changes = self.sync_indexes(index_sync == "report").await?;
 
-        MongoRepo { 
-            u_col,
-            cart_col
+        for change in &changes {
+            println!("🗂  {} {}.{}: {}{}", change.action.as_str(), change.collection, change.index, change.detail, if change.applied { "" } else { " (not applied)" });
         }
-    } 
+
+        Ok(())
+    }
 
     ////----------------------  START - User handler function ----------------------------- ////
 
     //user find by email handler
-    pub async fn find_by_email(&self, email: &String) -> Result<Option<User>, Error> {
+    pub async fn find_by_email(&self, email: &String) -> Result<Option<User>, AppError> {
 
         // let check_email = email;
 
         let user = self
             .u_col
-            .find_one( doc! {"email" : email}, None)
-            .await.ok()
-            .expect("Error finding user");
+            .find_one( doc! {"email" : email}, FindOneOptions::builder().collation(case_insensitive()).build())
+            .await?;
 
         
         Ok(user)
@@ -65,30 +208,42 @@ impl MongoRepo {
     }
 
     //user find by email handler
-    pub async fn find_by_email_pwd(&self, email: &String, pwd: &String) -> Result<Option<User>, Error> {
+    pub async fn find_by_email_pwd(&self, email: &String, pwd: &String) -> Result<Option<User>, AppError> {
 
         // let check_email = email;
 
+        //the collation would apply to the password as well, only the email is matched with it
         let user = self
-            .u_col
-            .find_one( doc! {"email" : email, "password": pwd}, None)
-            .await.ok()
-            .expect("Error finding user");
+            .find_by_email(email)
+            .await?
+            .filter(|user| user.password == *pwd);
 
-        
         Ok(user)
 
     }
 
-    //handler to create the user
-    pub async fn register_user(&self, new_user: User) -> Result<InsertOneResult, ErrorResponse> {
-        match self.find_by_email(&new_user.email.to_string()).await.unwrap(){
+    //user find by id handler
+    pub async fn find_user_by_id(&self, user_id: &str) -> Result<Option<User>, AppError> {
+        //guest ids are no user ids
+        let bson_id = match ObjectId::parse_str(user_id) {
+            Ok(bson_id) => bson_id,
+            Err(_) => return Ok(None),
+        };
+
+        let user = self
+            .u_col
+            .find_one( doc! {"_id" : bson_id}, None)
+            .await?;
+
+        Ok(user)
+    }
+
+    //handler to create the user, a guest cart is merged into the new account
+    pub async fn register_user(&self, new_user: User, guest_token: Option<&str>) -> Result<InsertOneResult, AppError> {
+        match self.find_by_email(&new_user.email.to_string()).await?{
             Some(_x) => {
                 Err(
-                    ErrorResponse{
-                        status: false,
-                        message: "Email already exists".to_owned()
-                    }
+                    AppError::Conflict("Email already exists".to_owned())
                 )
             
             }
@@ -103,73 +258,38 @@ impl MongoRepo {
                     created_at: None,
                 }; 
 
-                let user = self
-                    .u_col
-                    .insert_one(doc, None)
-                    .await.ok()
-                    .expect("Error creating user");
-
-                    Ok(user)
+                //the guest cart moves over in the same transaction, a failed merge leaves no account behind
+                self.with_transaction(&(&doc, guest_token), |repo, session, &(doc, guest_token)| {
+                    Box::pin(repo.insert_user(session, doc, guest_token))
+                })
+                .await
 
             }
             
         }
     }
 
-    //function for login user
-    pub async fn login_user(&self, user: LoginUserSchema) -> HttpResponse {
-        match self.find_by_email_pwd(&user.email, &user.password).await.unwrap() {
-            Some(x) => {
-
-                let jwt_secret = "secret".to_owned();
-
-                let id = x.id.unwrap();  //Convert Option<ObjectId> to ObjectId using unwrap()
-
-                let now = Utc::now();
-                let iat = now.timestamp() as usize;
-                
-                let exp = (now + Duration::minutes(60)).timestamp() as usize;
-                let claims: TokenClaims = TokenClaims {
-                    sub: id.to_string(),
-                    exp,
-                    iat,
-                };
-                
+    //inserts the user on the session and publishes UserRegistered with it, then takes over the guest cart
+    async fn insert_user(&self, session: &mut ClientSession, user: &User, guest_token: Option<&str>) -> Result<InsertOneResult, AppError> {
+        //the unique email index settles concurrent sign ups the check before let through
+        let inserted = match self.u_col.insert_one_with_session(user, None, session).await {
+            Ok(inserted) => inserted,
+            Err(error) if is_duplicate_key(&error) => return Err(AppError::Conflict("Email already exists".to_owned())),
+            Err(error) => return Err(error.into()),
+        };
 
-                let token = encode(
-                    &Header::default(),
-                    &claims,
-                    &EncodingKey::from_secret(jwt_secret.as_ref()),
-                )
-                .unwrap();
-
-                let cookie = Cookie::build("token", token.to_owned())
-                    .path("/")
-                    .max_age(ActixWebDuration::new(60 * 60, 0))
-                    .http_only(true)
-                    .finish();
-
-                // Ok(LoginResponse {
-                //     status: true,
-                //     token,
-                //     message: "You have successfully logged in.".to_string(),
-                // })
-                
-
-                HttpResponse::Ok()
-                    .cookie(cookie)
-                    .json(json!({"status" :  "success", "token": token}))
-            },
+        let user_id = inserted.inserted_id.as_object_id().map(|id| id.to_hex()).unwrap_or_default();
+        self.publish_with_session(DomainEvent::UserRegistered {
+            user_id: user_id.to_owned(),
+            email: user.email.to_owned(),
+            name: user.name.to_owned(),
+        }, session).await?;
 
-            None => {
-                return HttpResponse::BadRequest()
-                .json(ErrorResponse{
-                    status: false,
-                    message: "Invalid username or password".to_owned()
-                })
-            }
+        if let Some(guest_id) = guest_token.and_then(guest_cart_id) {
+            self.merge_guest_lines(session, &guest_id, &user_id).await?;
         }
 
+        Ok(inserted)
     }
 
     ////----------------------  END - User handler function ----------------------------- ////
@@ -179,7 +299,7 @@ impl MongoRepo {
     ////----------------------  START - Todo List handler function ----------------------------- ////
 
     //handler to validate the user
-    pub async fn validate_user(&self, token: &str) -> Result<Option<User>, HttpResponse>{
+    pub async fn validate_user(&self, token: &str) -> Result<Option<User>, AppError>{
         let secret_key = "secret".to_owned();
     
         let var = secret_key;
@@ -190,238 +310,373 @@ impl MongoRepo {
             &Validation::new(Algorithm::HS256),
         ); 
 
-        println!("decode: {:?}", decode);
-
         match decode {
             Ok(decoded) => {
-
-                println!("object_id{:?}", decoded.claims.sub.to_owned());
-
                 let id = decoded.claims.sub;
 
-                let bson_id = ObjectId::parse_str(id).unwrap(); //used to convert <String> to <bjectId>
+                //guest cart tokens carry no user id
+                let bson_id = match ObjectId::parse_str(id) {
+                    Ok(bson_id) => bson_id,
+                    Err(_) => return Ok(None),
+                };
 
                 let user = self
                     .u_col
                     .find_one( doc! {"_id" : bson_id }, None)
-                    .await.ok()
-                    .expect("Error finding user");
+                    .await?;
 
-                println!("{:?}", user);
-        
                 Ok(user)
 
             }
-            Err(_) => Err(
-                //HttpResponse::BadRequest().json(json!({"status" :  "fail", "message": "Invalid token"})))
-                HttpResponse::BadRequest().json(ErrorResponse{
-                    status: false,
-                    message: "Invalid token".to_owned()
-                }))
-            
+            Err(_) => Err(AppError::Unauthorized("Invalid token".to_owned()))
+
+        }
+    }
+
+    //handler to validate an admin user
+    pub async fn validate_admin(&self, token: &str) -> Result<User, AppError> {
+        match self.validate_user(token).await {
+            Ok(Some(user)) if self.admin_emails.contains(&user.email.to_lowercase()) => Ok(user),
+            Ok(Some(_)) => Err(AppError::Forbidden("Admin access required".to_owned())),
+            _ => Err(AppError::Unauthorized("Invalid token".to_owned())),
         }
     }
 
     //create todo list
-    pub async fn create_cart(&self, token: &str, new_cart: Cart) -> Result<InsertOneResult, ErrorResponse> {
-        match self.validate_user(token).await.unwrap(){
-            Some(x) => {
-                let user_id = x.id.unwrap().to_string();
+    pub async fn create_cart(&self, access: &CartAccess, new_cart: Cart) -> Result<Cart, AppError> {
+        access.ensure_editor()?;
+
+        if new_cart.qty <= 0.0 {
+            return Err(AppError::Validation("Quantity must be greater than zero".to_owned()));
+        }
+
+        let new_cart = self.resolve_line(new_cart).await?;
+
+        //the lookup, the stock and the write share one transaction, a line changed in between retries it
+        self.with_transaction(&(access, &new_cart), |repo, session, &(access, new_cart)| {
+            Box::pin(repo.add_cart_line(session, access, new_cart))
+        })
+        .await
+    }
 
+    //adds a line or grows the matching one on the session, the history and the outbox get it in the same transaction
+    async fn add_cart_line(&self, session: &mut ClientSession, access: &CartAccess, new_cart: &Cart) -> Result<Cart, AppError> {
+        let user_id = &access.owner;
+
+        //the same variant with the same options only grows the existing line
+        let existing = self
+            .cart_col
+            .find_one_with_session(self.line_filter(user_id, new_cart), None, session)
+            .await?;
+
+        let data = match &existing {
+            Some(existing) => {
+                let qty = existing.qty + new_cart.qty;
+                self.hold_line_stock(session, existing, qty).await?;
+
+                let options = FindOneAndUpdateOptions::builder()
+                    .return_document(ReturnDocument::After)
+                    .build();
+
+                //only the version that was read is updated, otherwise the transaction runs again
+                self.cart_col
+                    .find_one_and_update_with_session(
+                        doc! {"_id": existing.id, "version": existing.version, "deletedAt": not_deleted()},
+                        doc! {"$set": {"price": new_cart.price, "qty": qty, "_total": new_cart.price * qty}, "$inc": {"version": 1}},
+                        options,
+                        session,
+                    )
+                    .await?
+                    .ok_or_else(|| AppError::Transient("The cart line changed while it was updated".to_owned()))?
+            },
+            None => {
                 let data = Cart {
-                    id: None,
-                    user_id: Some(user_id),
-                    product_name: new_cart.product_name,
+                    id: Some(ObjectId::new()),
+                    user_id: Some(user_id.to_owned()),
+                    product_name: new_cart.product_name.to_owned(),
+                    sku: new_cart.sku.to_owned(),
+                    variant: new_cart.variant.to_owned(),
+                    options: new_cart.options.to_owned(),
                     price: new_cart.price,
                     qty: new_cart.qty,
                     total: (Some(new_cart.price*new_cart.qty)),
-                    created_at: Some(Utc::now())
+                    created_at: Some(Utc::now()),
+                    //guest items expire on their own through the TTL index
+                    expires_at: is_guest_cart(user_id).then(|| Utc::now() + self.guest_cart_ttl),
+                    updated_at: Some(Utc::now()),
+                    abandoned_at: None,
+                    version: Some(1),
+                    added_by: Some(access.actor.to_owned()),
+                    deleted_at: None
                 };
 
-                // // Create a HashMap and insert the new_todo into it
-                // let mut todo_list = HashMap::new();
-                // todo_list.insert(user_id, new_data);
-
-                // let doc = TodoList {
-                //     list: todo_list,
-                // };
+                //hold the stock right away when reservations are enabled, otherwise just validate it
+                self.hold_line_stock(session, &data, data.qty).await?;
 
-                let todo_doc = self 
-                    .cart_col
-                    .insert_one(data, None)
-                    .await
-                    .ok()
-                    .expect("Error creating cart");
+                self.cart_col
+                    .insert_one_with_session(&data, None, session)
+                    .await?;
 
-                println!("{:?}", todo_doc);
-                Ok(todo_doc)
+                data
             },
-            None => {
-                Err(ErrorResponse {
-                    status: false,
-                    message: "Not found user".to_string(),
-                })
-            }
-        }
-    }
+        };
+
+        self.touch_cart_with_session(user_id, session).await?;
+        self.record_cart_event_with_session(CartEvent::new(user_id, &access.actor, CartAction::Add, existing, Some(data.clone())), session).await?;
 
+        Ok(data)
+    }
 
 //handler to list all the Todos specified to User
-    pub async fn list_all_carts_by_user(&self, token: &str) -> Result<Vec<Cart>, ErrorResponse> {
-        match self.validate_user(token).await.unwrap(){
-            Some(x) => {
+    pub async fn list_all_carts_by_user(&self, access: &CartAccess) -> Result<Vec<Cart>, AppError> {
+        let user_id = &access.owner;
 
-                let user_id = x.id.unwrap().to_string();
+        let doc = doc! {
+            "_uid": user_id,
+            "deletedAt": not_deleted()
+        };
 
-                let doc = doc! {
-                    "_uid": user_id
-                };
+        let mut cart_doc = self 
+            .cart_col
+            .find(doc, None)
+            .await?;
 
-                let mut cart_doc = self 
-                    .cart_col
-                    .find(doc, None)
-                    .await
-                    .ok()
-                    .expect("Error geting cart data");
-
-                    let mut cart_vec = Vec::new();
-
-                    while let Some(doc) = cart_doc.next().await {
-
-                        match doc {
-                            Ok(data) => {
-                                cart_vec.push(data)
-                            },
-                            Err(err) => {
-                                eprintln!("Error finding cart: {:?}", err)
-                            },
-                        }
-                    }    
-
-                println!("{:?}", cart_doc);
-                Ok(cart_vec)
-            },
-            None => {
-                Err(ErrorResponse {
-                    status: false,
-                    message: "Not found user".to_string(),
-                })
-            }
+            let mut cart_vec = Vec::new();
+
+            while let Some(doc) = cart_doc.next().await {
+
+                match doc {
+                    Ok(data) => {
+                        cart_vec.push(data)
+                    },
+                    Err(err) => {
+                        eprintln!("Error finding cart: {:?}", err)
+                    },
+                }
+            }    
+
+        Ok(cart_vec)
+    }
+
+    //handler to update cart, the read and the conditional write share one transaction
+    pub async fn update_cart(&self, access: &CartAccess, cart_data: UpdateCart, cart_id: String, if_match: Option<i64>) -> Result<Cart, CartWriteError> {
+        access.ensure_editor()?;
+
+        let cart_id = ObjectId::parse_str(cart_id)?;
+
+        if cart_data.qty <= 0.0 {
+            return Err(CartWriteError::Failed(AppError::Validation("Quantity must be greater than zero".to_owned())));
         }
+
+        self.with_transaction(&(access, cart_id, cart_data.qty, if_match), |repo, session, &(access, cart_id, qty, if_match)| {
+            Box::pin(repo.update_cart_line(session, access, cart_id, qty, if_match))
+        })
+        .await?
+        .map_err(CartWriteError::Stale)
     }
 
-    //handler to update cart
-    pub async fn update_cart(&self, token: &str, cart_data: UpdateCart, cart_id: String ) -> Result<UpdateResult, ErrorResponse> {
-        match self.validate_user(token).await.unwrap(){
-            Some(_x) => {
+    //changes the quantity of a line on the session, a stale If-Match gives back the line as it is now
+    async fn update_cart_line(&self, session: &mut ClientSession, access: &CartAccess, cart_id: ObjectId, qty: f64, if_match: Option<i64>) -> Result<Result<Cart, Box<Cart>>, AppError> {
+        let user_id = &access.owner;
 
-                //let _user_id = x.id.unwrap().to_string();
+        let data = self
+            .cart_col
+            .find_one_with_session(doc! {"_id": cart_id, "_uid": &user_id, "deletedAt": not_deleted()}, None, session)
+            .await?
+            .ok_or(AppError::NotFound("Todo Not found".to_owned()))?;
 
-                let cart_id = ObjectId::parse_str(cart_id).unwrap();
+        if if_match.is_some_and(|version| version != data.version()) {
+            return Ok(Err(Box::new(data)));
+        }
 
-                match self.finding_cart(&token, &cart_id).await.unwrap() {
-                    Some(data) => {
+        //the hold follows the new quantity on the session, an aborted transaction gives the units back
+        self.hold_line_stock(session, &data, qty).await?;
 
-                        let filter = doc! {"_id": cart_id};
-                        let cart_price = data.price;
+        let mut fields = doc! {
+            "qty": qty,
+            "_total": data.price * qty
+        };
 
-                        let update_total = cart_price * cart_data.qty;
-    
-                        let new_doc = doc! {
-                            "$set":
-                                {
-                                    "qty": cart_data.qty,
-                                    "_total": update_total
-                                },
-                        };
-                        let updated_doc = self
-                            .cart_col
-                            .update_one(filter, new_doc, None)
-                            .await
-                            .ok()
-                            .expect("Error updating cart");
-                
-                        Ok(updated_doc)
-                    },
-                    None => {
-                        return Err(ErrorResponse {
-                                message: "Todo Not found".to_owned(),
-                                status: false
-                        })
-                    }
-                }
-            },
-            None => {
-                Err(ErrorResponse {
-                    status: false,
-                    message: "Not found user".to_string(),
-                })
-            }
+        //guest items live on for another full period after every change
+        if data.expires_at.is_some() {
+            fields.insert("expiresAt", BsonDateTime::from_chrono(Utc::now() + self.guest_cart_ttl));
         }
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        //a write that got in between since the read aborts the transaction, the retry reads it
+        let updated = self
+            .cart_col
+            .find_one_and_update_with_session(doc! {"_id": cart_id}, doc! {"$set": fields, "$inc": {"version": 1}}, options, session)
+            .await?
+            .ok_or(AppError::NotFound("Todo Not found".to_owned()))?;
+
+        self.touch_cart_with_session(user_id, session).await?;
+        self.record_cart_event_with_session(CartEvent::new(user_id, &access.actor, CartAction::Update, Some(data), Some(updated.clone())), session).await?;
+
+        Ok(Ok(updated))
     }
 
 //handler for finding cart
-pub async fn finding_cart(&self, token: &str, cart_id: &ObjectId) -> Result<Option<Cart>, ErrorResponse> {
-    match self.validate_user(token).await.unwrap(){
-        Some(x) => {
+pub async fn finding_cart(&self, access: &CartAccess, cart_id: &ObjectId) -> Result<Option<Cart>, AppError> {
+    let user_id = &access.owner;
+
+    let todo = self
+    .cart_col
+    .find_one(doc! {
+        "_id" : cart_id,
+        "_uid" : user_id,
+        "deletedAt" : not_deleted()
+    }, None)
+    .await?;
+
+    Ok(todo)
+    
+}
 
-            let user_id = x.id.unwrap().to_string();
+//handler for delete the todo list, the read and the delete share one transaction.
+//The line is only marked as deleted, it can be restored within the restore window
+pub async fn delete_cart(&self, access: &CartAccess, cart_id: String, if_match: Option<i64>) -> Result<UpdateResult, CartWriteError> {
+    access.ensure_editor()?;
 
-            let todo = self
-            .cart_col
-            .find_one(doc! {
-                "_id" : cart_id,
-                "_uid" : user_id
-            }, None)
-            .await.ok()
-            .expect("Error finding cart");
-
-            Ok(todo)
-        },
-        None => Err(ErrorResponse {
-            status: false,
-            message: "Not found user".to_string(),
+    let cart_id = ObjectId::parse_str(&cart_id)?;
+
+    let deleted = self
+        .with_transaction(&(access, cart_id, if_match), |repo, session, &(access, cart_id, if_match)| {
+            Box::pin(repo.delete_cart_line(session, access, cart_id, if_match))
         })
+        .await?
+        .map_err(CartWriteError::Stale)?;
+
+    //the hold goes back once the line is gone for sure
+    self.release_reservation(&cart_id).await?;
+
+    Ok(deleted)
+}
+
+//removes a line on the session, a stale If-Match gives back the line as it is now
+async fn delete_cart_line(&self, session: &mut ClientSession, access: &CartAccess, cart_id: ObjectId, if_match: Option<i64>) -> Result<Result<UpdateResult, Box<Cart>>, AppError> {
+    let user_id = &access.owner;
+
+    //the line as it was goes into the history
+    let before = self
+        .cart_col
+        .find_one_with_session(doc! {"_id": cart_id, "_uid": &user_id, "deletedAt": not_deleted()}, None, session)
+        .await?
+        .ok_or(AppError::NotFound("Todo Not found".to_owned()))?;
+
+    if if_match.is_some_and(|version| version != before.version()) {
+        return Ok(Err(Box::new(before)));
     }
-    
+
+    let delete_doc = self
+        .cart_col
+        .update_one_with_session(doc! {"_id": cart_id}, soft_delete(), None, session)
+        .await?;
+
+    self.touch_cart_with_session(user_id, session).await?;
+    self.record_cart_event_with_session(CartEvent::new(user_id, &access.actor, CartAction::Remove, Some(before), None), session).await?;
+
+    Ok(Ok(delete_doc))
 }
 
-//handler for delete the todo list
-pub async fn delete_cart(&self, token: &str, cart_id: String) -> Result<DeleteResult, ErrorResponse> {
-    match self.validate_user(token).await.unwrap(){
-        Some(x) => {
+//handler to empty the cart, returns the removed count
+pub async fn clear_cart(&self, access: &CartAccess) -> Result<u64, AppError> {
+    access.ensure_editor()?;
+    let user_id = &access.owner;
+    self.delete_cart_lines(access, doc! {"_uid": &user_id, "deletedAt": not_deleted()}).await
+}
+
+//handler to remove the cart items matching a filter, returns the removed count
+pub async fn delete_cart_items(&self, access: &CartAccess, filter: CartDeleteFilter) -> Result<u64, AppError> {
+    access.ensure_editor()?;
+    let user_id = &access.owner;
 
-            let _user_id = x.id.unwrap().to_string();
+    let mut doc = doc! {"_uid": &user_id, "deletedAt": not_deleted()};
+
+    if let Some(product_name) = filter.product_name {
+        doc.insert("product_name", product_name);
+    }
+    if let Some(sku) = filter.sku {
+        doc.insert("sku", sku);
+    }
 
-            let cart_id = ObjectId::parse_str(&cart_id).unwrap();
+    let mut created = doc! {};
+    if let Some(before) = filter.created_before {
+        created.insert("$lt", BsonDateTime::from_chrono(before));
+    }
+    if let Some(after) = filter.created_after {
+        created.insert("$gte", BsonDateTime::from_chrono(after));
+    }
+    if !created.is_empty() {
+        doc.insert("createdAt", created);
+    }
 
-            match self.finding_cart(&token, &cart_id).await.unwrap() {
-                Some(_) => {
+    //an empty filter would clear the whole cart, that is what DELETE /cart is for
+    if doc.len() == 2 {
+        return Err(AppError::Validation("At least one filter is required".to_owned()));
+    }
 
-                    let filter = doc! {"_id": cart_id};
+    self.delete_cart_lines(access, doc).await
+}
 
-                    let delete_doc = self
-                            .cart_col
-                            .delete_one(filter, None)
-                            .await
-                            .ok()
-                            .expect("Error deleting todos");
-            
-                    Ok(delete_doc)
-                },
-                None => {
-                    return Err(ErrorResponse {
-                            message: "Todo Not found".to_owned(),
-                            status: false
-                    })
-                }
-            }
-        },
-        None => Err(ErrorResponse {
-            status: false,
-            message: "Not found user".to_string(),
+//marks the matching lines as deleted in one transaction with their history and outbox records,
+//then gives back the stock they held
+async fn delete_cart_lines(&self, access: &CartAccess, filter: Document) -> Result<u64, AppError> {
+    let cart_ids = self
+        .with_transaction(&(access, &filter), |repo, session, &(access, filter)| {
+            Box::pin(repo.delete_cart_lines_with_session(session, access, filter))
         })
+        .await?;
+
+    if self.reservation_ttl.is_some() {
+        for cart_id in &cart_ids {
+            self.release_reservation(cart_id).await?;
+        }
+    }
+
+    Ok(cart_ids.len() as u64)
+}
+
+//returns the ids of the lines removed on the session
+pub(super) async fn delete_cart_lines_with_session(&self, session: &mut ClientSession, access: &CartAccess, filter: &Document) -> Result<Vec<ObjectId>, AppError> {
+    let user_id = &access.owner;
+
+    let mut cart_doc = self
+        .cart_col
+        .find_with_session(filter.clone(), None, session)
+        .await?;
+
+    let mut lines = Vec::new();
+
+    while let Some(doc) = cart_doc.next(session).await {
+        match doc {
+            Ok(data) => lines.push(data),
+            Err(err) => eprintln!("Error finding cart: {:?}", err),
+        }
+    }
+
+    if lines.is_empty() {
+        return Ok(Vec::new());
     }
+
+    //only the lines that were read are deleted, so the history holds exactly what was removed
+    let cart_ids: Vec<ObjectId> = lines.iter().filter_map(|line| line.id).collect();
+
+    self.cart_col
+        .update_many_with_session(doc! {"_uid": user_id, "_id": {"$in": &cart_ids}}, soft_delete(), None, session)
+        .await?;
+
+    self.touch_cart_with_session(user_id, session).await?;
+
+    self.record_cart_event_with_session(CartEvent {
+        removed: lines,
+        ..CartEvent::new(user_id, &access.actor, CartAction::Clear, None, None)
+    }, session).await?;
+
+    Ok(cart_ids)
 }
 
-}
\ No newline at end of file
+}
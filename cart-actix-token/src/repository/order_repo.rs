@@ -0,0 +1,303 @@
+use chrono::Utc;
+use futures::StreamExt;
+use mongodb::{
+    ClientSession,
+    bson::{doc, oid::ObjectId, to_bson},
+    options::{FindOneAndUpdateOptions, ReturnDocument}};
+
+use crate::error::AppError;
+use crate::model::{cart_model::CartNotice, share_model::CartAccess, user_model::User, order_model::{Order, OrderLine, OrderStatus, CheckoutSchema}, outbox_model::DomainEvent, payment_model::PaymentStatus};
+
+use super::{mongodb_repo::MongoRepo, soft_delete_repo::not_deleted, transaction_repo::find_one_on};
+
+//id of a user read from the database as the orders store it
+fn user_id(user: &User) -> Result<String, AppError> {
+    user.id
+        .map(|id| id.to_string())
+        .ok_or_else(|| AppError::Internal("User has no id".to_owned()))
+}
+
+//round an amount to whole cents
+pub fn round_money(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Order handler function ----------------------------- ////
+
+    //handler to turn the cart of the user into a pending order inside one transaction
+    pub async fn checkout(&self, token: &str, data: CheckoutSchema) -> Result<Result<Order, Vec<CartNotice>>, AppError> {
+        match self.validate_user(token).await?{
+            Some(x) => {
+                let user_id = user_id(&x)?;
+
+                self.with_transaction(&(user_id.as_str(), &data), |repo, session, &(user_id, data)| {
+                    Box::pin(repo.place_order(session, user_id, data))
+                })
+                .await
+            },
+            None => Err(AppError::Unauthorized("Not found user".to_string()))
+        }
+    }
+
+    //snapshot the cart into an order, take its stock and empty the cart, all on the given session
+    //gives back the notices instead when the cart changed since the items were added
+    async fn place_order(&self, session: &mut ClientSession, user_id: &str, data: &CheckoutSchema) -> Result<Result<Order, Vec<CartNotice>>, AppError> {
+        let mut cart_doc = self
+            .cart_col
+            .find_with_session(doc! {"_uid": user_id, "deletedAt": not_deleted()}, None, session)
+            .await?;
+
+        let mut carts = Vec::new();
+
+        while let Some(doc) = cart_doc.next(session).await {
+            match doc {
+                Ok(data) => carts.push(data),
+                Err(err) => eprintln!("Error finding cart: {:?}", err),
+            }
+        }
+
+        if carts.is_empty() {
+            return Err(AppError::Validation("Cart is empty".to_owned()));
+        }
+
+        //checked on the session, a price or stock change cannot slip in between the check and the order
+        let notices = self.revalidate_lines_on(Some(&mut *session), &carts).await?;
+        if !notices.is_empty() {
+            return Ok(Err(notices));
+        }
+
+        let mut items = Vec::new();
+
+        for cart in carts {
+            //units already held by a live reservation only leave the stock, not the available count
+            let held = match cart.id {
+                Some(cart_id) => self
+                    .reservation_col
+                    .find_one_and_delete_with_session(doc! {"_cid": cart_id}, None, session)
+                    .await?
+                    .map(|r| r.qty)
+                    .unwrap_or(0.0),
+                None => 0.0,
+            };
+
+            let taken = self
+                .inventory_col
+                .find_one_and_update_with_session(
+                    doc! {"sku": cart.stock_key(), "available": {"$gte": cart.qty - held}},
+                    doc! {"$inc": {"stock": -cart.qty, "available": held - cart.qty}},
+                    None,
+                    session,
+                )
+                .await?;
+
+            if taken.is_none() {
+                return Err(AppError::Conflict(format!("Insufficient stock for {}", cart.product_name)));
+            }
+
+            items.push(OrderLine {
+                total: round_money(cart.price * cart.qty),
+                product_name: cart.product_name,
+                sku: cart.sku,
+                variant: cart.variant,
+                options: cart.options,
+                price: cart.price,
+                qty: cart.qty,
+                discount: 0.0,
+            });
+        }
+
+        let subtotal = round_money(items.iter().map(|i| i.total).sum());
+        let discount = round_money(items.iter().map(|i| i.discount).sum());
+        let tax = round_money((subtotal - discount) * self.tax_rate);
+
+        let shipping_address = data.shipping_address.clone();
+        let billing_address = data.billing_address.clone().unwrap_or_else(|| shipping_address.clone());
+
+        let order = Order {
+            id: None,
+            user_id: user_id.to_owned(),
+            items,
+            subtotal,
+            discount,
+            tax_rate: self.tax_rate,
+            tax,
+            total: round_money(subtotal - discount + tax),
+            shipping_address,
+            billing_address,
+            status: OrderStatus::Pending,
+            payment: None,
+            created_at: Some(Utc::now()),
+        };
+
+        let order_doc = self
+            .order_col
+            .insert_one_with_session(order.clone(), None, session)
+            .await?;
+
+        //the ordered lines leave the cart like a clear does, with their history and outbox records
+        let access = CartAccess::own(user_id.to_owned());
+        self.delete_cart_lines_with_session(session, &access, &doc! {"_uid": user_id, "deletedAt": not_deleted()})
+            .await?;
+
+        let order = Order { id: order_doc.inserted_id.as_object_id(), ..order };
+
+        if let Some(order_id) = order.id {
+            self.publish_with_session(DomainEvent::OrderPlaced {
+                user_id: user_id.to_owned(),
+                order_id,
+                total: order.total,
+            }, session).await?;
+        }
+
+        Ok(Ok(order))
+    }
+
+    //handler to list all the orders of the user
+    pub async fn list_orders_by_user(&self, token: &str) -> Result<Vec<Order>, AppError> {
+        match self.validate_user(token).await?{
+            Some(x) => {
+                let user_id = user_id(&x)?;
+
+                let mut order_doc = self
+                    .order_col
+                    .find(doc! {"_uid": user_id}, None)
+                    .await?;
+
+                let mut order_vec = Vec::new();
+
+                while let Some(doc) = order_doc.next().await {
+                    match doc {
+                        Ok(data) => order_vec.push(data),
+                        Err(err) => eprintln!("Error finding order: {:?}", err),
+                    }
+                }
+
+                Ok(order_vec)
+            },
+            None => Err(AppError::Unauthorized("Not found user".to_string()))
+        }
+    }
+
+    //handler for finding an order of the user
+    pub async fn finding_order(&self, token: &str, order_id: &ObjectId) -> Result<Option<Order>, AppError> {
+        match self.validate_user(token).await?{
+            Some(x) => {
+                let user_id = user_id(&x)?;
+
+                let order = self
+                    .order_col
+                    .find_one(doc! {"_id": order_id, "_uid": user_id}, None)
+                    .await?;
+
+                Ok(order)
+            },
+            None => Err(AppError::Unauthorized("Not found user".to_string()))
+        }
+    }
+
+    //handler to move an order to its next status, rejects moves the state machine does not allow
+    pub async fn transition_order(&self, order_id: &ObjectId, next: OrderStatus) -> Result<Order, AppError> {
+        let order = self
+            .order_col
+            .find_one(doc! {"_id": order_id}, None)
+            .await?;
+
+        let current = match order {
+            Some(data) => data.status,
+            None => return Err(AppError::NotFound("Order Not found".to_owned())),
+        };
+
+        if !current.can_transition_to(next) {
+            return Err(AppError::Conflict(format!("Cannot move order from {} to {}", current.as_str(), next.as_str())));
+        }
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        //only applies if nobody moved the order since we read it
+        let updated = self
+            .order_col
+            .find_one_and_update(
+                doc! {"_id": order_id, "status": current.as_str()},
+                doc! {"$set": {"status": next.as_str()}},
+                options,
+            )
+            .await?;
+
+        updated.ok_or(AppError::Conflict("Order status changed concurrently".to_owned()))
+    }
+
+    //handler to cancel a pending order of the user and put its stock back
+    pub async fn cancel_order(&self, token: &str, order_id: String) -> Result<Order, AppError> {
+        let order_id = ObjectId::parse_str(order_id)?;
+
+        match self.finding_order(token, &order_id).await? {
+            Some(data) => {
+                if !data.status.can_transition_to(OrderStatus::Cancelled) {
+                    return Err(AppError::Conflict(format!("Cannot move order from {} to {}", data.status.as_str(), OrderStatus::Cancelled.as_str())));
+                }
+
+                //an authorization that was never captured has to be released at the gateway
+                let mut voided = None;
+                if let Some(payment) = &data.payment {
+                    if matches!(payment.status, PaymentStatus::Authorized | PaymentStatus::Pending | PaymentStatus::RequiresAction) {
+                        voided = Some(self.payments.void(&payment.payment_id).await?.status);
+                    }
+                }
+
+                self.with_transaction(&(order_id, voided), |repo, session, &(order_id, voided)| {
+                    Box::pin(repo.cancel_placed_order(session, order_id, voided))
+                })
+                .await
+            },
+            None => Err(AppError::NotFound("Order Not found".to_owned()))
+        }
+    }
+
+    //moves the order to cancelled with the status of its voided payment and puts its stock back, all on the session
+    async fn cancel_placed_order(&self, session: &mut ClientSession, order_id: ObjectId, voided: Option<PaymentStatus>) -> Result<Order, AppError> {
+        let current = find_one_on(&self.order_col, doc! {"_id": order_id}, Some(&mut *session))
+            .await?
+            .ok_or(AppError::NotFound("Order Not found".to_owned()))?
+            .status;
+
+        if !current.can_transition_to(OrderStatus::Cancelled) {
+            return Err(AppError::Conflict(format!("Cannot move order from {} to {}", current.as_str(), OrderStatus::Cancelled.as_str())));
+        }
+
+        let mut fields = doc! {"status": OrderStatus::Cancelled.as_str()};
+        if let Some(status) = voided {
+            fields.insert("payment.status", to_bson(&status)?);
+        }
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        //only applies if nobody moved the order since we read it
+        let order = self
+            .order_col
+            .find_one_and_update_with_session(doc! {"_id": order_id, "status": current.as_str()}, doc! {"$set": fields}, options, session)
+            .await?
+            .ok_or(AppError::Conflict("Order status changed concurrently".to_owned()))?;
+
+        for item in &order.items {
+            self.inventory_col
+                .update_one_with_session(
+                    doc! {"sku": item.stock_key()},
+                    doc! {"$inc": {"stock": item.qty, "available": item.qty}},
+                    None,
+                    session,
+                )
+                .await?;
+        }
+
+        Ok(order)
+    }
+
+    ////----------------------  END - Order handler function ----------------------------- ////
+
+}
@@ -0,0 +1,120 @@
+use chrono::{Duration, Utc};
+use mongodb::{
+    ClientSession,
+    bson::{doc, DateTime as BsonDateTime},
+    options::{FindOneAndUpdateOptions, FindOneOptions, ReturnDocument}};
+
+use crate::error::AppError;
+use crate::model::outbox_model::{DomainEvent, OutboxMessage};
+use crate::outbox::EventSink;
+
+use super::mongodb_repo::MongoRepo;
+
+//events handed to the sink in one run of the relay
+const RELAY_BATCH: u64 = 100;
+
+//how long a relay may take to deliver the event it claimed before another relay takes it over
+fn claim_lease() -> Duration {
+    Duration::seconds(60)
+}
+
+//wait before the next attempt of a failed delivery, doubling up to five minutes
+fn retry_delay(attempts: i64) -> Duration {
+    Duration::seconds(2_i64.saturating_pow(attempts.clamp(0, 16) as u32).min(300))
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Outbox handler function ----------------------------- ////
+
+    //handler to write a domain event to the outbox inside the transaction of the change it describes
+    pub async fn publish_with_session(&self, event: DomainEvent, session: &mut ClientSession) -> Result<(), AppError> {
+        self.outbox_col
+            .insert_one_with_session(OutboxMessage::new(event), None, session)
+            .await?;
+
+        Ok(())
+    }
+
+    //claims the oldest undelivered event. While that one is claimed or waiting for its retry
+    //nothing is claimed, so the sink gets the events in the order they were written
+    async fn claim_outbox_message(&self) -> Result<Option<OutboxMessage>, AppError> {
+        let now = Utc::now();
+
+        let head = self
+            .outbox_col
+            .find_one(doc! {"deliveredAt": {"$exists": false}}, FindOneOptions::builder().sort(doc! {"_id": 1}).build())
+            .await?;
+
+        let head = match head {
+            Some(head) if head.claimed_until.is_none_or(|until| until <= now) => head,
+            _ => return Ok(None),
+        };
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        //a relay that read the same head in between wins the claim, this one stops for now
+        let claimed = self
+            .outbox_col
+            .find_one_and_update(
+                doc! {
+                    "_id": head.id,
+                    "deliveredAt": {"$exists": false},
+                    "$or": [{"claimedUntil": {"$exists": false}}, {"claimedUntil": {"$lte": BsonDateTime::from_chrono(now)}}]
+                },
+                doc! {"$set": {"claimedUntil": BsonDateTime::from_chrono(now + claim_lease())}, "$inc": {"attempts": 1}},
+                options,
+            )
+            .await?;
+
+        Ok(claimed)
+    }
+
+    //handler to deliver the pending events to the sink, returns the delivered count.
+    //A failed delivery stops the run and is tried again after a growing delay
+    pub async fn relay_outbox(&self, sink: &dyn EventSink) -> Result<u64, AppError> {
+        let mut delivered = 0;
+
+        while delivered < RELAY_BATCH {
+            let message = match self.claim_outbox_message().await? {
+                Some(message) => message,
+                None => break,
+            };
+
+            match sink.deliver(&message).await {
+                Ok(()) => {
+                    self.outbox_col
+                        .update_one(
+                            doc! {"_id": message.id},
+                            doc! {"$set": {"deliveredAt": BsonDateTime::now()}, "$unset": {"claimedUntil": "", "lastError": ""}},
+                            None,
+                        )
+                        .await?;
+
+                    delivered += 1;
+                },
+                Err(error) => {
+                    let retry_at = Utc::now() + retry_delay(message.attempts);
+
+                    self.outbox_col
+                        .update_one(
+                            doc! {"_id": message.id},
+                            doc! {"$set": {"claimedUntil": BsonDateTime::from_chrono(retry_at), "lastError": error.message()}},
+                            None,
+                        )
+                        .await?;
+
+                    let message = format!("Delivering {} {} to the {} sink failed: {}", message.event.name(), message.id.unwrap_or_default(), sink.name(), error);
+                    return Err(error.with_message(message));
+                },
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    ////----------------------  END - Outbox handler function ----------------------------- ////
+
+}
@@ -0,0 +1,174 @@
+use mongodb::bson::{doc, oid::ObjectId, to_bson};
+
+use crate::error::AppError;
+use crate::model::{order_model::{Order, OrderStatus}, payment_model::{Payment, PaymentResult, PaymentStatus, PayOrderSchema}};
+
+use super::mongodb_repo::MongoRepo;
+
+impl MongoRepo {
+
+    ////----------------------  START - Payment handler function ----------------------------- ////
+
+    //handler to authorize the payment of a pending order of the user
+    pub async fn pay_order(&self, token: &str, order_id: String, data: PayOrderSchema) -> Result<(Order, PaymentResult), AppError> {
+        let order_id = ObjectId::parse_str(order_id)?;
+
+        let order = match self.finding_order(token, &order_id).await? {
+            Some(order) => order,
+            None => return Err(AppError::NotFound("Order Not found".to_owned())),
+        };
+
+        if order.status != OrderStatus::Pending {
+            return Err(AppError::Conflict("Only pending orders can be paid".to_owned()));
+        }
+
+        //a declined or voided payment may be retried, anything else is still in flight
+        if let Some(payment) = &order.payment {
+            if !matches!(payment.status, PaymentStatus::Declined | PaymentStatus::Voided) {
+                return Err(AppError::Conflict("Payment already in progress".to_owned()));
+            }
+        }
+
+        let result = self
+            .payments
+            .authorize(&order_id.to_hex(), order.total, &data.payment_method)
+            .await?;
+
+        let payment = Payment {
+            provider: self.payments.name().to_owned(),
+            payment_id: result.payment_id.to_owned(),
+            amount: order.total,
+            status: result.status,
+        };
+
+        self.order_col
+            .update_one(
+                doc! {"_id": order_id},
+                doc! {"$set": {"payment": to_bson(&payment)?}},
+                None,
+            )
+            .await?;
+
+        let order = self.apply_payment_status(&order_id, result.status).await?;
+
+        Ok((order, result))
+    }
+
+    //handler to refund a paid order, only for admins
+    pub async fn refund_order(&self, token: &str, order_id: String) -> Result<Order, AppError> {
+        self.validate_admin(token).await?;
+        let order_id = ObjectId::parse_str(order_id)?;
+
+        let order = match self.order_col.find_one(doc! {"_id": order_id}, None).await? {
+            Some(order) => order,
+            None => return Err(AppError::NotFound("Order Not found".to_owned())),
+        };
+
+        let payment = match order.payment {
+            Some(payment) if payment.status == PaymentStatus::Captured && order.status.can_transition_to(OrderStatus::Refunded) => payment,
+            _ => return Err(AppError::Conflict("Only paid orders can be refunded".to_owned())),
+        };
+
+        let result = self.payments.refund(&payment.payment_id, payment.amount).await?;
+
+        self.apply_payment_status(&order_id, result.status).await
+    }
+
+    //handler to check that a payment belongs to an order of the user before the user acts on it
+    pub async fn check_payment_owner(&self, token: &str, payment_id: &str) -> Result<(), AppError> {
+        let user_id = match self.validate_user(token).await? {
+            Some(user) => user.id.map(|id| id.to_string()).unwrap_or_default(),
+            None => return Err(AppError::Unauthorized("Invalid token".to_owned())),
+        };
+
+        let order = self
+            .order_col
+            .find_one(doc! {"_uid": user_id, "payment.payment_id": payment_id}, None)
+            .await?;
+
+        match order {
+            Some(_) => Ok(()),
+            None => Err(AppError::NotFound("Payment not found".to_owned())),
+        }
+    }
+
+    //handler for provider webhooks, the signature is checked before anything changes
+    pub async fn handle_payment_webhook(&self, payload: &str, signature: &str) -> Result<Order, AppError> {
+        let event = self.payments.verify_webhook(payload, signature)?;
+
+        let order = self
+            .order_col
+            .find_one(doc! {"payment.payment_id": &event.payment_id}, None)
+            .await?;
+
+        match order.and_then(|order| order.id) {
+            Some(order_id) => self.apply_payment_status(&order_id, event.status).await,
+            None => Err(AppError::NotFound("Order Not found".to_owned())),
+        }
+    }
+
+    //move the order along with the payment status the provider reported
+    async fn apply_payment_status(&self, order_id: &ObjectId, status: PaymentStatus) -> Result<Order, AppError> {
+        let order = self
+            .order_col
+            .find_one(doc! {"_id": order_id}, None)
+            .await?
+            .ok_or(AppError::NotFound("Order Not found".to_owned()))?;
+
+        let payment = match &order.payment {
+            Some(payment) => payment,
+            None => return Err(AppError::Validation("Order has no payment".to_owned())),
+        };
+
+        match status {
+            PaymentStatus::Authorized => {
+                //repeated notifications for an already captured payment change nothing
+                if payment.status == PaymentStatus::Captured {
+                    return Ok(order);
+                }
+
+                let captured = self.payments.capture(&payment.payment_id).await?;
+                self.set_payment_status(order_id, captured.status).await?;
+
+                self.transition_order(order_id, OrderStatus::Paid).await
+            },
+            PaymentStatus::Captured => {
+                self.set_payment_status(order_id, status).await?;
+
+                if order.status == OrderStatus::Pending {
+                    return self.transition_order(order_id, OrderStatus::Paid).await;
+                }
+                Ok(Order { payment: Some(Payment { status, ..payment.clone() }), ..order })
+            },
+            PaymentStatus::Refunded => {
+                self.set_payment_status(order_id, status).await?;
+
+                if order.status == OrderStatus::Refunded {
+                    return Ok(Order { payment: Some(Payment { status, ..payment.clone() }), ..order });
+                }
+                self.transition_order(order_id, OrderStatus::Refunded).await
+            },
+            PaymentStatus::Pending | PaymentStatus::RequiresAction | PaymentStatus::Declined | PaymentStatus::Voided => {
+                self.set_payment_status(order_id, status).await?;
+
+                Ok(Order { payment: Some(Payment { status, ..payment.clone() }), ..order })
+            },
+        }
+    }
+
+    //handler to record the latest payment status on the order
+    pub async fn set_payment_status(&self, order_id: &ObjectId, status: PaymentStatus) -> Result<(), AppError> {
+        self.order_col
+            .update_one(
+                doc! {"_id": order_id},
+                doc! {"$set": {"payment.status": to_bson(&status)?}},
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    ////----------------------  END - Payment handler function ----------------------------- ////
+
+}
@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use futures::StreamExt;
+use mongodb::{
+    ClientSession,
+    bson::{doc, oid::ObjectId, Bson, Document},
+    options::{FindOneAndUpdateOptions, ReturnDocument}};
+
+use crate::error::AppError;
+use crate::model::{cart_model::Cart, product_model::{Product, ProductSchema, ProductView, Variant, VariantView}};
+
+use super::{mongodb_repo::MongoRepo, soft_delete_repo::not_deleted, transaction_repo::find_one_on};
+
+fn invalid_product(message: &str) -> AppError {
+    AppError::Validation(message.to_owned())
+}
+
+//check that every variant picks exactly one known value per axis and no two variants collide
+pub(super) fn validate_product(data: &ProductSchema) -> Result<(), AppError> {
+    if data.name.trim().is_empty() {
+        return Err(invalid_product("Product name is required"));
+    }
+
+    if data.variants.is_empty() {
+        return Err(invalid_product("A product needs at least one variant"));
+    }
+
+    let mut axis_names = HashSet::new();
+    for axis in &data.axes {
+        if axis.values.is_empty() || !axis_names.insert(axis.name.as_str()) {
+            return Err(invalid_product(&format!("Invalid option axis {}", axis.name)));
+        }
+    }
+
+    let mut skus = HashSet::new();
+    let mut combinations = HashSet::new();
+
+    for variant in &data.variants {
+        if variant.sku.trim().is_empty() || !skus.insert(variant.sku.as_str()) {
+            return Err(invalid_product(&format!("Duplicate or empty SKU {}", variant.sku)));
+        }
+
+        let covers_axes = variant.options.len() == data.axes.len()
+            && data.axes.iter().all(|axis| {
+                variant.options.get(&axis.name).is_some_and(|value| axis.values.contains(value))
+            });
+
+        if !covers_axes {
+            return Err(invalid_product(&format!("Variant {} must pick one value of every option axis", variant.sku)));
+        }
+
+        if !combinations.insert(&variant.options) {
+            return Err(invalid_product(&format!("Variant {} repeats the options of another variant", variant.sku)));
+        }
+    }
+
+    Ok(())
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Product handler function ----------------------------- ////
+
+    //handler to create a product with its variants, their prices and stock are written to the inventory
+    pub async fn create_product(&self, token: &str, data: ProductSchema) -> Result<ProductView, AppError> {
+        self.validate_admin(token).await?;
+        validate_product(&data)?;
+
+        let skus: Vec<&str> = data.variants.iter().map(|v| v.sku.as_str()).collect();
+
+        let taken = self
+            .product_col
+            .find_one(doc! {"variants.sku": {"$in": &skus}}, None)
+            .await?;
+
+        if taken.is_some() {
+            return Err(AppError::Conflict("SKU already used by another product".to_owned()));
+        }
+
+        for variant in &data.variants {
+            self.set_inventory(&variant.sku, Some(&data.name), variant.stock, Some(variant.price)).await?;
+        }
+
+        let product = Product {
+            id: None,
+            name: data.name.trim().to_owned(),
+            axes: data.axes,
+            variants: data
+                .variants
+                .into_iter()
+                .map(|v| Variant { sku: v.sku, options: v.options })
+                .collect(),
+            customizations: data.customizations,
+            active: true,
+            created_at: Some(Utc::now()),
+        };
+
+        let product_doc = self
+            .product_col
+            .insert_one(product.clone(), None)
+            .await?;
+
+        Ok(self.product_view(Product { id: product_doc.inserted_id.as_object_id(), ..product }).await)
+    }
+
+    //handler to discontinue a product or bring it back, admins only
+    pub async fn set_product_status(&self, token: &str, product_id: String, active: bool) -> Result<ProductView, AppError> {
+        self.validate_admin(token).await?;
+        let product_id = ObjectId::parse_str(product_id)?;
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let product = self
+            .product_col
+            .find_one_and_update(doc! {"_id": product_id}, doc! {"$set": {"active": active}}, options)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Product Not found".to_owned()))?;
+
+        Ok(self.product_view(product).await)
+    }
+
+    //handler to find a product with current variant prices and stock
+    pub async fn find_product(&self, product_id: String) -> Result<Option<ProductView>, AppError> {
+        let product_id = ObjectId::parse_str(product_id)?;
+
+        let product = self
+            .product_col
+            .find_one(doc! {"_id": product_id}, None)
+            .await?;
+
+        match product {
+            Some(product) => Ok(Some(self.product_view(product).await)),
+            None => Ok(None),
+        }
+    }
+
+    //handler to list all products
+    pub async fn list_products(&self) -> Result<Vec<ProductView>, AppError> {
+        let mut product_doc = self
+            .product_col
+            .find(None, None)
+            .await?;
+
+        let mut product_vec = Vec::new();
+
+        while let Some(doc) = product_doc.next().await {
+            match doc {
+                Ok(data) => product_vec.push(self.product_view(data).await),
+                Err(err) => eprintln!("Error finding product: {:?}", err),
+            }
+        }
+
+        Ok(product_vec)
+    }
+
+    async fn product_view(&self, product: Product) -> ProductView {
+        let mut variants = Vec::new();
+
+        for variant in product.variants {
+            let inventory = self.find_inventory(&variant.sku).await.ok().flatten();
+
+            variants.push(VariantView {
+                price: inventory.as_ref().and_then(|i| i.price),
+                available: inventory.map(|i| i.available.max(0.0)).unwrap_or(0.0),
+                sku: variant.sku,
+                options: variant.options,
+            });
+        }
+
+        ProductView {
+            id: product.id,
+            name: product.name,
+            axes: product.axes,
+            variants,
+            customizations: product.customizations,
+            active: product.active,
+        }
+    }
+
+    //handler to price a cart line from the catalog, variant lines get the variant price plus customization surcharges
+    pub async fn resolve_line(&self, line: Cart) -> Result<Cart, AppError> {
+        self.resolve_line_on(None, line).await
+    }
+
+    //same as resolve_line, the catalog is read on the session when one is given
+    pub(super) async fn resolve_line_on(&self, mut session: Option<&mut ClientSession>, line: Cart) -> Result<Cart, AppError> {
+        //an empty option set is the same line as no options at all
+        let options = line.options.clone().filter(|options| !options.is_empty());
+
+        let sku = match &line.sku {
+            Some(sku) => sku.to_owned(),
+            None => {
+                let product = find_one_on(&self.product_col, doc! {"name": &line.product_name}, session.as_deref_mut()).await?;
+
+                if product.is_some() {
+                    return Err(invalid_product(&format!("Choose a variant of {}", line.product_name)));
+                }
+
+                //lines of products outside the catalog keep their price unless the inventory has one
+                let price = self.current_price_on(session, &line.product_name).await.unwrap_or(line.price);
+                return Ok(Cart { price, variant: None, options, ..line });
+            }
+        };
+
+        let product = find_one_on(&self.product_col, doc! {"variants.sku": &sku}, session.as_deref_mut())
+            .await?
+            .ok_or_else(|| AppError::NotFound("Variant not found".to_owned()))?;
+
+        if !product.active {
+            return Err(AppError::Conflict(format!("{} is discontinued", product.name)));
+        }
+
+        let variant = product
+            .variants
+            .iter()
+            .find(|v| v.sku == sku)
+            .ok_or_else(|| AppError::NotFound("Variant not found".to_owned()))?;
+
+        let base = self
+            .current_price_on(session, &sku)
+            .await
+            .ok_or_else(|| AppError::Conflict("Variant has no price".to_owned()))?;
+
+        let surcharge: f64 = product
+            .customizations
+            .iter()
+            .filter(|c| options.as_ref().is_some_and(|o| o.get(&c.name).is_some_and(|value| !value.is_empty())))
+            .map(|c| c.price)
+            .sum();
+
+        Ok(Cart {
+            product_name: product.name.to_owned(),
+            variant: Some(variant.options.clone()),
+            options,
+            price: base + surcharge,
+            ..line
+        })
+    }
+
+    //filter matching the line of a user that is the same as the given one, same variant and same options
+    pub fn line_filter(&self, user_id: &str, line: &Cart) -> Document {
+        let mut filter = doc! {"_uid": user_id, "product_name": &line.product_name, "deletedAt": not_deleted()};
+
+        match &line.sku {
+            Some(sku) => filter.insert("sku", sku),
+            None => filter.insert("sku", doc! {"$exists": false}),
+        };
+
+        match line.options.as_ref().filter(|options| !options.is_empty()) {
+            Some(options) => filter.insert("options", options.iter().map(|(name, value)| (name.to_owned(), Bson::String(value.to_owned()))).collect::<Document>()),
+            None => filter.insert("options", doc! {"$exists": false}),
+        };
+
+        filter
+    }
+
+    ////----------------------  END - Product handler function ----------------------------- ////
+
+}
@@ -0,0 +1,209 @@
+use mongodb::{
+    ClientSession,
+    bson::{doc, Document},
+    options::{FindOneAndUpdateOptions, ReturnDocument}};
+
+use crate::error::AppError;
+use crate::model::{history_model::{CartAction, CartEvent}, share_model::CartAccess, cart_model::{Cart, CartNotice, NoticeKind}};
+
+use super::{mongodb_repo::MongoRepo, order_repo::round_money, soft_delete_repo::{not_deleted, soft_delete}, transaction_repo::find_one_on};
+
+fn notice(line: &Cart, kind: NoticeKind, value: String, message: String) -> CartNotice {
+    CartNotice {
+        id: format!("{}:{}:{}", line.id.unwrap_or_default(), kind.as_str(), value),
+        cart_id: line.id.unwrap_or_default(),
+        product_name: line.product_name.to_owned(),
+        sku: line.sku.to_owned(),
+        kind,
+        message,
+        old_price: None,
+        new_price: None,
+        available: None,
+    }
+}
+
+//a notice of its kind always carries the field, one read back without it is a bug
+fn incomplete_notice(field: &str) -> AppError {
+    AppError::Internal(format!("Notice has no {}", field))
+}
+
+//pipeline expression bumping the version of a line
+fn next_version() -> Document {
+    doc! {"$add": [{"$ifNull": ["$version", 0]}, 1]}
+}
+
+//the line after a change, for the history
+pub(super) fn updated_line() -> FindOneAndUpdateOptions {
+    FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build()
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Revalidation handler function ----------------------------- ////
+
+    //handler to check every line of the cart against the catalog
+    pub async fn cart_notices(&self, access: &CartAccess) -> Result<Vec<CartNotice>, AppError> {
+        let lines = self.list_all_carts_by_user(access).await?;
+        self.revalidate_lines(&lines).await
+    }
+
+    //handler to compare lines with the current price, stock and active status of their product
+    pub async fn revalidate_lines(&self, lines: &[Cart]) -> Result<Vec<CartNotice>, AppError> {
+        self.revalidate_lines_on(None, lines).await
+    }
+
+    //same as revalidate_lines, the catalog and the stock are read on the session when one is given
+    pub(super) async fn revalidate_lines_on(&self, mut session: Option<&mut ClientSession>, lines: &[Cart]) -> Result<Vec<CartNotice>, AppError> {
+        let mut notices = Vec::new();
+
+        for line in lines.iter().filter(|line| line.id.is_some()) {
+            if self.is_discontinued(session.as_deref_mut(), line).await? {
+                notices.push(notice(line, NoticeKind::Discontinued, String::new(), format!("{} is discontinued", line.product_name)));
+                continue;
+            }
+
+            //units this line already holds count as available to it
+            let held = find_one_on(&self.reservation_col, doc! {"_cid": line.id}, session.as_deref_mut())
+                .await?
+                .map(|r| r.qty)
+                .unwrap_or(0.0);
+
+            let available = find_one_on(&self.inventory_col, doc! {"sku": line.stock_key()}, session.as_deref_mut())
+                .await
+                .ok()
+                .flatten()
+                .map(|inventory| inventory.available.max(0.0) + held)
+                .unwrap_or(0.0);
+
+            if available <= 0.0 {
+                notices.push(notice(line, NoticeKind::OutOfStock, String::new(), format!("{} is out of stock", line.product_name)));
+                continue;
+            }
+
+            if available < line.qty {
+                notices.push(CartNotice {
+                    available: Some(available),
+                    ..notice(line, NoticeKind::InsufficientStock, available.to_string(), format!("only {} of {} available", available, line.product_name))
+                });
+            }
+
+            if let Ok(current) = self.resolve_line_on(session.as_deref_mut(), line.clone()).await {
+                let price = round_money(current.price);
+                if price != round_money(line.price) {
+                    notices.push(CartNotice {
+                        old_price: Some(line.price),
+                        new_price: Some(price),
+                        ..notice(line, NoticeKind::PriceChanged, price.to_string(), format!("price of {} changed from {} to {}", line.product_name, line.price, price))
+                    });
+                }
+            }
+        }
+
+        Ok(notices)
+    }
+
+    //a line is discontinued when its product was deactivated or its variant is gone from the catalog
+    async fn is_discontinued(&self, session: Option<&mut ClientSession>, line: &Cart) -> Result<bool, AppError> {
+        let filter = match &line.sku {
+            Some(sku) => doc! {"variants.sku": sku},
+            None => doc! {"name": &line.product_name},
+        };
+
+        let product = find_one_on(&self.product_col, filter, session).await?;
+
+        Ok(match product {
+            Some(product) => !product.active,
+            None => line.sku.is_some(),
+        })
+    }
+
+    //handler to accept notices, the cart takes the change each one describes:
+    //new prices are applied, short lines shrink to what is available and unavailable lines are removed
+    pub async fn acknowledge_notices(&self, access: &CartAccess, ids: Vec<String>) -> Result<Vec<CartNotice>, AppError> {
+        access.ensure_editor()?;
+
+        let lines = self.list_all_carts_by_user(access).await?;
+
+        //notices that changed since the user saw them are not applied, they come back with a new id
+        let accepted: Vec<CartNotice> = self
+            .revalidate_lines(&lines)
+            .await?
+            .into_iter()
+            .filter(|notice| ids.contains(&notice.id))
+            .collect();
+
+        for accepted in accepted {
+            let line = match lines.iter().find(|line| line.id == Some(accepted.cart_id)) {
+                Some(line) => line,
+                None => continue,
+            };
+
+            //the stock follows the new quantity before the line does
+            if accepted.kind == NoticeKind::InsufficientStock {
+                self.adjust_reservation(line, accepted.available.ok_or_else(|| incomplete_notice("available quantity"))?).await?;
+            }
+
+            self.with_transaction(&(access, line, &accepted), |repo, session, &(access, line, accepted)| {
+                Box::pin(repo.apply_notice(session, access, line, accepted))
+            })
+            .await?;
+
+            if matches!(accepted.kind, NoticeKind::OutOfStock | NoticeKind::Discontinued) {
+                self.release_reservation(&accepted.cart_id).await?;
+            }
+        }
+
+        self.touch_cart(&access.owner).await?;
+
+        self.cart_notices(access).await
+    }
+
+    //applies an accepted notice to its line on the session, with the history and outbox records.
+    //A line can take a new price and a new quantity in one go, totals are computed from the stored fields
+    async fn apply_notice(&self, session: &mut ClientSession, access: &CartAccess, line: &Cart, accepted: &CartNotice) -> Result<(), AppError> {
+        match accepted.kind {
+            NoticeKind::PriceChanged => {
+                let price = accepted.new_price.ok_or_else(|| incomplete_notice("new price"))?;
+
+                let updated = self
+                    .cart_col
+                    .find_one_and_update_with_session(
+                        doc! {"_id": line.id, "deletedAt": not_deleted()},
+                        vec![doc! {"$set": {"price": price, "_total": {"$multiply": [price, "$qty"]}, "version": next_version()}}],
+                        updated_line(),
+                        session,
+                    )
+                    .await?;
+
+                self.record_cart_event_with_session(CartEvent::new(&access.owner, &access.actor, CartAction::Update, Some(line.clone()), updated), session).await
+            },
+            NoticeKind::InsufficientStock => {
+                let qty = accepted.available.ok_or_else(|| incomplete_notice("available quantity"))?;
+
+                let updated = self
+                    .cart_col
+                    .find_one_and_update_with_session(
+                        doc! {"_id": line.id, "deletedAt": not_deleted()},
+                        vec![doc! {"$set": {"qty": qty, "_total": {"$multiply": ["$price", qty]}, "version": next_version()}}],
+                        updated_line(),
+                        session,
+                    )
+                    .await?;
+
+                self.record_cart_event_with_session(CartEvent::new(&access.owner, &access.actor, CartAction::Update, Some(line.clone()), updated), session).await
+            },
+            NoticeKind::OutOfStock | NoticeKind::Discontinued => {
+                self.cart_col
+                    .update_one_with_session(doc! {"_id": line.id, "deletedAt": not_deleted()}, soft_delete(), None, session)
+                    .await?;
+
+                self.record_cart_event_with_session(CartEvent::new(&access.owner, &access.actor, CartAction::Remove, Some(line.clone()), None), session).await
+            },
+        }
+    }
+
+    ////----------------------  END - Revalidation handler function ----------------------------- ////
+
+}
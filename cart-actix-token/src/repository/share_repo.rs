@@ -0,0 +1,211 @@
+use chrono::{Duration, Utc};
+use futures::StreamExt;
+use jsonwebtoken::{encode, decode, Header, EncodingKey, DecodingKey, Validation, Algorithm};
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson, Document},
+    options::{FindOneAndUpdateOptions, ReturnDocument}};
+
+use crate::error::AppError;
+use crate::model::{share_model::{CartAccess, CartShare, ShareClaims, ShareRole}};
+
+use super::{guest_cart_repo::is_guest_cart, mongodb_repo::MongoRepo};
+
+//share links carry the owner behind this prefix, so they never pass as a login token
+const SHARE_PREFIX: &str = "share:";
+
+fn no_access() -> AppError {
+    AppError::Forbidden("No access to this cart".to_owned())
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Shared cart handler function ----------------------------- ////
+
+    //handler to resolve whose cart a request works on and with which role,
+    //the own cart unless the caller names a cart shared with them
+    pub async fn cart_access(&self, token: &str, shared: Option<&str>) -> Result<CartAccess, AppError> {
+        let actor = self.cart_owner(token).await?.ok_or(AppError::Unauthorized("Not found user".to_string()))?;
+
+        let owner = match shared {
+            Some(owner) if owner != actor => owner,
+            _ => return Ok(CartAccess::own(actor)),
+        };
+
+        //guests cannot be invited, only registered users have an email to match
+        let user = match self.find_user_by_id(&actor).await? {
+            Some(user) => user,
+            None => return Err(no_access()),
+        };
+
+        let share = self
+            .share_col
+            .find_one(doc! {
+                "_uid": owner,
+                "$or": [{"member": &actor}, {"email": user.email.to_lowercase()}]
+            }, None)
+            .await?
+            .ok_or_else(no_access)?;
+
+        Ok(CartAccess {
+            owner: owner.to_owned(),
+            actor,
+            role: share.role,
+        })
+    }
+
+    //resolve the registered owner managing the sharing of their cart
+    async fn share_owner(&self, token: &str) -> Result<String, AppError> {
+        let access = self.cart_access(token, None).await?;
+
+        if is_guest_cart(&access.owner) {
+            return Err(AppError::Unauthorized("Sign in to share your cart".to_owned()));
+        }
+
+        Ok(access.owner)
+    }
+
+    async fn upsert_share(&self, filter: Document, role: ShareRole) -> Result<CartShare, AppError> {
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+
+        self.share_col
+            .find_one_and_update(
+                filter,
+                doc! {
+                    "$set": {"role": role.as_str()},
+                    "$setOnInsert": {"createdAt": to_bson(&Utc::now())?}
+                },
+                options,
+            )
+            .await?
+            .ok_or(AppError::NotFound("Share Not found".to_owned()))
+    }
+
+    //handler to invite a user by email as editor or viewer of the cart
+    pub async fn invite_to_cart(&self, token: &str, email: String, role: ShareRole) -> Result<CartShare, AppError> {
+        let owner = self.share_owner(token).await?;
+
+        if role == ShareRole::Owner {
+            return Err(AppError::Validation("A cart has only one owner".to_owned()));
+        }
+
+        let email = email.trim().to_lowercase();
+        if email.is_empty() {
+            return Err(AppError::Validation("Email is required".to_owned()));
+        }
+
+        self.upsert_share(doc! {"_uid": owner, "email": email}, role).await
+    }
+
+    //handler to create a signed link that makes whoever opens it an editor or viewer of the cart
+    pub async fn create_share_link(&self, token: &str, role: ShareRole) -> Result<String, AppError> {
+        let owner = self.share_owner(token).await?;
+
+        if role == ShareRole::Owner {
+            return Err(AppError::Validation("A cart has only one owner".to_owned()));
+        }
+
+        let jwt_secret = "secret".to_owned();
+
+        let now = Utc::now();
+        let claims = ShareClaims {
+            sub: format!("{}{}", SHARE_PREFIX, owner),
+            role,
+            iat: now.timestamp() as usize,
+            exp: (now + Duration::days(7)).timestamp() as usize,
+        };
+
+        Ok(encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(jwt_secret.as_ref()),
+        )?)
+    }
+
+    //handler to join a cart through a share link
+    pub async fn join_shared_cart(&self, token: &str, link: &str) -> Result<CartShare, AppError> {
+        let member = self.share_owner(token).await?;
+
+        let secret_key = "secret".to_owned();
+        let claims = decode::<ShareClaims>(
+            link,
+            &DecodingKey::from_secret(secret_key.as_bytes()),
+            &Validation::new(Algorithm::HS256),
+        )
+        .map_err(|_| AppError::Validation("Invalid or expired share link".to_owned()))?
+        .claims;
+
+        let owner = claims.sub.strip_prefix(SHARE_PREFIX).ok_or_else(no_access)?;
+
+        if owner == member {
+            return Err(AppError::Validation("This is your own cart".to_owned()));
+        }
+
+        self.upsert_share(doc! {"_uid": owner, "member": member}, claims.role).await
+    }
+
+    //handler to list the members of the own cart and the carts shared with the user
+    pub async fn list_cart_shares(&self, token: &str) -> Result<(Vec<CartShare>, Vec<CartShare>), AppError> {
+        let user_id = self.share_owner(token).await?;
+        let email = self
+            .find_user_by_id(&user_id)
+            .await?
+            .map(|user| user.email.to_lowercase())
+            .unwrap_or_default();
+
+        let members = self.find_shares(doc! {"_uid": &user_id}).await?;
+        let shared_with_me = self
+            .find_shares(doc! {"$or": [{"member": &user_id}, {"email": email}]})
+            .await?;
+
+        Ok((members, shared_with_me))
+    }
+
+    async fn find_shares(&self, filter: Document) -> Result<Vec<CartShare>, AppError> {
+        let mut share_doc = self
+            .share_col
+            .find(filter, None)
+            .await?;
+
+        let mut share_vec = Vec::new();
+
+        while let Some(doc) = share_doc.next().await {
+            match doc {
+                Ok(data) => share_vec.push(data),
+                Err(err) => eprintln!("Error finding cart share: {:?}", err),
+            }
+        }
+
+        Ok(share_vec)
+    }
+
+    //handler to end a share, the owner removes a member or a member leaves
+    pub async fn remove_cart_share(&self, token: &str, share_id: String) -> Result<u64, AppError> {
+        let user_id = self.share_owner(token).await?;
+        let share_id = ObjectId::parse_str(share_id)?;
+
+        let email = self
+            .find_user_by_id(&user_id)
+            .await?
+            .map(|user| user.email.to_lowercase())
+            .unwrap_or_default();
+
+        let deleted = self
+            .share_col
+            .delete_one(
+                doc! {"_id": share_id, "$or": [{"_uid": &user_id}, {"member": &user_id}, {"email": email}]},
+                None,
+            )
+            .await?;
+
+        match deleted.deleted_count {
+            0 => Err(AppError::NotFound("Share Not found".to_owned())),
+            count => Ok(count),
+        }
+    }
+
+    ////----------------------  END - Shared cart handler function ----------------------------- ////
+
+}
@@ -0,0 +1,129 @@
+use chrono::Utc;
+use mongodb::{
+    ClientSession,
+    bson::{doc, oid::ObjectId, DateTime as BsonDateTime, Document},
+    options::{FindOneAndUpdateOptions, ReturnDocument}};
+
+use crate::error::AppError;
+use crate::model::{history_model::{CartAction, CartEvent}, share_model::CartAccess, cart_model::Cart};
+
+use super::mongodb_repo::MongoRepo;
+
+//condition on deletedAt of the lines still in the cart, every read of the cart goes through it
+pub(super) fn not_deleted() -> Document {
+    doc! {"$exists": false}
+}
+
+//update that moves a line to the trash, the version bump lets a stale If-Match fail on it
+pub(super) fn soft_delete() -> Document {
+    doc! {"$set": {"deletedAt": BsonDateTime::now()}, "$inc": {"version": 1}}
+}
+
+pub(super) fn not_restorable() -> AppError {
+    AppError::NotFound("Todo Not found or no longer restorable".to_owned())
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Soft delete handler function ----------------------------- ////
+
+    //filter of a line removed recently enough to be restored
+    fn restorable_filter(&self, user_id: &str, cart_id: ObjectId) -> Document {
+        let since = BsonDateTime::from_chrono(Utc::now() - self.restore_window);
+        doc! {"_id": cart_id, "_uid": user_id, "deletedAt": {"$gte": since}}
+    }
+
+    //handler to bring a removed line back into the cart while the restore window is open,
+    //the line takes its stock again as if it was added now
+    pub async fn restore_cart(&self, access: &CartAccess, cart_id: String) -> Result<Cart, AppError> {
+        access.ensure_editor()?;
+        let user_id = &access.owner;
+        let cart_id = ObjectId::parse_str(cart_id)?;
+
+        let line = self
+            .cart_col
+            .find_one(self.restorable_filter(user_id, cart_id), None)
+            .await?
+            .ok_or_else(not_restorable)?;
+
+        //the stock went back when the line was removed
+        match self.reservation_ttl {
+            Some(_) => self.reserve_stock(line.stock_key(), line.qty).await?,
+            None => self.check_stock(line.stock_key(), line.qty).await?,
+        }
+
+        let restored = self
+            .with_transaction(&(access, &line), |repo, session, &(access, line)| {
+                Box::pin(repo.restore_cart_line(session, access, line))
+            })
+            .await;
+
+        match restored {
+            Ok(restored) => {
+                self.create_reservation(user_id, cart_id, restored.stock_key(), restored.qty).await?;
+                Ok(restored)
+            },
+            Err(error) => {
+                if self.reservation_ttl.is_some() {
+                    let _ = self.release_stock(line.stock_key(), line.qty).await;
+                }
+                Err(error)
+            }
+        }
+    }
+
+    async fn restore_cart_line(&self, session: &mut ClientSession, access: &CartAccess, line: &Cart) -> Result<Cart, AppError> {
+        let user_id = &access.owner;
+
+        //the same variant added again since the removal keeps its own line, the two are not merged
+        let mut live = self.line_filter(user_id, line);
+        live.insert("_id", doc! {"$ne": line.id});
+
+        let duplicate = self
+            .cart_col
+            .find_one_with_session(live, None, session)
+            .await?;
+
+        if duplicate.is_some() {
+            return Err(AppError::Conflict("The item is already in the cart again".to_owned()));
+        }
+
+        let mut update = doc! {"$unset": {"deletedAt": ""}, "$inc": {"version": 1}};
+
+        //guest items live on for another full period after every change
+        if line.expires_at.is_some() {
+            update.insert("$set", doc! {"expiresAt": BsonDateTime::from_chrono(Utc::now() + self.guest_cart_ttl)});
+        }
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        //the purge or a second restore may have got there first
+        let restored = self
+            .cart_col
+            .find_one_and_update_with_session(self.restorable_filter(user_id, line.id.unwrap()), update, options, session)
+            .await?
+            .ok_or_else(not_restorable)?;
+
+        self.touch_cart_with_session(user_id, session).await?;
+        self.record_cart_event_with_session(CartEvent::new(user_id, &access.actor, CartAction::Restore, Some(line.clone()), Some(restored.clone())), session).await?;
+
+        Ok(restored)
+    }
+
+    //handler to hard-delete the lines removed longer ago than the retention, returns the purged count
+    pub async fn purge_deleted_carts(&self) -> Result<u64, AppError> {
+        let cutoff = BsonDateTime::from_chrono(Utc::now() - self.deleted_cart_retention);
+
+        let purged = self
+            .cart_col
+            .delete_many(doc! {"deletedAt": {"$lt": cutoff}}, None)
+            .await?;
+
+        Ok(purged.deleted_count)
+    }
+
+    ////----------------------  END - Soft delete handler function ----------------------------- ////
+
+}
@@ -0,0 +1,837 @@
+use std::{collections::BTreeMap, env, str::FromStr};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm};
+use mongodb::bson::{oid::ObjectId, Bson};
+use rust_decimal::{prelude::{FromPrimitive, ToPrimitive}, Decimal, RoundingStrategy};
+use sqlx::{any::{AnyPoolOptions, AnyRow}, AnyConnection, AnyPool, Row};
+
+use crate::error::AppError;
+use crate::model::{
+    cart_model::{Cart, CartDeleteFilter, CartMergeResult, CartNotice, CartPage, CartQuery, CartSort, CartWriteError, PageInfo, SortOrder, UpdateCart},
+    idempotency_model::{IdempotencyRecord, IDEMPOTENCY_LEASE_SECONDS},
+    share_model::CartAccess,
+    user_model::{TokenClaims, User}};
+
+use super::{
+    cart_page_repo::{decode_cursor, encode_cursor, invalid_cursor, page_limit, sort_value},
+    guest_cart_repo::{guest_cart_id, is_guest_cart},
+    memory_store::same_line,
+    soft_delete_repo::not_restorable,
+    store::{CartStore, IdempotencyStore, UserStore}};
+
+//columns of a cart line, the NUMERIC ones are read as text since sqlx::Any has no decimal type
+const CART_COLUMNS: &str = "id, owner, product_name, sku, variant, options, \
+    CAST(price AS TEXT) AS price, CAST(qty AS TEXT) AS qty, CAST(total AS TEXT) AS total, \
+    created_at, expires_at, updated_at, version, added_by, deleted_at";
+
+const USER_COLUMNS: &str = "id, name, email, password, created_at";
+
+const IDEMPOTENCY_COLUMNS: &str = "idempotency_key, owner, request, status, headers, body, created_at";
+
+fn todo_not_found() -> AppError {
+    AppError::NotFound("Todo Not found".to_owned())
+}
+
+fn corrupt_row(column: &str) -> AppError {
+    eprintln!("Database error: unreadable {} column", column);
+    AppError::Database("Database error".to_owned())
+}
+
+//prices and totals are kept to the cent and quantities to a thousandth like the columns store them,
+//halves rounded up the way a receipt does
+fn money(value: f64) -> Decimal {
+    Decimal::from_f64(value).unwrap_or_default().round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+}
+
+fn quantity(value: f64) -> Decimal {
+    Decimal::from_f64(value).unwrap_or_default().round_dp_with_strategy(3, RoundingStrategy::MidpointAwayFromZero)
+}
+
+//the line with its amounts rounded the way they are stored and the total worked out in decimal,
+//so what the handler answers is what a later read gives back
+fn priced(line: Cart) -> Cart {
+    let price = money(line.price);
+    let qty = quantity(line.qty);
+
+    Cart {
+        price: price.to_f64().unwrap_or_default(),
+        qty: qty.to_f64().unwrap_or_default(),
+        total: Some((price * qty).round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero).to_f64().unwrap_or_default()),
+        ..line
+    }
+}
+
+fn decimal_column(row: &AnyRow, column: &str) -> Result<f64, AppError> {
+    let text: String = row.try_get(column)?;
+
+    Decimal::from_str(&text)
+        .or_else(|_| Decimal::from_scientific(&text))
+        .ok()
+        .and_then(|value| value.to_f64())
+        .ok_or_else(|| corrupt_row(column))
+}
+
+//times are stored as Unix milliseconds
+fn millis(at: Option<DateTime<Utc>>) -> Option<i64> {
+    at.map(|at| at.timestamp_millis())
+}
+
+fn time_column(row: &AnyRow, column: &str) -> Result<Option<DateTime<Utc>>, AppError> {
+    let at: Option<i64> = row.try_get(column)?;
+    Ok(at.and_then(|at| Utc.timestamp_millis_opt(at).single()))
+}
+
+fn json_text<T: serde::Serialize>(value: &T) -> Result<String, AppError> {
+    serde_json::to_string(value).map_err(|error| AppError::Internal(format!("Error encoding JSON: {}", error)))
+}
+
+fn json_column<T: serde::de::DeserializeOwned>(row: &AnyRow, column: &str) -> Result<Option<T>, AppError> {
+    let text: Option<String> = row.try_get(column)?;
+
+    text.map(|text| serde_json::from_str(&text).map_err(|_| corrupt_row(column)))
+        .transpose()
+}
+
+fn id_column(row: &AnyRow) -> Result<ObjectId, AppError> {
+    let id: String = row.try_get("id")?;
+    ObjectId::parse_str(id).map_err(|_| corrupt_row("id"))
+}
+
+fn cart_from_row(row: &AnyRow) -> Result<Cart, AppError> {
+    Ok(Cart {
+        id: Some(id_column(row)?),
+        user_id: Some(row.try_get("owner")?),
+        product_name: row.try_get("product_name")?,
+        sku: row.try_get("sku")?,
+        variant: json_column(row, "variant")?,
+        options: json_column(row, "options")?,
+        price: decimal_column(row, "price")?,
+        qty: decimal_column(row, "qty")?,
+        total: Some(decimal_column(row, "total")?),
+        created_at: time_column(row, "created_at")?,
+        expires_at: time_column(row, "expires_at")?,
+        updated_at: time_column(row, "updated_at")?,
+        abandoned_at: None,
+        version: Some(row.try_get("version")?),
+        added_by: row.try_get("added_by")?,
+        deleted_at: time_column(row, "deleted_at")?,
+    })
+}
+
+fn user_from_row(row: &AnyRow) -> Result<User, AppError> {
+    Ok(User {
+        id: Some(id_column(row)?),
+        name: row.try_get("name")?,
+        email: row.try_get("email")?,
+        password: row.try_get("password")?,
+        created_at: time_column(row, "created_at")?,
+    })
+}
+
+fn idempotency_from_row(row: &AnyRow) -> Result<IdempotencyRecord, AppError> {
+    let status: Option<i64> = row.try_get("status")?;
+
+    Ok(IdempotencyRecord {
+        id: None,
+        key: row.try_get("idempotency_key")?,
+        owner: row.try_get("owner")?,
+        request: row.try_get("request")?,
+        status: status.and_then(|status| u16::try_from(status).ok()),
+        headers: json_column(row, "headers")?.unwrap_or_default(),
+        body: row.try_get("body")?,
+        created_at: time_column(row, "created_at")?,
+    })
+}
+
+fn is_unique_violation(error: &sqlx::Error) -> bool {
+    matches!(error, sqlx::Error::Database(error) if error.is_unique_violation())
+}
+
+//text searched for with LIKE, its wildcards escaped
+fn like_pattern(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+//column a listing is sorted by
+fn sort_column(sort: CartSort) -> &'static str {
+    match sort {
+        CartSort::CreatedAt => "created_at",
+        CartSort::Price => "price",
+        CartSort::Total => "total",
+    }
+}
+
+//sort value of a cursor as bound into the query, like the NUMERIC columns without a float in between
+fn cursor_value(value: &Bson) -> Option<String> {
+    match value {
+        Bson::DateTime(at) => Some(at.timestamp_millis().to_string()),
+        Bson::Double(value) => Decimal::from_f64(*value).map(|value| value.to_string()),
+        Bson::Int32(value) => Some(value.to_string()),
+        Bson::Int64(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+async fn user_lines(conn: &mut AnyConnection, owner: &str) -> Result<Vec<Cart>, AppError> {
+    let sql = format!("SELECT {} FROM cart_lines WHERE owner = $1 AND deleted_at IS NULL ORDER BY created_at, id", CART_COLUMNS);
+
+    sqlx::query(&sql)
+        .bind(owner)
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(cart_from_row)
+        .collect()
+}
+
+//the line in the cart of the owner, deleted lines only when asked for since the given time
+async fn find_line(conn: &mut AnyConnection, owner: &str, id: &ObjectId, deleted_since: Option<DateTime<Utc>>) -> Result<Option<Cart>, AppError> {
+    let row = match deleted_since {
+        Some(since) => {
+            let sql = format!("SELECT {} FROM cart_lines WHERE id = $1 AND owner = $2 AND deleted_at >= $3", CART_COLUMNS);
+            sqlx::query(&sql)
+                .bind(id.to_hex())
+                .bind(owner)
+                .bind(since.timestamp_millis())
+                .fetch_optional(&mut *conn)
+                .await?
+        },
+        None => {
+            let sql = format!("SELECT {} FROM cart_lines WHERE id = $1 AND owner = $2 AND deleted_at IS NULL", CART_COLUMNS);
+            sqlx::query(&sql)
+                .bind(id.to_hex())
+                .bind(owner)
+                .fetch_optional(&mut *conn)
+                .await?
+        },
+    };
+
+    row.as_ref().map(cart_from_row).transpose()
+}
+
+//live line of the owner that the given one would be merged into
+async fn matching_line(conn: &mut AnyConnection, owner: &str, line: &Cart) -> Result<Option<Cart>, AppError> {
+    let sql = format!("SELECT {} FROM cart_lines WHERE owner = $1 AND product_name = $2 AND deleted_at IS NULL", CART_COLUMNS);
+
+    let rows = sqlx::query(&sql)
+        .bind(owner)
+        .bind(&line.product_name)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    for row in &rows {
+        let existing = cart_from_row(row)?;
+        if existing.id != line.id && same_line(&existing, line) {
+            return Ok(Some(existing));
+        }
+    }
+
+    Ok(None)
+}
+
+async fn insert_line(conn: &mut AnyConnection, line: &Cart) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO cart_lines (id, owner, product_name, sku, variant, options, price, qty, total, created_at, expires_at, updated_at, version, added_by) \
+         VALUES ($1, $2, $3, $4, $5, $6, CAST($7 AS NUMERIC), CAST($8 AS NUMERIC), CAST($9 AS NUMERIC), $10, $11, $12, $13, $14)",
+    )
+    .bind(line.id.unwrap_or_default().to_hex())
+    .bind(line.user_id.clone().unwrap_or_default())
+    .bind(&line.product_name)
+    .bind(&line.sku)
+    .bind(line.variant.as_ref().map(json_text).transpose()?)
+    .bind(line.options.as_ref().map(json_text).transpose()?)
+    .bind(money(line.price).to_string())
+    .bind(quantity(line.qty).to_string())
+    .bind(money(line.total.unwrap_or_default()).to_string())
+    .bind(millis(line.created_at).unwrap_or_default())
+    .bind(millis(line.expires_at))
+    .bind(millis(line.updated_at))
+    .bind(line.version())
+    .bind(&line.added_by)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+//writes the changeable fields of a line, as long as nobody changed it since it was read at read_version.
+//Two writers that read the same version cannot both win, the later one is told to try again
+async fn save_line(conn: &mut AnyConnection, line: &Cart, read_version: i64) -> Result<(), AppError> {
+    let saved = sqlx::query(
+        "UPDATE cart_lines SET owner = $1, price = CAST($2 AS NUMERIC), qty = CAST($3 AS NUMERIC), total = CAST($4 AS NUMERIC), \
+         expires_at = $5, updated_at = $6, version = $7, deleted_at = $8 WHERE id = $9 AND version = $10",
+    )
+    .bind(line.user_id.clone().unwrap_or_default())
+    .bind(money(line.price).to_string())
+    .bind(quantity(line.qty).to_string())
+    .bind(money(line.total.unwrap_or_default()).to_string())
+    .bind(millis(line.expires_at))
+    .bind(millis(line.updated_at))
+    .bind(line.version())
+    .bind(millis(line.deleted_at))
+    .bind(line.id.unwrap_or_default().to_hex())
+    .bind(read_version)
+    .execute(&mut *conn)
+    .await?;
+
+    if saved.rows_affected() == 0 {
+        return Err(AppError::Transient("The cart item changed meanwhile, try again".to_owned()));
+    }
+
+    Ok(())
+}
+
+//folds the guest lines into the cart of the user, matching lines have their quantities summed
+async fn merge_guest_lines(conn: &mut AnyConnection, guest_id: &str, user_id: &str) -> Result<CartMergeResult, AppError> {
+    let mut result = CartMergeResult::default();
+
+    for item in user_lines(conn, guest_id).await? {
+        match matching_line(conn, user_id, &item).await? {
+            Some(line) => {
+                let merged = priced(Cart {
+                    qty: line.qty + item.qty,
+                    version: Some(line.version() + 1),
+                    ..line.clone()
+                });
+                save_line(conn, &merged, line.version()).await?;
+
+                //deleted like any other removal, so the guest line stays restorable
+                let removed = Cart {
+                    deleted_at: Some(Utc::now()),
+                    version: Some(item.version() + 1),
+                    ..item.clone()
+                };
+                save_line(conn, &removed, item.version()).await?;
+
+                result.merged += 1;
+            },
+            None => {
+                let moved = Cart {
+                    user_id: Some(user_id.to_owned()),
+                    expires_at: None,
+                    version: Some(item.version() + 1),
+                    ..item.clone()
+                };
+                save_line(conn, &moved, item.version()).await?;
+
+                result.moved += 1;
+            },
+        }
+    }
+
+    Ok(result)
+}
+
+//Store keeping users and carts in SQLite or PostgreSQL through sqlx. Like the memory store it has
+//no catalog, stock or sharing: lines keep the price they were added with.
+pub struct SqlStore {
+    pool: AnyPool,
+    guest_cart_ttl: Duration,
+    restore_window: Duration,
+    deleted_cart_retention: Duration,
+    idempotency_ttl: Duration,
+}
+
+impl SqlStore {
+    //connects to a sqlite: or postgres:// url and brings the schema up to date
+    pub async fn connect(url: &str) -> Result<Self, AppError> {
+        sqlx::any::install_default_drivers();
+
+        //every connection to an in-memory SQLite database gets a database of its own,
+        //so that one keeps a single connection for as long as the store lives
+        let options = if url.contains(":memory:") {
+            AnyPoolOptions::new().max_connections(1).idle_timeout(None).max_lifetime(None)
+        } else {
+            AnyPoolOptions::new().max_connections(10)
+        };
+
+        let pool = options.connect(url).await?;
+        sqlx::migrate!("./sql_migrations").run(&pool).await?;
+
+        let guest_cart_ttl = env::var("GUEST_CART_TTL_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(Duration::days)
+            .unwrap_or_else(|| Duration::days(7));
+
+        let restore_window = env::var("CART_RESTORE_WINDOW_MINUTES")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(Duration::minutes)
+            .unwrap_or_else(|| Duration::minutes(30));
+
+        let deleted_cart_retention = env::var("DELETED_CART_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(Duration::days)
+            .unwrap_or_else(|| Duration::days(7));
+
+        let idempotency_ttl = env::var("IDEMPOTENCY_TTL_HOURS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .map(Duration::hours)
+            .unwrap_or_else(|| Duration::hours(24));
+
+        Ok(SqlStore {
+            pool,
+            guest_cart_ttl,
+            restore_window,
+            deleted_cart_retention,
+            idempotency_ttl,
+        })
+    }
+
+    //there are no TTL indexes here: drops expired guest lines, deleted lines past the retention
+    //and Idempotency-Key records past the replay window, returns the removed count
+    pub async fn purge_expired(&self) -> Result<u64, AppError> {
+        let now = Utc::now();
+        let mut purged = 0;
+
+        purged += sqlx::query("DELETE FROM cart_lines WHERE expires_at < $1")
+            .bind(now.timestamp_millis())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        purged += sqlx::query("DELETE FROM cart_lines WHERE deleted_at < $1")
+            .bind((now - self.deleted_cart_retention).timestamp_millis())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        purged += sqlx::query("DELETE FROM idempotency_keys WHERE created_at < $1")
+            .bind((now - self.idempotency_ttl).timestamp_millis())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(purged)
+    }
+
+    //marks the live lines of the cart that match as deleted in one transaction, returns how many
+    async fn delete_lines(&self, access: &CartAccess, matches: impl Fn(&Cart) -> bool) -> Result<u64, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let mut deleted = 0;
+
+        for line in user_lines(&mut tx, &access.owner).await?.into_iter().filter(|line| matches(line)) {
+            let removed = Cart {
+                deleted_at: Some(Utc::now()),
+                version: Some(line.version() + 1),
+                ..line.clone()
+            };
+            save_line(&mut tx, &removed, line.version()).await?;
+            deleted += 1;
+        }
+
+        tx.commit().await?;
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl UserStore for SqlStore {
+    async fn find_by_email_pwd(&self, email: &str, pwd: &str) -> Result<Option<User>, AppError> {
+        let sql = format!("SELECT {} FROM users WHERE LOWER(email) = LOWER($1) AND password = $2", USER_COLUMNS);
+
+        sqlx::query(&sql)
+            .bind(email)
+            .bind(pwd)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(user_from_row)
+            .transpose()
+    }
+
+    async fn register_user(&self, new_user: User, guest_token: Option<&str>) -> Result<User, AppError> {
+        let user_id = ObjectId::new();
+        let user = User { id: Some(user_id), created_at: Some(Utc::now()), ..new_user };
+
+        //the new user and the guest cart merge land together or not at all
+        let mut tx = self.pool.begin().await?;
+
+        let inserted = sqlx::query("INSERT INTO users (id, name, email, password, created_at) VALUES ($1, $2, $3, $4, $5)")
+            .bind(user_id.to_hex())
+            .bind(&user.name)
+            .bind(&user.email)
+            .bind(&user.password)
+            .bind(millis(user.created_at).unwrap_or_default())
+            .execute(&mut *tx)
+            .await;
+
+        //the unique index on the lowercased email decides between two sign ups racing for it
+        match inserted {
+            Err(error) if is_unique_violation(&error) => return Err(AppError::Conflict("Email already exists".to_owned())),
+            inserted => inserted?,
+        };
+
+        if let Some(guest_id) = guest_token.and_then(guest_cart_id) {
+            merge_guest_lines(&mut tx, &guest_id, &user_id.to_hex()).await?;
+        }
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    async fn merge_guest_cart(&self, guest_token: &str, user_id: &str) -> Result<CartMergeResult, AppError> {
+        let guest_id = match guest_cart_id(guest_token) {
+            Some(guest_id) => guest_id,
+            None => return Ok(CartMergeResult::default()),
+        };
+
+        let mut tx = self.pool.begin().await?;
+        let result = merge_guest_lines(&mut tx, &guest_id, user_id).await?;
+        tx.commit().await?;
+
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl CartStore for SqlStore {
+    fn guest_cart_ttl(&self) -> Duration {
+        self.guest_cart_ttl
+    }
+
+    async fn cart_owner(&self, token: &str) -> Result<Option<String>, AppError> {
+        if let Some(guest_id) = guest_cart_id(token) {
+            return Ok(Some(guest_id));
+        }
+
+        let secret_key = "secret".to_owned();
+        let claims = decode::<TokenClaims>(
+            token,
+            &DecodingKey::from_secret(secret_key.as_bytes()),
+            &Validation::new(Algorithm::HS256),
+        )
+        .map_err(|_| AppError::Unauthorized("Invalid token".to_owned()))?
+        .claims;
+
+        let user = sqlx::query("SELECT id FROM users WHERE id = $1")
+            .bind(&claims.sub)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        user.map(|row| row.try_get("id")).transpose().map_err(AppError::from)
+    }
+
+    async fn cart_access(&self, token: &str, shared: Option<&str>) -> Result<CartAccess, AppError> {
+        let actor = self.cart_owner(token).await?.ok_or(AppError::Unauthorized("Not found user".to_string()))?;
+
+        //carts are never shared here
+        match shared {
+            Some(owner) if owner != actor => Err(AppError::Forbidden("No access to this cart".to_owned())),
+            _ => Ok(CartAccess::own(actor)),
+        }
+    }
+
+    async fn create_cart(&self, access: &CartAccess, new_cart: Cart) -> Result<Cart, AppError> {
+        access.ensure_editor()?;
+        let user_id = &access.owner;
+
+        if new_cart.qty <= 0.0 {
+            return Err(AppError::Validation("Quantity must be greater than zero".to_owned()));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let line = match matching_line(&mut tx, user_id, &new_cart).await? {
+            Some(line) => {
+                let merged = priced(Cart {
+                    qty: line.qty + new_cart.qty,
+                    price: new_cart.price,
+                    updated_at: Some(Utc::now()),
+                    version: Some(line.version() + 1),
+                    ..line.clone()
+                });
+                save_line(&mut tx, &merged, line.version()).await?;
+                merged
+            },
+            None => {
+                let data = priced(Cart {
+                    id: Some(ObjectId::new()),
+                    user_id: Some(user_id.to_owned()),
+                    variant: None,
+                    options: new_cart.options.to_owned().filter(|options| !options.is_empty()),
+                    created_at: Some(Utc::now()),
+                    expires_at: is_guest_cart(user_id).then(|| Utc::now() + self.guest_cart_ttl),
+                    updated_at: Some(Utc::now()),
+                    abandoned_at: None,
+                    version: Some(1),
+                    added_by: Some(access.actor.to_owned()),
+                    deleted_at: None,
+                    ..new_cart
+                });
+                insert_line(&mut tx, &data).await?;
+                data
+            },
+        };
+
+        tx.commit().await?;
+        Ok(line)
+    }
+
+    async fn list_all_carts_by_user(&self, access: &CartAccess) -> Result<Vec<Cart>, AppError> {
+        let mut conn = self.pool.acquire().await?;
+        user_lines(&mut conn, &access.owner).await
+    }
+
+    async fn list_cart_page(&self, access: &CartAccess, query: CartQuery) -> Result<CartPage, AppError> {
+        let limit = page_limit(&query)?;
+        let column = sort_column(query.sort);
+
+        let (direction, past) = match query.order {
+            SortOrder::Asc => ("ASC", ">"),
+            SortOrder::Desc => ("DESC", "<"),
+        };
+
+        //every condition is bound as text, the numeric ones are cast back on the database side
+        let mut conditions = vec!["owner = $1".to_owned(), "deleted_at IS NULL".to_owned()];
+        let mut binds = vec![access.owner.to_owned()];
+
+        let mut bind = |value: String| {
+            binds.push(value);
+            format!("${}", binds.len())
+        };
+
+        if let Some(name) = query.product_name.as_deref().map(|name| name.trim().to_lowercase()).filter(|name| !name.is_empty()) {
+            conditions.push(format!("LOWER(product_name) LIKE {} ESCAPE '\\'", bind(like_pattern(&name))));
+        }
+
+        if let Some(min) = query.min_price {
+            conditions.push(format!("price >= CAST({} AS NUMERIC)", bind(money(min).to_string())));
+        }
+
+        if let Some(max) = query.max_price {
+            conditions.push(format!("price <= CAST({} AS NUMERIC)", bind(money(max).to_string())));
+        }
+
+        //lines after the cursor in the listing order, the id breaks ties between equal sort values
+        if let Some(cursor) = &query.cursor {
+            let (value, id) = decode_cursor(cursor).ok_or_else(invalid_cursor)?;
+            let value = cursor_value(&value).ok_or_else(invalid_cursor)?;
+
+            let value = bind(value);
+            let id = bind(id.to_hex());
+            conditions.push(format!(
+                "({column} {past} CAST({value} AS NUMERIC) OR ({column} = CAST({value} AS NUMERIC) AND id {past} {id}))"
+            ));
+        }
+
+        let sql = format!(
+            "SELECT {} FROM cart_lines WHERE {} ORDER BY {column} {direction}, id {direction} LIMIT {}",
+            CART_COLUMNS,
+            conditions.join(" AND "),
+            limit + 1,
+        );
+
+        let rows = binds
+            .iter()
+            .fold(sqlx::query(&sql), |query, value| query.bind(value))
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut items = rows.iter().map(cart_from_row).collect::<Result<Vec<Cart>, AppError>>()?;
+
+        let has_more = items.len() as i64 > limit;
+        items.truncate(limit as usize);
+
+        let next_cursor = match items.last() {
+            Some(last) if has_more => last.id.map(|id| encode_cursor(sort_value(last, query.sort), id)),
+            _ => None,
+        };
+
+        Ok(CartPage {
+            items,
+            page: PageInfo { limit, has_more, next_cursor },
+        })
+    }
+
+    async fn update_cart(&self, access: &CartAccess, cart_data: UpdateCart, cart_id: String, if_match: Option<i64>) -> Result<Cart, CartWriteError> {
+        access.ensure_editor()?;
+        let cart_id = ObjectId::parse_str(cart_id).map_err(|_| todo_not_found())?;
+
+        if cart_data.qty <= 0.0 {
+            return Err(CartWriteError::Failed(AppError::Validation("Quantity must be greater than zero".to_owned())));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let line = find_line(&mut tx, &access.owner, &cart_id, None).await?.ok_or_else(todo_not_found)?;
+
+        if if_match.is_some_and(|version| version != line.version()) {
+            return Err(CartWriteError::Stale(Box::new(line)));
+        }
+
+        let updated = priced(Cart {
+            qty: cart_data.qty,
+            updated_at: Some(Utc::now()),
+            version: Some(line.version() + 1),
+            //guest items live on for another full period after every change
+            expires_at: line.expires_at.map(|_| Utc::now() + self.guest_cart_ttl),
+            ..line.clone()
+        });
+        save_line(&mut tx, &updated, line.version()).await?;
+
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn delete_cart(&self, access: &CartAccess, cart_id: String, if_match: Option<i64>) -> Result<u64, CartWriteError> {
+        access.ensure_editor()?;
+        let cart_id = ObjectId::parse_str(cart_id).map_err(|_| todo_not_found())?;
+
+        let mut tx = self.pool.begin().await?;
+        let line = find_line(&mut tx, &access.owner, &cart_id, None).await?.ok_or_else(todo_not_found)?;
+
+        if if_match.is_some_and(|version| version != line.version()) {
+            return Err(CartWriteError::Stale(Box::new(line)));
+        }
+
+        let deleted = Cart {
+            deleted_at: Some(Utc::now()),
+            version: Some(line.version() + 1),
+            ..line.clone()
+        };
+        save_line(&mut tx, &deleted, line.version()).await?;
+
+        tx.commit().await?;
+        Ok(1)
+    }
+
+    async fn restore_cart(&self, access: &CartAccess, cart_id: String) -> Result<Cart, AppError> {
+        access.ensure_editor()?;
+        let cart_id = ObjectId::parse_str(cart_id).map_err(|_| not_restorable())?;
+        let since = Utc::now() - self.restore_window;
+
+        let mut tx = self.pool.begin().await?;
+        let line = find_line(&mut tx, &access.owner, &cart_id, Some(since)).await?.ok_or_else(not_restorable)?;
+
+        //the same variant added again since the removal keeps its own line, the two are not merged
+        if matching_line(&mut tx, &access.owner, &line).await?.is_some() {
+            return Err(AppError::Conflict("The item is already in the cart again".to_owned()));
+        }
+
+        let restored = Cart {
+            deleted_at: None,
+            updated_at: Some(Utc::now()),
+            version: Some(line.version() + 1),
+            expires_at: line.expires_at.map(|_| Utc::now() + self.guest_cart_ttl),
+            ..line.clone()
+        };
+        save_line(&mut tx, &restored, line.version()).await?;
+
+        tx.commit().await?;
+        Ok(restored)
+    }
+
+    async fn clear_cart(&self, access: &CartAccess) -> Result<u64, AppError> {
+        access.ensure_editor()?;
+        self.delete_lines(access, |_| true).await
+    }
+
+    async fn delete_cart_items(&self, access: &CartAccess, filter: CartDeleteFilter) -> Result<u64, AppError> {
+        access.ensure_editor()?;
+
+        //an empty filter would clear the whole cart, that is what DELETE /cart is for
+        if filter.is_empty() {
+            return Err(AppError::Validation("At least one filter is required".to_owned()));
+        }
+
+        self.delete_lines(access, |line| filter.matches(line)).await
+    }
+
+    async fn finding_cart(&self, access: &CartAccess, cart_id: &ObjectId) -> Result<Option<Cart>, AppError> {
+        let mut conn = self.pool.acquire().await?;
+        find_line(&mut conn, &access.owner, cart_id, None).await
+    }
+
+    //without a catalog lines never go stale
+    async fn revalidate_lines(&self, _lines: &[Cart]) -> Result<Vec<CartNotice>, AppError> {
+        Ok(Vec::new())
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for SqlStore {
+    async fn claim_idempotency_key(&self, key: &str, owner: &str, request: &str) -> Result<Option<IdempotencyRecord>, AppError> {
+        let now = Utc::now();
+
+        //a record past the replay window is gone as far as the client can tell, even before the purge
+        sqlx::query("DELETE FROM idempotency_keys WHERE idempotency_key = $1 AND owner = $2 AND created_at < $3")
+            .bind(key)
+            .bind(owner)
+            .bind((now - self.idempotency_ttl).timestamp_millis())
+            .execute(&self.pool)
+            .await?;
+
+        let claimed = sqlx::query(
+            "INSERT INTO idempotency_keys (idempotency_key, owner, request, headers, created_at) VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT (idempotency_key, owner) DO NOTHING",
+        )
+        .bind(key)
+        .bind(owner)
+        .bind(request)
+        .bind(json_text(&BTreeMap::<String, String>::new())?)
+        .bind(now.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        if claimed.rows_affected() == 1 {
+            return Ok(None);
+        }
+
+        //a claim that never got its response is taken over once its lease ran out
+        let taken_over = sqlx::query(
+            "UPDATE idempotency_keys SET request = $1, created_at = $2 \
+             WHERE idempotency_key = $3 AND owner = $4 AND status IS NULL AND created_at < $5",
+        )
+        .bind(request)
+        .bind(now.timestamp_millis())
+        .bind(key)
+        .bind(owner)
+        .bind((now - Duration::seconds(IDEMPOTENCY_LEASE_SECONDS)).timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        if taken_over.rows_affected() == 1 {
+            return Ok(None);
+        }
+
+        let sql = format!("SELECT {} FROM idempotency_keys WHERE idempotency_key = $1 AND owner = $2", IDEMPOTENCY_COLUMNS);
+
+        sqlx::query(&sql)
+            .bind(key)
+            .bind(owner)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(idempotency_from_row)
+            .transpose()
+    }
+
+    async fn complete_idempotency_key(&self, key: &str, owner: &str, status: u16, headers: BTreeMap<String, String>, body: String) -> Result<(), AppError> {
+        sqlx::query("UPDATE idempotency_keys SET status = $1, headers = $2, body = $3 WHERE idempotency_key = $4 AND owner = $5")
+            .bind(i64::from(status))
+            .bind(json_text(&headers)?)
+            .bind(body)
+            .bind(key)
+            .bind(owner)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, key: &str, owner: &str) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE idempotency_key = $1 AND owner = $2")
+            .bind(key)
+            .bind(owner)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+//the implementations are grouped under //// banners like the route files, not doc comments
+#![allow(clippy::four_forward_slashes)]
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use mongodb::bson::oid::ObjectId;
+
+use crate::error::AppError;
+use crate::model::{
+    cart_model::{Cart, CartDeleteFilter, CartMergeResult, CartNotice, CartPage, CartQuery, CartWriteError, UpdateCart},
+    idempotency_model::IdempotencyRecord,
+    share_model::CartAccess,
+    user_model::User};
+
+use super::mongodb_repo::MongoRepo;
+
+//Storage of users, the user routes only talk to it through this trait
+#[async_trait]
+pub trait UserStore: Send + Sync {
+    async fn find_by_email_pwd(&self, email: &str, pwd: &str) -> Result<Option<User>, AppError>;
+
+    //creates the user, a guest cart is merged into the new account
+    async fn register_user(&self, new_user: User, guest_token: Option<&str>) -> Result<User, AppError>;
+
+    async fn merge_guest_cart(&self, guest_token: &str, user_id: &str) -> Result<CartMergeResult, AppError>;
+}
+
+//Storage of cart lines, the cart routes only talk to it through this trait
+#[async_trait]
+pub trait CartStore: Send + Sync {
+    //how long a guest cart lives
+    fn guest_cart_ttl(&self) -> Duration;
+
+    async fn cart_owner(&self, token: &str) -> Result<Option<String>, AppError>;
+
+    async fn cart_access(&self, token: &str, shared: Option<&str>) -> Result<CartAccess, AppError>;
+
+    async fn create_cart(&self, access: &CartAccess, new_cart: Cart) -> Result<Cart, AppError>;
+
+    async fn list_all_carts_by_user(&self, access: &CartAccess) -> Result<Vec<Cart>, AppError>;
+
+    async fn list_cart_page(&self, access: &CartAccess, query: CartQuery) -> Result<CartPage, AppError>;
+
+    async fn update_cart(&self, access: &CartAccess, cart_data: UpdateCart, cart_id: String, if_match: Option<i64>) -> Result<Cart, CartWriteError>;
+
+    //returns the removed count
+    async fn delete_cart(&self, access: &CartAccess, cart_id: String, if_match: Option<i64>) -> Result<u64, CartWriteError>;
+
+    //brings back a line deleted within the restore window
+    async fn restore_cart(&self, access: &CartAccess, cart_id: String) -> Result<Cart, AppError>;
+
+    //empties the cart, returns the removed count
+    async fn clear_cart(&self, access: &CartAccess) -> Result<u64, AppError>;
+
+    //removes the lines matching the filter, returns the removed count
+    async fn delete_cart_items(&self, access: &CartAccess, filter: CartDeleteFilter) -> Result<u64, AppError>;
+
+    async fn finding_cart(&self, access: &CartAccess, cart_id: &ObjectId) -> Result<Option<Cart>, AppError>;
+
+    async fn revalidate_lines(&self, lines: &[Cart]) -> Result<Vec<CartNotice>, AppError>;
+}
+
+//Storage of Idempotency-Key records
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    //claims the key for the owner, the existing record when it was claimed before
+    async fn claim_idempotency_key(&self, key: &str, owner: &str, request: &str) -> Result<Option<IdempotencyRecord>, AppError>;
+
+    async fn complete_idempotency_key(&self, key: &str, owner: &str, status: u16, headers: BTreeMap<String, String>, body: String) -> Result<(), AppError>;
+
+    async fn release_idempotency_key(&self, key: &str, owner: &str) -> Result<(), AppError>;
+}
+
+//Everything the user and cart routes need, handlers get it as Data<dyn Store>
+pub trait Store: UserStore + CartStore + IdempotencyStore {}
+
+impl<T: UserStore + CartStore + IdempotencyStore> Store for T {}
+
+
+////----------------------  START - MongoDB store ----------------------------- ////
+
+#[async_trait]
+impl UserStore for MongoRepo {
+    async fn find_by_email_pwd(&self, email: &str, pwd: &str) -> Result<Option<User>, AppError> {
+        Ok(MongoRepo::find_by_email_pwd(self, &email.to_owned(), &pwd.to_owned()).await?)
+    }
+
+    async fn register_user(&self, new_user: User, guest_token: Option<&str>) -> Result<User, AppError> {
+        let user = MongoRepo::register_user(self, new_user.clone(), guest_token).await?;
+        Ok(User { id: user.inserted_id.as_object_id(), ..new_user })
+    }
+
+    async fn merge_guest_cart(&self, guest_token: &str, user_id: &str) -> Result<CartMergeResult, AppError> {
+        MongoRepo::merge_guest_cart(self, guest_token, user_id).await
+    }
+}
+
+#[async_trait]
+impl CartStore for MongoRepo {
+    fn guest_cart_ttl(&self) -> Duration {
+        self.guest_cart_ttl
+    }
+
+    async fn cart_owner(&self, token: &str) -> Result<Option<String>, AppError> {
+        MongoRepo::cart_owner(self, token).await
+    }
+
+    async fn cart_access(&self, token: &str, shared: Option<&str>) -> Result<CartAccess, AppError> {
+        MongoRepo::cart_access(self, token, shared).await
+    }
+
+    async fn create_cart(&self, access: &CartAccess, new_cart: Cart) -> Result<Cart, AppError> {
+        MongoRepo::create_cart(self, access, new_cart).await
+    }
+
+    async fn list_all_carts_by_user(&self, access: &CartAccess) -> Result<Vec<Cart>, AppError> {
+        MongoRepo::list_all_carts_by_user(self, access).await
+    }
+
+    async fn list_cart_page(&self, access: &CartAccess, query: CartQuery) -> Result<CartPage, AppError> {
+        MongoRepo::list_cart_page(self, access, query).await
+    }
+
+    async fn update_cart(&self, access: &CartAccess, cart_data: UpdateCart, cart_id: String, if_match: Option<i64>) -> Result<Cart, CartWriteError> {
+        MongoRepo::update_cart(self, access, cart_data, cart_id, if_match).await
+    }
+
+    async fn delete_cart(&self, access: &CartAccess, cart_id: String, if_match: Option<i64>) -> Result<u64, CartWriteError> {
+        MongoRepo::delete_cart(self, access, cart_id, if_match).await.map(|deleted| deleted.modified_count)
+    }
+
+    async fn restore_cart(&self, access: &CartAccess, cart_id: String) -> Result<Cart, AppError> {
+        MongoRepo::restore_cart(self, access, cart_id).await
+    }
+
+    async fn clear_cart(&self, access: &CartAccess) -> Result<u64, AppError> {
+        MongoRepo::clear_cart(self, access).await
+    }
+
+    async fn delete_cart_items(&self, access: &CartAccess, filter: CartDeleteFilter) -> Result<u64, AppError> {
+        MongoRepo::delete_cart_items(self, access, filter).await
+    }
+
+    async fn finding_cart(&self, access: &CartAccess, cart_id: &ObjectId) -> Result<Option<Cart>, AppError> {
+        MongoRepo::finding_cart(self, access, cart_id).await
+    }
+
+    async fn revalidate_lines(&self, lines: &[Cart]) -> Result<Vec<CartNotice>, AppError> {
+        MongoRepo::revalidate_lines(self, lines).await
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MongoRepo {
+    async fn claim_idempotency_key(&self, key: &str, owner: &str, request: &str) -> Result<Option<IdempotencyRecord>, AppError> {
+        MongoRepo::claim_idempotency_key(self, key, owner, request).await
+    }
+
+    async fn complete_idempotency_key(&self, key: &str, owner: &str, status: u16, headers: BTreeMap<String, String>, body: String) -> Result<(), AppError> {
+        MongoRepo::complete_idempotency_key(self, key, owner, status, headers, body).await
+    }
+
+    async fn release_idempotency_key(&self, key: &str, owner: &str) -> Result<(), AppError> {
+        MongoRepo::release_idempotency_key(self, key, owner).await
+    }
+}
+
+////----------------------  END - MongoDB store ----------------------------- ////
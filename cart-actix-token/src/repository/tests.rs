@@ -0,0 +1,77 @@
+//unit tests of the repository rules that need no database
+
+use serde_json::json;
+
+use crate::model::product_model::ProductSchema;
+
+use super::product_repo::validate_product;
+
+//a shirt in two sizes, the changes swap in the parts a test is about
+fn product(changes: serde_json::Value) -> ProductSchema {
+    let mut data = json!({
+        "name": "Shirt",
+        "axes": [{"name": "size", "values": ["S", "M"]}],
+        "variants": [
+            {"sku": "shirt-s", "options": {"size": "S"}, "price": 10.0, "stock": 5.0},
+            {"sku": "shirt-m", "options": {"size": "M"}, "price": 10.0, "stock": 5.0}
+        ]
+    });
+
+    for (key, value) in changes.as_object().unwrap() {
+        data[key] = value.clone();
+    }
+
+    serde_json::from_value(data).unwrap()
+}
+
+#[test]
+fn product_with_one_variant_per_combination_is_valid() {
+    assert!(validate_product(&product(json!({}))).is_ok());
+}
+
+#[test]
+fn product_needs_a_name_and_a_variant() {
+    assert!(validate_product(&product(json!({"name": " "}))).is_err());
+    assert!(validate_product(&product(json!({"variants": []}))).is_err());
+}
+
+#[test]
+fn option_axes_need_values_and_distinct_names() {
+    assert!(validate_product(&product(json!({"axes": [{"name": "size", "values": []}]}))).is_err());
+
+    let axes = json!([{"name": "size", "values": ["S", "M"]}, {"name": "size", "values": ["L"]}]);
+    assert!(validate_product(&product(json!({"axes": axes}))).is_err());
+}
+
+#[test]
+fn variants_pick_one_known_value_of_every_axis() {
+    //a value the axis does not have
+    let variants = json!([{"sku": "shirt-xl", "options": {"size": "XL"}, "price": 10.0, "stock": 5.0}]);
+    assert!(validate_product(&product(json!({"variants": variants}))).is_err());
+
+    //no value for the axis
+    let variants = json!([{"sku": "shirt", "options": {}, "price": 10.0, "stock": 5.0}]);
+    assert!(validate_product(&product(json!({"variants": variants}))).is_err());
+
+    //an axis the product does not have
+    let variants = json!([{"sku": "shirt-s", "options": {"size": "S", "color": "red"}, "price": 10.0, "stock": 5.0}]);
+    assert!(validate_product(&product(json!({"variants": variants}))).is_err());
+}
+
+#[test]
+fn variants_have_their_own_sku_and_options() {
+    let variants = json!([
+        {"sku": "shirt", "options": {"size": "S"}, "price": 10.0, "stock": 5.0},
+        {"sku": "shirt", "options": {"size": "M"}, "price": 10.0, "stock": 5.0}
+    ]);
+    assert!(validate_product(&product(json!({"variants": variants}))).is_err());
+
+    let variants = json!([
+        {"sku": "shirt-a", "options": {"size": "S"}, "price": 10.0, "stock": 5.0},
+        {"sku": "shirt-b", "options": {"size": "S"}, "price": 10.0, "stock": 5.0}
+    ]);
+    assert!(validate_product(&product(json!({"variants": variants}))).is_err());
+
+    let variants = json!([{"sku": " ", "options": {"size": "S"}, "price": 10.0, "stock": 5.0}]);
+    assert!(validate_product(&product(json!({"variants": variants}))).is_err());
+}
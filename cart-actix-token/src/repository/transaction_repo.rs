@@ -0,0 +1,84 @@
+use futures::future::BoxFuture;
+use mongodb::{
+    ClientSession,
+    Collection,
+    bson::Document,
+    error::{TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT}};
+use serde::de::DeserializeOwned;
+
+use crate::error::AppError;
+
+use super::mongodb_repo::MongoRepo;
+
+//attempts of a transaction, and of its commit, before the error goes to the caller
+const TRANSACTION_ATTEMPTS: usize = 5;
+
+//reads one document, inside the transaction of the session when one is given
+pub(super) async fn find_one_on<T>(col: &Collection<T>, filter: Document, session: Option<&mut ClientSession>) -> Result<Option<T>, AppError>
+where
+    T: DeserializeOwned + Unpin + Send + Sync,
+{
+    let found = match session {
+        Some(session) => col.find_one_with_session(filter, None, session).await?,
+        None => col.find_one(filter, None).await?,
+    };
+
+    Ok(found)
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Transaction handler function ----------------------------- ////
+
+    //runs body in a transaction on a new session and commits it.
+    //A transient error runs body again from the start, an unknown commit result commits again.
+    //The body gets the repository and the context as arguments instead of capturing them,
+    //it may run more than once so it must not consume anything
+    pub async fn with_transaction<C, T, F>(&self, context: &C, mut body: F) -> Result<T, AppError>
+    where
+        C: Sync,
+        F: for<'s> FnMut(&'s MongoRepo, &'s mut ClientSession, &'s C) -> BoxFuture<'s, Result<T, AppError>>,
+    {
+        let mut session = self
+            .client
+            .start_session(None)
+            .await?;
+
+        let mut attempt = 0;
+
+        'transaction: loop {
+            attempt += 1;
+
+            session
+                .start_transaction(None)
+                .await?;
+
+            let value = match body(self, &mut session, context).await {
+                Ok(value) => value,
+                Err(error) => {
+                    let _ = session.abort_transaction().await;
+                    if matches!(error, AppError::Transient(_)) && attempt < TRANSACTION_ATTEMPTS {
+                        continue 'transaction;
+                    }
+                    return Err(error);
+                }
+            };
+
+            let mut commits = 0;
+
+            loop {
+                commits += 1;
+
+                match session.commit_transaction().await {
+                    Ok(()) => return Ok(value),
+                    Err(error) if error.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT) && commits < TRANSACTION_ATTEMPTS => continue,
+                    Err(error) if error.contains_label(TRANSIENT_TRANSACTION_ERROR) && attempt < TRANSACTION_ATTEMPTS => continue 'transaction,
+                    Err(error) => return Err(error.into()),
+                }
+            }
+        }
+    }
+
+    ////----------------------  END - Transaction handler function ----------------------------- ////
+
+}
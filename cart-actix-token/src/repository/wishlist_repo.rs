@@ -0,0 +1,338 @@
+use chrono::Utc;
+use futures::StreamExt;
+use mongodb::{
+    ClientSession,
+    bson::{doc, oid::ObjectId, to_bson},
+    options::{FindOneAndUpdateOptions, ReturnDocument},
+    results::DeleteResult};
+
+use crate::error::AppError;
+use crate::model::{history_model::{CartAction, CartEvent}, cart_model::Cart, wishlist_model::{SavedList, ListKind}};
+
+use super::{mongodb_repo::MongoRepo, soft_delete_repo::not_deleted};
+
+fn list_not_found() -> AppError {
+    AppError::NotFound("List Not found".to_owned())
+}
+
+impl MongoRepo {
+
+    ////----------------------  START - Wishlist handler function ----------------------------- ////
+
+    //handler to resolve the registered user behind a token, lists are not kept for guests
+    async fn list_owner(&self, token: &str) -> Result<String, AppError> {
+        match self.validate_user(token).await {
+            Ok(Some(x)) => Ok(x.id.unwrap().to_string()),
+            _ => Err(AppError::Unauthorized("Not found user".to_string())),
+        }
+    }
+
+    //handler to list the saved-for-later list and the wishlists of the user
+    pub async fn list_saved_lists(&self, token: &str) -> Result<Vec<SavedList>, AppError> {
+        let user_id = self.list_owner(token).await?;
+
+        let mut list_doc = self
+            .list_col
+            .find(doc! {"_uid": user_id}, None)
+            .await?;
+
+        let mut list_vec = Vec::new();
+
+        while let Some(doc) = list_doc.next().await {
+            match doc {
+                Ok(data) => list_vec.push(data),
+                Err(err) => eprintln!("Error finding list: {:?}", err),
+            }
+        }
+
+        Ok(list_vec)
+    }
+
+    //handler to create a named wishlist
+    pub async fn create_wishlist(&self, token: &str, name: String) -> Result<SavedList, AppError> {
+        let user_id = self.list_owner(token).await?;
+
+        let name = name.trim().to_owned();
+        if name.is_empty() {
+            return Err(AppError::Validation("Wishlist name is required".to_owned()));
+        }
+
+        let existing = self
+            .list_col
+            .find_one(doc! {"_uid": &user_id, "kind": ListKind::Wishlist.as_str(), "name": &name}, None)
+            .await?;
+
+        if existing.is_some() {
+            return Err(AppError::Conflict("Wishlist already exists".to_owned()));
+        }
+
+        let data = SavedList {
+            id: None,
+            user_id,
+            name,
+            kind: ListKind::Wishlist,
+            items: Vec::new(),
+            created_at: Some(Utc::now()),
+        };
+
+        let list_doc = self
+            .list_col
+            .insert_one(data.clone(), None)
+            .await?;
+
+        Ok(SavedList { id: list_doc.inserted_id.as_object_id(), ..data })
+    }
+
+    //handler to delete a wishlist with its items, the saved-for-later list stays
+    pub async fn delete_wishlist(&self, token: &str, list_id: String) -> Result<DeleteResult, AppError> {
+        let user_id = self.list_owner(token).await?;
+        let list_id = ObjectId::parse_str(list_id)?;
+
+        let deleted = self
+            .list_col
+            .delete_one(doc! {"_id": list_id, "_uid": user_id, "kind": ListKind::Wishlist.as_str()}, None)
+            .await?;
+
+        match deleted.deleted_count {
+            0 => Err(list_not_found()),
+            _ => Ok(deleted),
+        }
+    }
+
+    //handler to add an item straight to a list
+    pub async fn add_to_list(&self, token: &str, list_id: String, item: Cart) -> Result<SavedList, AppError> {
+        let user_id = self.list_owner(token).await?;
+        let list_id = ObjectId::parse_str(list_id)?;
+
+        let data = Cart {
+            id: Some(ObjectId::new()),
+            user_id: Some(user_id.to_owned()),
+            total: Some(item.price * item.qty),
+            created_at: Some(Utc::now()),
+            expires_at: None,
+            updated_at: None,
+            abandoned_at: None,
+            deleted_at: None,
+            ..item
+        };
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        self.list_col
+            .find_one_and_update(
+                doc! {"_id": list_id, "_uid": user_id},
+                doc! {"$push": {"items": to_bson(&data)?}},
+                options,
+            )
+            .await?
+            .ok_or_else(list_not_found)
+    }
+
+    //handler to remove an item from a list
+    pub async fn delete_list_item(&self, token: &str, list_id: String, item_id: String) -> Result<SavedList, AppError> {
+        let user_id = self.list_owner(token).await?;
+        let list_id = ObjectId::parse_str(list_id)?;
+        let item_id = ObjectId::parse_str(item_id)?;
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        self.list_col
+            .find_one_and_update(
+                doc! {"_id": list_id, "_uid": user_id, "items._id": item_id},
+                doc! {"$pull": {"items": {"_id": item_id}}},
+                options,
+            )
+            .await?
+            .ok_or_else(list_not_found)
+    }
+
+    //handler to move a cart item into a list, the saved-for-later list when no list is given
+    pub async fn move_to_list(&self, token: &str, cart_id: String, list_id: Option<String>) -> Result<SavedList, AppError> {
+        let user_id = self.list_owner(token).await?;
+        let cart_id = ObjectId::parse_str(cart_id)?;
+        let list_id = list_id.map(ObjectId::parse_str).transpose()?;
+
+        let list = self
+            .with_transaction(&(user_id.as_str(), cart_id, list_id), |repo, session, &(user_id, cart_id, list_id)| {
+                Box::pin(repo.move_item_to_list(session, user_id, cart_id, list_id))
+            })
+            .await?;
+
+        //the item left the cart, so does its stock hold
+        self.release_reservation(&cart_id).await?;
+        self.touch_cart(&user_id).await?;
+
+        Ok(list)
+    }
+
+    async fn move_item_to_list(&self, session: &mut ClientSession, user_id: &str, cart_id: ObjectId, list_id: Option<ObjectId>) -> Result<SavedList, AppError> {
+        let item = self
+            .cart_col
+            .find_one_and_delete_with_session(doc! {"_id": cart_id, "_uid": user_id, "deletedAt": not_deleted()}, None, session)
+            .await?
+            .ok_or(AppError::NotFound("Todo Not found".to_owned()))?;
+
+        self.record_cart_event_with_session(CartEvent::new(user_id, user_id, CartAction::Remove, Some(item.clone()), None), session).await?;
+
+        let item = Cart {
+            expires_at: None,
+            updated_at: None,
+            abandoned_at: None,
+            ..item
+        };
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .upsert(list_id.is_none())
+            .build();
+
+        //the saved-for-later list is created on first use
+        let (filter, set_on_insert) = match list_id {
+            Some(list_id) => (doc! {"_id": list_id, "_uid": user_id}, doc! {}),
+            None => (
+                doc! {"_uid": user_id, "kind": ListKind::SavedForLater.as_str()},
+                doc! {"name": "Saved for later", "createdAt": to_bson(&Utc::now())?},
+            ),
+        };
+
+        let mut update = doc! {"$push": {"items": to_bson(&item)?}};
+        if !set_on_insert.is_empty() {
+            update.insert("$setOnInsert", set_on_insert);
+        }
+
+        self.list_col
+            .find_one_and_update_with_session(filter, update, options, session)
+            .await?
+            .ok_or_else(list_not_found)
+    }
+
+    //handler to move a list item back into the cart at the current catalog price
+    pub async fn move_to_cart(&self, token: &str, list_id: String, item_id: String) -> Result<Cart, AppError> {
+        let user_id = self.list_owner(token).await?;
+        let list_id = ObjectId::parse_str(list_id)?;
+        let item_id = ObjectId::parse_str(item_id)?;
+
+        let list = self
+            .list_col
+            .find_one(doc! {"_id": list_id, "_uid": &user_id}, None)
+            .await?
+            .ok_or_else(list_not_found)?;
+
+        let item = list
+            .items
+            .into_iter()
+            .find(|item| item.id == Some(item_id))
+            .ok_or(AppError::NotFound("Item Not found".to_owned()))?;
+
+        //prices may have changed while the item was parked
+        let item = self.resolve_line(item).await?;
+
+        //an identical line already in the cart takes the quantity instead of a second line
+        let existing = self
+            .cart_col
+            .find_one(self.line_filter(&user_id, &item), None)
+            .await?;
+
+        let data = match &existing {
+            Some(line) => {
+                let qty = line.qty + item.qty;
+                self.adjust_reservation(line, qty).await?;
+
+                Cart {
+                    price: item.price,
+                    qty,
+                    total: Some(item.price * qty),
+                    updated_at: Some(Utc::now()),
+                    version: Some(line.version() + 1),
+                    ..line.clone()
+                }
+            },
+            None => {
+                match self.reservation_ttl {
+                    Some(_) => self.reserve_stock(item.stock_key(), item.qty).await?,
+                    None => self.check_stock(item.stock_key(), item.qty).await?,
+                }
+
+                Cart {
+                    user_id: Some(user_id.to_owned()),
+                    total: Some(item.price * item.qty),
+                    created_at: Some(Utc::now()),
+                    updated_at: Some(Utc::now()),
+                    version: Some(1),
+                    added_by: Some(user_id.to_owned()),
+                    ..item
+                }
+            },
+        };
+
+        let moved = self
+            .with_transaction(&(user_id.as_str(), list_id, item_id, &data, existing.as_ref()), |repo, session, &(user_id, list_id, item_id, data, existing)| {
+                Box::pin(repo.move_item_to_cart(session, user_id, list_id, item_id, data, existing))
+            })
+            .await;
+
+        match moved {
+            Ok(()) => {
+                if existing.is_none() {
+                    self.create_reservation(&user_id, item_id, data.stock_key(), data.qty).await?;
+                }
+                Ok(data)
+            },
+            Err(error) => {
+                match &existing {
+                    Some(line) => {
+                        let _ = self.adjust_reservation(line, line.qty).await;
+                    },
+                    None => {
+                        if self.reservation_ttl.is_some() {
+                            let _ = self.release_stock(data.stock_key(), data.qty).await;
+                        }
+                    }
+                }
+                Err(error)
+            }
+        }
+    }
+
+    async fn move_item_to_cart(&self, session: &mut ClientSession, user_id: &str, list_id: ObjectId, item_id: ObjectId, line: &Cart, existing: Option<&Cart>) -> Result<(), AppError> {
+        //the pull only matches while the item is still in the list, a concurrent move loses here
+        let pulled = self
+            .list_col
+            .update_one_with_session(
+                doc! {"_id": list_id, "items._id": item_id},
+                doc! {"$pull": {"items": {"_id": item_id}}},
+                None,
+                session,
+            )
+            .await?;
+
+        if pulled.modified_count == 0 {
+            return Err(AppError::NotFound("Item Not found".to_owned()));
+        }
+
+        if existing.is_some() {
+            self.cart_col
+                .update_one_with_session(
+                    doc! {"_id": line.id},
+                    doc! {"$set": {"qty": line.qty, "price": line.price, "_total": line.total}, "$inc": {"version": 1}},
+                    None,
+                    session,
+                )
+                .await?;
+        } else {
+            self.cart_col
+                .insert_one_with_session(line, None, session)
+                .await?;
+        }
+
+        self.touch_cart_with_session(user_id, session).await?;
+        self.record_cart_event_with_session(CartEvent::new(user_id, user_id, CartAction::Add, existing.cloned(), Some(line.clone())), session).await
+    }
+
+    ////----------------------  END - Wishlist handler function ----------------------------- ////
+
+}